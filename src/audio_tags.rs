@@ -21,8 +21,11 @@
 
 //! The tags extracted from an audio file.
 
-use sndfile::{SndFile, Title, Copyright, Software, Artist, Comment, Date,
-              Album, License, TrackNumber, Genre};
+use std::ascii::AsciiExt;
+
+use sndfile::{SndFile, StringSoundType, Title, Copyright, Software, Artist,
+              Comment, Date, Album, License, TrackNumber, Genre, ReadWrite,
+              NoError};
 
 /**
  * Structure containing the tags of a sound.
@@ -83,8 +86,164 @@ pub fn get_sound_tags(file: &SndFile) -> Tags {
     }
 }
 
+/**
+ * Set a field of the tags from a Vorbis comment.
+ *
+ * The names are the ones of the Vorbis comments, like TITLE or ARTIST, the
+ * case is ignored. The unknown names are ignored.
+ *
+ * # Arguments
+ * * `tags` - The tags to modify
+ * * `name` - The name of the field
+ * * `value` - The value of the field
+ */
+pub fn set_comment(tags: &mut Tags, name: &str, value: &str) -> () {
+    let field = match name.to_ascii_upper().as_slice() {
+        "TITLE"                     => &mut tags.title,
+        "COPYRIGHT"                 => &mut tags.copyright,
+        "ENCODER"                   => &mut tags.software,
+        "ARTIST"                    => &mut tags.artist,
+        "COMMENT" | "DESCRIPTION"   => &mut tags.comment,
+        "DATE"                      => &mut tags.date,
+        "ALBUM"                     => &mut tags.album,
+        "LICENSE"                   => &mut tags.license,
+        "TRACKNUMBER"               => &mut tags.track_number,
+        "GENRE"                     => &mut tags.genre,
+        _                           => return
+    };
+    *field = value.to_string();
+}
+
+/// Get the non empty fields of the tags with their type.
+fn get_fields<'a>(tags: &'a Tags) -> Vec<(StringSoundType, &'a String)> {
+    let fields = vec![(Title, &tags.title),
+                      (Copyright, &tags.copyright),
+                      (Software, &tags.software),
+                      (Artist, &tags.artist),
+                      (Comment, &tags.comment),
+                      (Date, &tags.date),
+                      (Album, &tags.album),
+                      (License, &tags.license),
+                      (TrackNumber, &tags.track_number),
+                      (Genre, &tags.genre)];
+    fields.into_iter().filter(|&(_, value)| !value.is_empty()).collect()
+}
+
+/**
+ * Set the tags on an open file.
+ *
+ * The empty fields are not set.
+ *
+ * # Return
+ * The fields the file was able to store.
+ */
+pub fn set_sound_tags(file: &mut SndFile, tags: &Tags) -> Vec<StringSoundType> {
+    let mut stored = Vec::new();
+    for (string_type, value) in get_fields(tags).into_iter() {
+        match file.set_string(string_type, value.clone()) {
+            NoError => stored.push(string_type),
+            _       => {}
+        }
+    }
+    stored
+}
+
+/**
+ * Write tags in an audio file.
+ *
+ * The empty fields of the tags are not written. The formats which can't be
+ * opened in read/write mode by libsndfile, like FLAC or Ogg, can't be
+ * tagged this way: stamp them when they are created instead.
+ *
+ * # Arguments
+ * * `path` - The path of the file to tag
+ * * `tags` - The tags to write
+ *
+ * # Return
+ * Ok with the fields the container can store, the other fields are not
+ * written. Err with a description of the error if the file can't be
+ * opened.
+ *
+ * # Example
+ * ```Rust
+ * let mut tags = ears::Sound::new("path/to/sound.wav").unwrap().get_tags();
+ * tags.software = "my asset pipeline".to_string();
+ *
+ * match ears::write_tags("path/to/sound.wav", &tags) {
+ *     Ok(stored) => println!("Stored tags: {}", stored),
+ *     Err(err)   => println!("{}", err)
+ * }
+ * ```
+ */
+pub fn write_tags(path: &str, tags: &Tags) -> Result<Vec<StringSoundType>, String> {
+    let mut file = match SndFile::new(path, ReadWrite) {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+    let stored = set_sound_tags(&mut file, tags);
+    match file.close() {
+        NoError => Ok(stored),
+        error   => Err(SndFile::error_number(error))
+    }
+}
+
 /// AudioTags trait implemented by all struct who can provides audio.
 pub trait AudioTags{
     /// Get the tags of the audio source.
     fn get_tags(&self) -> Tags;
 }
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::TempDir;
+
+    use audio_tags::{empty, write_tags, get_sound_tags, set_comment};
+    use io::{SoundReader, SoundWriter, FormatWav, FormatPcm16};
+    use sndfile::{SndFile, Read, Title, Artist};
+
+    #[test]
+    fn audio_tags_write_tags_OK() -> () {
+        let mut info = SoundReader::open("res/shot.wav").unwrap().get_info();
+        info.channels = 1;
+        info.format = (FormatWav | FormatPcm16) as i32;
+        let dir = TempDir::new("ears_tags").unwrap();
+        let path = dir.path().join("test.wav");
+        let path = path.as_str().unwrap();
+        let mut writer = SoundWriter::create(path, info).unwrap();
+        writer.write([0i16, 1, 2, 3].as_slice()).unwrap();
+        writer.finish().unwrap();
+
+        let mut tags = empty();
+        tags.title = "Shot".to_string();
+        tags.artist = "ears".to_string();
+
+        let stored = write_tags(path, &tags).unwrap();
+        assert_eq!(stored, vec![Title, Artist]);
+
+        let file = SndFile::new(path, Read).unwrap();
+        let read = get_sound_tags(&file);
+        assert_eq!(read.title.as_slice(), "Shot");
+        assert_eq!(read.artist.as_slice(), "ears");
+    }
+
+    #[test]
+    fn audio_tags_write_tags_FAIL() -> () {
+        match write_tags("toto.wav", &empty()) {
+            Ok(_)  => panic!(),
+            Err(_) => {}
+        }
+    }
+
+    #[test]
+    fn audio_tags_set_comment_OK() -> () {
+        let mut tags = empty();
+        set_comment(&mut tags, "Title", "Shot");
+        set_comment(&mut tags, "TRACKNUMBER", "3");
+        set_comment(&mut tags, "REPLAYGAIN_TRACK_GAIN", "-3 dB");
+        assert_eq!(tags.title.as_slice(), "Shot");
+        assert_eq!(tags.track_number.as_slice(), "3");
+        assert_eq!(tags.comment.as_slice(), "");
+    }
+}
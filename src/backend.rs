@@ -0,0 +1,502 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+/*!
+ * Internal interface between ears and the audio implementation.
+ *
+ * The wrappers of the openal::al module call the backend of the process
+ * instead of the OpenAL library, so the Sounds, the Musics and the Recorders
+ * can run on another implementation. The backend uses the names and the
+ * enums of OpenAL: sources and buffers are identified by u32 names, and the
+ * parameters are the AL_* constants.
+ *
+ * The backend is global, the streaming and recording tasks use the same one
+ * as the task which created the context. The OpenAL backend is used unless
+ * another one, like the SoftwareMixer, is set before the first use of ears.
+ */
+
+#![allow(raw_pointer_deriving)]
+
+use std::{mem, ptr};
+use std::sync::{Once, ONCE_INIT};
+use libc::c_void;
+
+use openal::ffi;
+
+type GetInteger64vSOFT = extern "C" fn(*mut ffi::ALCdevice, i32, i32, *mut i64);
+type SourcePlayAtTimeSOFT = extern "C" fn(u32, i64);
+
+/**
+ * The functions an audio implementation must provide.
+ *
+ * The pointers follow the conventions of the OpenAL functions of the same
+ * name.
+ */
+pub trait AudioBackend {
+    /// Get the name of the backend.
+    fn get_name(&self) -> &'static str;
+
+    /// Check if the backend needs an OpenAL context.
+    fn needs_context(&self) -> bool;
+
+    /// Check if an AL extension is supported.
+    fn is_extension_present(&self, extension: &str) -> bool;
+
+    /// Get the time of the audio clock in nanoseconds, None without clock.
+    fn get_clock(&self) -> Option<i64>;
+
+    /// Start a source at a time of the audio clock, needs the
+    /// AL_SOFT_source_start_delay extension.
+    fn source_play_at_time(&self, source: u32, time: i64) -> ();
+
+    /// Create `n` buffers.
+    fn gen_buffers(&self, n: i32, buffers: *mut u32) -> ();
+    /// Delete `n` buffers.
+    fn delete_buffers(&self, n: i32, buffers: *mut u32) -> ();
+    /// Fill a buffer with samples.
+    fn buffer_data(&self, buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> ();
+    /// Get an integer property of a buffer.
+    fn get_bufferi(&self, buffer: u32, param: i32, value: *mut i32) -> ();
+
+    /// Create `n` sources.
+    fn gen_sources(&self, n: i32, sources: *mut u32) -> ();
+    /// Delete `n` sources.
+    fn delete_sources(&self, n: i32, sources: *mut u32) -> ();
+    /// Set an integer property of a source.
+    fn sourcei(&self, source: u32, param: i32, value: i32) -> ();
+    /// Set a float property of a source.
+    fn sourcef(&self, source: u32, param: i32, value: f32) -> ();
+    /// Set a vector property of a source.
+    fn sourcefv(&self, source: u32, param: i32, value: *const f32) -> ();
+    /// Get an integer property of a source.
+    fn get_sourcei(&self, source: u32, param: i32, value: *mut i32) -> ();
+    /// Get a float property of a source.
+    fn get_sourcef(&self, source: u32, param: i32, value: *mut f32) -> ();
+    /// Get a vector property of a source.
+    fn get_sourcefv(&self, source: u32, param: i32, value: *mut f32) -> ();
+    /// Play a source.
+    fn source_play(&self, source: u32) -> ();
+    /// Pause a source.
+    fn source_pause(&self, source: u32) -> ();
+    /// Stop a source.
+    fn source_stop(&self, source: u32) -> ();
+    /// Rewind a source.
+    fn source_rewind(&self, source: u32) -> ();
+    /// Queue buffers at the end of the queue of a source.
+    fn source_queue_buffers(&self, source: u32, nb: i32, buffers: *const u32) -> ();
+    /// Remove played buffers from the queue of a source.
+    fn source_unqueue_buffers(&self, source: u32, nb: i32, buffers: *mut u32) -> ();
+
+    /// Set a float property of the listener.
+    fn listenerf(&self, param: i32, value: f32) -> ();
+    /// Set a vector property of the listener.
+    fn listener3f(&self, param: i32, value1: f32, value2: f32, value3: f32) -> ();
+    /// Set a vector property of the listener.
+    fn listenerfv(&self, param: i32, values: *const f32) -> ();
+    /// Get a float property of the listener.
+    fn get_listenerf(&self, param: i32, value: *mut f32) -> ();
+    /// Get a vector property of the listener.
+    fn get_listener3f(&self, param: i32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> ();
+    /// Get a vector property of the listener.
+    fn get_listenerfv(&self, param: i32, values: *mut f32) -> ();
+
+    /// Open the default capture device, null on failure.
+    fn capture_open(&self, samplerate: i32, format: i32, buffer_size: i32) -> *mut ffi::ALCdevice;
+    /// Close a capture device.
+    fn capture_close(&self, device: *mut ffi::ALCdevice) -> ();
+    /// Start the capture.
+    fn capture_start(&self, device: *mut ffi::ALCdevice) -> ();
+    /// Stop the capture.
+    fn capture_stop(&self, device: *mut ffi::ALCdevice) -> ();
+    /// Get the count of captured samples.
+    fn capture_available(&self, device: *mut ffi::ALCdevice) -> i32;
+    /// Read captured samples.
+    fn capture_samples(&self, device: *mut ffi::ALCdevice, buffer: *mut c_void, samples: i32) -> ();
+
+    /// Get and clear the last error, AL_NO_ERROR if none.
+    fn get_error(&self) -> i32;
+}
+
+/// The backend calling the OpenAL library.
+pub struct OpenAlBackend;
+
+impl AudioBackend for OpenAlBackend {
+    fn get_name(&self) -> &'static str {
+        "OpenAL"
+    }
+
+    fn needs_context(&self) -> bool {
+        true
+    }
+
+    fn is_extension_present(&self, extension: &str) -> bool {
+        extension.with_c_str(|c_str| unsafe {
+            ffi::alIsExtensionPresent(c_str)
+        }) == ffi::ALC_TRUE
+    }
+
+    fn get_clock(&self) -> Option<i64> {
+        let device = unsafe { ffi::alcGetContextsDevice(ffi::alcGetCurrentContext()) };
+        if device.is_null() || "ALC_SOFT_device_clock".with_c_str(|c_str| unsafe {
+            ffi::alcIsExtensionPresent(device, c_str)
+        }) == ffi::ALC_FALSE {
+            return None;
+        }
+        let func = "alcGetInteger64vSOFT".with_c_str(|c_str| unsafe {
+            ffi::alcGetProcAddress(device, c_str)
+        });
+        if func.is_null() {
+            return None;
+        }
+        let get_integer: GetInteger64vSOFT = unsafe { mem::transmute(func) };
+        let mut value = 0i64;
+        get_integer(device, ffi::ALC_DEVICE_CLOCK_SOFT, 1, &mut value);
+        Some(value)
+    }
+
+    fn source_play_at_time(&self, source: u32, time: i64) -> () {
+        let func = "alSourcePlayAtTimeSOFT".with_c_str(|c_str| unsafe {
+            ffi::alGetProcAddress(c_str)
+        });
+        if func.is_null() {
+            self.source_play(source);
+        } else {
+            let play_at_time: SourcePlayAtTimeSOFT = unsafe { mem::transmute(func) };
+            play_at_time(source, time);
+        }
+    }
+
+    fn gen_buffers(&self, n: i32, buffers: *mut u32) -> () {
+        unsafe { ffi::alGenBuffers(n, buffers); }
+    }
+
+    fn delete_buffers(&self, n: i32, buffers: *mut u32) -> () {
+        unsafe { ffi::alDeleteBuffers(n, buffers); }
+    }
+
+    fn buffer_data(&self, buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> () {
+        unsafe { ffi::alBufferData(buffer, format, data, size, freq); }
+    }
+
+    fn get_bufferi(&self, buffer: u32, param: i32, value: *mut i32) -> () {
+        unsafe { ffi::alGetBufferi(buffer, param, value); }
+    }
+
+    fn gen_sources(&self, n: i32, sources: *mut u32) -> () {
+        unsafe { ffi::alGenSources(n, sources); }
+    }
+
+    fn delete_sources(&self, n: i32, sources: *mut u32) -> () {
+        unsafe { ffi::alDeleteSources(n, sources); }
+    }
+
+    fn sourcei(&self, source: u32, param: i32, value: i32) -> () {
+        unsafe { ffi::alSourcei(source, param, value); }
+    }
+
+    fn sourcef(&self, source: u32, param: i32, value: f32) -> () {
+        unsafe { ffi::alSourcef(source, param, value); }
+    }
+
+    fn sourcefv(&self, source: u32, param: i32, value: *const f32) -> () {
+        unsafe { ffi::alSourcefv(source, param, value); }
+    }
+
+    fn get_sourcei(&self, source: u32, param: i32, value: *mut i32) -> () {
+        unsafe { ffi::alGetSourcei(source, param, value); }
+    }
+
+    fn get_sourcef(&self, source: u32, param: i32, value: *mut f32) -> () {
+        unsafe { ffi::alGetSourcef(source, param, value); }
+    }
+
+    fn get_sourcefv(&self, source: u32, param: i32, value: *mut f32) -> () {
+        unsafe { ffi::alGetSourcefv(source, param, value); }
+    }
+
+    fn source_play(&self, source: u32) -> () {
+        unsafe { ffi::alSourcePlay(source); }
+    }
+
+    fn source_pause(&self, source: u32) -> () {
+        unsafe { ffi::alSourcePause(source); }
+    }
+
+    fn source_stop(&self, source: u32) -> () {
+        unsafe { ffi::alSourceStop(source); }
+    }
+
+    fn source_rewind(&self, source: u32) -> () {
+        unsafe { ffi::alSourceRewind(source); }
+    }
+
+    fn source_queue_buffers(&self, source: u32, nb: i32, buffers: *const u32) -> () {
+        unsafe { ffi::alSourceQueueBuffers(source, nb, buffers); }
+    }
+
+    fn source_unqueue_buffers(&self, source: u32, nb: i32, buffers: *mut u32) -> () {
+        unsafe { ffi::alSourceUnqueueBuffers(source, nb, buffers); }
+    }
+
+    fn listenerf(&self, param: i32, value: f32) -> () {
+        unsafe { ffi::alListenerf(param, value); }
+    }
+
+    fn listener3f(&self, param: i32, value1: f32, value2: f32, value3: f32) -> () {
+        unsafe { ffi::alListener3f(param, value1, value2, value3); }
+    }
+
+    fn listenerfv(&self, param: i32, values: *const f32) -> () {
+        unsafe { ffi::alListenerfv(param, values); }
+    }
+
+    fn get_listenerf(&self, param: i32, value: *mut f32) -> () {
+        unsafe { ffi::alGetListenerf(param, value); }
+    }
+
+    fn get_listener3f(&self, param: i32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> () {
+        unsafe { ffi::alGetListener3f(param, value1, value2, value3); }
+    }
+
+    fn get_listenerfv(&self, param: i32, values: *mut f32) -> () {
+        unsafe { ffi::alGetListenerfv(param, values); }
+    }
+
+    fn capture_open(&self, samplerate: i32, format: i32, buffer_size: i32) -> *mut ffi::ALCdevice {
+        unsafe { ffi::alcCaptureOpenDevice(ptr::null_mut(), samplerate, format, buffer_size) }
+    }
+
+    fn capture_close(&self, device: *mut ffi::ALCdevice) -> () {
+        unsafe { ffi::alcCaptureCloseDevice(device); }
+    }
+
+    fn capture_start(&self, device: *mut ffi::ALCdevice) -> () {
+        unsafe { ffi::alcCaptureStart(device); }
+    }
+
+    fn capture_stop(&self, device: *mut ffi::ALCdevice) -> () {
+        unsafe { ffi::alcCaptureStop(device); }
+    }
+
+    fn capture_available(&self, device: *mut ffi::ALCdevice) -> i32 {
+        let mut available = 0;
+        unsafe { ffi::alcGetIntegerv(device, ffi::ALC_CAPTURE_SAMPLES, 1, &mut available); }
+        available
+    }
+
+    fn capture_samples(&self, device: *mut ffi::ALCdevice, buffer: *mut c_void, samples: i32) -> () {
+        unsafe { ffi::alcCaptureSamples(device, buffer, samples); }
+    }
+
+    fn get_error(&self) -> i32 {
+        unsafe { ffi::alGetError() }
+    }
+}
+
+static mut BACKEND: *const Box<AudioBackend + Send + Sync> =
+    0 as *const Box<AudioBackend + Send + Sync>;
+static INIT: Once = ONCE_INIT;
+
+local_data_key!(task_backend: &'static (AudioBackend + Send + Sync))
+
+/**
+ * Set the backend of the process.
+ *
+ * The backend can only be set once, before the first use of ears.
+ *
+ * # Argument
+ * * `backend` - The backend to use
+ *
+ * # Return
+ * true if the backend is set, false if a backend is already in use.
+ */
+pub fn set_backend(backend: Box<AudioBackend + Send + Sync>) -> bool {
+    let mut backend = Some(backend);
+    INIT.doit(|| unsafe {
+        BACKEND = mem::transmute(box backend.take().unwrap());
+    });
+    backend.is_none()
+}
+
+/**
+ * Set the backend of the current task only.
+ *
+ * Used by the tests to run on their own backend while the other tests use
+ * the backend of the process. The tasks spawned by ears, like the streaming
+ * tasks, still use the backend of the process. The backend is never freed.
+ *
+ * # Argument
+ * * `backend` - The backend to use in the current task
+ */
+#[doc(hidden)]
+pub fn set_task_backend(backend: Box<AudioBackend + Send + Sync>) -> () {
+    let backend: &'static Box<AudioBackend + Send + Sync> = unsafe {
+        mem::transmute(box backend)
+    };
+    task_backend.replace(Some(&**backend));
+}
+
+/**
+ * Get the backend of the process.
+ *
+ * Set the OpenAL backend if no backend is set yet. The backend of the task
+ * is used instead if one was set with set_task_backend.
+ */
+pub fn get() -> &'static (AudioBackend + Send + Sync) {
+    match task_backend.get() {
+        Some(backend) => return *backend,
+        None          => {}
+    }
+    INIT.doit(|| unsafe {
+        let backend: Box<AudioBackend + Send + Sync> = box OpenAlBackend;
+        BACKEND = mem::transmute(box backend);
+    });
+    unsafe { &**BACKEND }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::sync::{Arc, Mutex};
+    use libc::c_void;
+
+    use backend;
+    use backend::{AudioBackend, OpenAlBackend};
+    use openal::{ffi, al};
+
+    /// A backend which records the names of the called functions.
+    struct RecordingBackend {
+        calls: Arc<Mutex<Vec<String>>>
+    }
+
+    impl RecordingBackend {
+        fn record(&self, call: &str) -> () {
+            self.calls.lock().push(call.to_string());
+        }
+    }
+
+    impl AudioBackend for RecordingBackend {
+        fn get_name(&self) -> &'static str { "Recording" }
+        fn needs_context(&self) -> bool { false }
+        fn is_extension_present(&self, _: &str) -> bool { false }
+        fn get_clock(&self) -> Option<i64> { None }
+        fn source_play_at_time(&self, _: u32, _: i64) -> () {
+            self.record("source_play_at_time")
+        }
+        fn gen_buffers(&self, _: i32, _: *mut u32) -> () { self.record("gen_buffers") }
+        fn delete_buffers(&self, _: i32, _: *mut u32) -> () { self.record("delete_buffers") }
+        fn buffer_data(&self, _: u32, _: i32, _: *mut c_void, _: i32, _: i32) -> () {
+            self.record("buffer_data")
+        }
+        fn get_bufferi(&self, _: u32, _: i32, _: *mut i32) -> () { self.record("get_bufferi") }
+        fn gen_sources(&self, n: i32, sources: *mut u32) -> () {
+            for i in range(0, n as int) {
+                unsafe { *sources.offset(i) = 7; }
+            }
+            self.record("gen_sources")
+        }
+        fn delete_sources(&self, _: i32, _: *mut u32) -> () { self.record("delete_sources") }
+        fn sourcei(&self, _: u32, _: i32, _: i32) -> () { self.record("sourcei") }
+        fn sourcef(&self, _: u32, _: i32, _: f32) -> () { self.record("sourcef") }
+        fn sourcefv(&self, _: u32, _: i32, _: *const f32) -> () { self.record("sourcefv") }
+        fn get_sourcei(&self, _: u32, _: i32, value: *mut i32) -> () {
+            unsafe { *value = ffi::AL_PLAYING; }
+            self.record("get_sourcei")
+        }
+        fn get_sourcef(&self, _: u32, _: i32, _: *mut f32) -> () { self.record("get_sourcef") }
+        fn get_sourcefv(&self, _: u32, _: i32, _: *mut f32) -> () { self.record("get_sourcefv") }
+        fn source_play(&self, _: u32) -> () { self.record("source_play") }
+        fn source_pause(&self, _: u32) -> () { self.record("source_pause") }
+        fn source_stop(&self, _: u32) -> () { self.record("source_stop") }
+        fn source_rewind(&self, _: u32) -> () { self.record("source_rewind") }
+        fn source_queue_buffers(&self, _: u32, _: i32, _: *const u32) -> () {
+            self.record("source_queue_buffers")
+        }
+        fn source_unqueue_buffers(&self, _: u32, _: i32, _: *mut u32) -> () {
+            self.record("source_unqueue_buffers")
+        }
+        fn listenerf(&self, _: i32, _: f32) -> () { self.record("listenerf") }
+        fn listener3f(&self, _: i32, _: f32, _: f32, _: f32) -> () { self.record("listener3f") }
+        fn listenerfv(&self, _: i32, _: *const f32) -> () { self.record("listenerfv") }
+        fn get_listenerf(&self, _: i32, _: *mut f32) -> () { self.record("get_listenerf") }
+        fn get_listener3f(&self, _: i32, _: *mut f32, _: *mut f32, _: *mut f32) -> () {
+            self.record("get_listener3f")
+        }
+        fn get_listenerfv(&self, _: i32, _: *mut f32) -> () { self.record("get_listenerfv") }
+        fn capture_open(&self, _: i32, _: i32, _: i32) -> *mut ffi::ALCdevice {
+            self.record("capture_open");
+            0 as *mut ffi::ALCdevice
+        }
+        fn capture_close(&self, _: *mut ffi::ALCdevice) -> () { self.record("capture_close") }
+        fn capture_start(&self, _: *mut ffi::ALCdevice) -> () { self.record("capture_start") }
+        fn capture_stop(&self, _: *mut ffi::ALCdevice) -> () { self.record("capture_stop") }
+        fn capture_available(&self, _: *mut ffi::ALCdevice) -> i32 {
+            self.record("capture_available");
+            0
+        }
+        fn capture_samples(&self, _: *mut ffi::ALCdevice, _: *mut c_void, _: i32) -> () {
+            self.record("capture_samples")
+        }
+        fn get_error(&self) -> i32 {
+            self.record("get_error");
+            ffi::AL_NO_ERROR
+        }
+    }
+
+    #[test]
+    fn backend_default_openal_OK() -> () {
+        assert_eq!(backend::get().get_name(), "OpenAL");
+    }
+
+    #[test]
+    fn backend_set_after_use_FAIL() -> () {
+        backend::get();
+        assert_eq!(backend::set_backend(box OpenAlBackend), false);
+    }
+
+    #[test]
+    fn backend_al_calls_reach_backend_OK() -> () {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        backend::set_task_backend(box RecordingBackend { calls: calls.clone() });
+        assert_eq!(backend::get().get_name(), "Recording");
+
+        let mut source = 0;
+        al::alGenSources(1, &mut source);
+        assert_eq!(source, 7);
+        al::alSourcePlay(source);
+        al::alSourcePlayAtTimeSOFT(source, 1000);
+        assert_eq!(al::alGetState(source), ffi::AL_PLAYING);
+        let (mut x, mut y, mut z) = (0f32, 0f32, 0f32);
+        al::alGetListener3f(ffi::AL_POSITION, &mut x, &mut y, &mut z);
+        al::alDeleteSources(1, &mut source);
+        al::alcCaptureStop(0 as *mut ffi::ALCdevice);
+        assert!(al::openal_has_error().is_none());
+
+        let expected = ["gen_sources", "source_play", "source_play_at_time", "get_sourcei",
+                        "get_listener3f", "delete_sources", "capture_stop", "get_error"];
+        let calls = calls.lock();
+        assert_eq!(calls.len(), expected.len());
+        for (call, name) in calls.iter().zip(expected.iter()) {
+            assert_eq!(call.as_slice(), *name);
+        }
+    }
+}
@@ -0,0 +1,567 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Mixer buses to control groups of Sounds and Musics.
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+
+/**
+ * A mixer bus.
+ *
+ * A Bus control the volume, the pitch and the playback of a group of Sounds
+ * and Musics. Buses can be nested, the volume and the pitch of a Bus are
+ * multiplied by the ones of its parents.
+ *
+ * The volume of a Sound or a Music assigned to a Bus keep its meaning: it's
+ * the gain of the source inside the Bus.
+ *
+ * A Bus is a shared handle, cloning it gives another handle on the same Bus.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use ears::{Bus, Sound, Music, AudioController};
+ *
+ * fn main() -> () {
+ *     let master = Bus::new();
+ *     let sfx = Bus::new_child(&master);
+ *     let music = Bus::new_child(&master);
+ *
+ *     let mut snd = Sound::new("path/to/my/sound.wav").unwrap();
+ *     snd.set_bus(&sfx);
+ *     let mut msc = Music::new("path/to/my/music.ogg").unwrap();
+ *     msc.set_bus(&music);
+ *
+ *     master.set_volume(0.8);
+ *     music.set_volume(0.5);
+ *     snd.play();
+ *     msc.play();
+ * }
+ * ```
+ */
+#[deriving(Clone)]
+pub struct Bus {
+    inner: Rc<RefCell<BusData>>
+}
+
+/// A source assigned to a Bus.
+struct Member {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The volume of the source inside the Bus
+    volume: f32,
+    /// The pitch of the source inside the Bus
+    pitch: f32,
+    /// Was the source paused by the Bus
+    paused: bool
+}
+
+struct BusData {
+    volume: f32,
+    pitch: f32,
+    muted: bool,
+    solo: bool,
+    paused: bool,
+    duck_volume: f32,
+    parent: Option<Bus>,
+    children: Vec<Weak<RefCell<BusData>>>,
+    members: Vec<Member>
+}
+
+impl Bus {
+    /**
+     * Create a new root Bus.
+     *
+     * # Return
+     * A new Bus with a volume and a pitch of 1.
+     */
+    pub fn new() -> Bus {
+        Bus {
+            inner: Rc::new(RefCell::new(BusData {
+                volume: 1.,
+                pitch: 1.,
+                muted: false,
+                solo: false,
+                paused: false,
+                duck_volume: 1.,
+                parent: None,
+                children: Vec::new(),
+                members: Vec::new()
+            }))
+        }
+    }
+
+    /**
+     * Create a new Bus inside another one.
+     *
+     * # Argument
+     * * `parent` - The parent Bus
+     *
+     * # Return
+     * A new Bus, child of `parent`.
+     */
+    pub fn new_child(parent: &Bus) -> Bus {
+        let bus = Bus::new();
+        bus.inner.borrow_mut().parent = Some(parent.clone());
+
+        let mut parent_data = parent.inner.borrow_mut();
+        parent_data.children.retain(|c| c.upgrade().is_some());
+        parent_data.children.push(bus.inner.downgrade());
+        bus
+    }
+
+    /// Get the parent of the Bus, if any.
+    pub fn get_parent(&self) -> Option<Bus> {
+        self.inner.borrow().parent.clone()
+    }
+
+    /**
+     * Set the volume of the Bus.
+     *
+     * # Argument
+     * * `volume` - The volume of the Bus, should be between 0. and 1.
+     */
+    pub fn set_volume(&self, volume: f32) -> () {
+        if volume < 0. {
+            println!("Error : the volume of a Bus cannot be negative.");
+            return;
+        }
+        self.inner.borrow_mut().volume = volume;
+        self.root().apply();
+    }
+
+    /**
+     * Get the volume of the Bus.
+     *
+     * # Return
+     * The volume of the Bus, without the volume of its parents.
+     */
+    pub fn get_volume(&self) -> f32 {
+        self.inner.borrow().volume
+    }
+
+    /**
+     * Get the volume really applied to the members of the Bus.
+     *
+     * # Return
+     * The volume of the Bus multiplied by the volume of its parents, 0 if the
+     * Bus is muted or silenced by a solo Bus.
+     */
+    pub fn get_effective_volume(&self) -> f32 {
+        if self.is_silenced_by_solo() {
+            0.
+        } else {
+            self.chain_volume()
+        }
+    }
+
+    /**
+     * Set the pitch of the Bus.
+     *
+     * # Argument
+     * * `pitch` - The pitch of the Bus in the range [0.5 - 2.0]
+     */
+    pub fn set_pitch(&self, pitch: f32) -> () {
+        if pitch <= 0. {
+            println!("Error : the pitch of a Bus must be positive.");
+            return;
+        }
+        self.inner.borrow_mut().pitch = pitch;
+        self.apply();
+    }
+
+    /**
+     * Get the pitch of the Bus.
+     *
+     * # Return
+     * The pitch of the Bus, without the pitch of its parents.
+     */
+    pub fn get_pitch(&self) -> f32 {
+        self.inner.borrow().pitch
+    }
+
+    /**
+     * Get the pitch really applied to the members of the Bus.
+     *
+     * # Return
+     * The pitch of the Bus multiplied by the pitch of its parents.
+     */
+    pub fn get_effective_pitch(&self) -> f32 {
+        let data = self.inner.borrow();
+        match data.parent {
+            Some(ref parent) => data.pitch * parent.get_effective_pitch(),
+            None             => data.pitch
+        }
+    }
+
+    /**
+     * Mute or unmute the Bus.
+     *
+     * # Argument
+     * * `muted` - true to mute the Bus, false to unmute it
+     */
+    pub fn set_muted(&self, muted: bool) -> () {
+        self.inner.borrow_mut().muted = muted;
+        self.apply();
+    }
+
+    /// Is the Bus muted or not ?
+    pub fn is_muted(&self) -> bool {
+        self.inner.borrow().muted
+    }
+
+    /**
+     * Solo or unsolo the Bus.
+     *
+     * When a Bus is solo, all the Buses of the same hierarchy which are not
+     * solo, or parent or child of a solo Bus, are silenced.
+     *
+     * # Argument
+     * * `solo` - true to solo the Bus, false otherwise
+     */
+    pub fn set_solo(&self, solo: bool) -> () {
+        self.inner.borrow_mut().solo = solo;
+        self.root().apply();
+    }
+
+    /// Is the Bus solo or not ?
+    pub fn is_solo(&self) -> bool {
+        self.inner.borrow().solo
+    }
+
+    /**
+     * Pause all the playing sources of the Bus and of its children.
+     *
+     * The sources paused by the Bus are resumed by `resume`. A playing source
+     * assigned to the Bus while it's paused is paused too.
+     */
+    pub fn pause(&self) -> () {
+        check_openal_context!(());
+
+        {
+            let mut data = self.inner.borrow_mut();
+            data.paused = true;
+            for member in data.members.iter_mut() {
+                if al::alGetState(member.al_source) == ffi::AL_PLAYING {
+                    al::alSourcePause(member.al_source);
+                    member.paused = true;
+                }
+            }
+        }
+        for child in self.children().iter() {
+            child.pause();
+        }
+    }
+
+    /// Resume all the sources paused by the Bus and by its children.
+    pub fn resume(&self) -> () {
+        check_openal_context!(());
+
+        {
+            let mut data = self.inner.borrow_mut();
+            data.paused = false;
+            for member in data.members.iter_mut() {
+                if member.paused {
+                    if al::alGetState(member.al_source) == ffi::AL_PAUSED {
+                        al::alSourcePlay(member.al_source);
+                    }
+                    member.paused = false;
+                }
+            }
+        }
+        for child in self.children().iter() {
+            child.resume();
+        }
+    }
+
+    /// Is the Bus paused or not ?
+    pub fn is_paused(&self) -> bool {
+        self.inner.borrow().paused
+    }
+
+    /**
+     * Check if a source of the Bus or of its children is playing.
+     *
+     * # Return
+     * true if at least one source is playing, false otherwise.
+     */
+    pub fn is_playing(&self) -> bool {
+        check_openal_context!(false);
+
+        let playing = self.inner.borrow().members.iter().any(|m| {
+            al::alGetState(m.al_source) == ffi::AL_PLAYING
+        });
+        playing || self.children().iter().any(|c| c.is_playing())
+    }
+
+    /// Get the live children of the Bus.
+    fn children(&self) -> Vec<Bus> {
+        self.inner.borrow().children.iter()
+                     .filter_map(|c| c.upgrade())
+                     .map(|c| Bus { inner: c })
+                     .collect()
+    }
+
+    /// Get the top Bus of the hierarchy.
+    fn root(&self) -> Bus {
+        match self.inner.borrow().parent {
+            Some(ref parent) => parent.root(),
+            None             => self.clone()
+        }
+    }
+
+    /// The volume of the Bus multiplied by the volume of its parents.
+    fn chain_volume(&self) -> f32 {
+        let data = self.inner.borrow();
+        let volume = if data.muted { 0. } else { data.volume * data.duck_volume };
+        match data.parent {
+            Some(ref parent) => volume * parent.chain_volume(),
+            None             => volume
+        }
+    }
+
+    /// Is there a solo Bus in the Bus or in its children.
+    fn has_solo_child(&self) -> bool {
+        self.is_solo() || self.children().iter().any(|c| c.has_solo_child())
+    }
+
+    /// Is there a solo Bus in the parents of the Bus.
+    fn has_solo_parent(&self) -> bool {
+        match self.inner.borrow().parent {
+            Some(ref parent) => parent.is_solo() || parent.has_solo_parent(),
+            None             => false
+        }
+    }
+
+    fn is_silenced_by_solo(&self) -> bool {
+        self.root().has_solo_child() &&
+            !(self.has_solo_child() || self.has_solo_parent())
+    }
+
+    /// Apply the volume and the pitch on the members of the Bus and its children.
+    fn apply(&self) -> () {
+        check_openal_context!(());
+
+        let volume = self.get_effective_volume();
+        let pitch = self.get_effective_pitch();
+        for member in self.inner.borrow().members.iter() {
+            apply_member(member, volume, pitch);
+        }
+        for child in self.children().iter() {
+            child.apply();
+        }
+    }
+
+    /// Is the Bus or one of its parents paused.
+    fn is_paused_by_chain(&self) -> bool {
+        let data = self.inner.borrow();
+        data.paused || match data.parent {
+            Some(ref parent) => parent.is_paused_by_chain(),
+            None             => false
+        }
+    }
+
+    fn find_member(&self, al_source: u32) -> Option<uint> {
+        self.inner.borrow().members.iter().position(|m| m.al_source == al_source)
+    }
+}
+
+impl PartialEq for Bus {
+    /// Two Buses are equal if they are handles on the same Bus.
+    fn eq(&self, other: &Bus) -> bool {
+        &*self.inner as *const RefCell<BusData> == &*other.inner as *const RefCell<BusData>
+    }
+}
+
+fn apply_member(member: &Member, volume: f32, pitch: f32) -> () {
+    al::alSourcef(member.al_source, ffi::AL_GAIN, member.volume * volume);
+    al::alSourcef(member.al_source, ffi::AL_PITCH, member.pitch * pitch);
+}
+
+/// Set the attenuation applied on a Bus by the ducking rules.
+#[doc(hidden)]
+pub fn set_duck_volume(bus: &Bus, volume: f32) -> () {
+    if bus.inner.borrow().duck_volume != volume {
+        bus.inner.borrow_mut().duck_volume = volume;
+        bus.apply();
+    }
+}
+
+/// Assign a source to a Bus.
+#[doc(hidden)]
+pub fn add_source(bus: &Bus, al_source: u32, volume: f32, pitch: f32) -> () {
+    let mut member = Member {
+        al_source: al_source,
+        volume: volume,
+        pitch: pitch,
+        paused: false
+    };
+    let (bus_volume, bus_pitch) = (bus.get_effective_volume(),
+                                   bus.get_effective_pitch());
+    apply_member(&member, bus_volume, bus_pitch);
+    // The Bus holds the source until it's resumed
+    if bus.is_paused_by_chain() && al::alGetState(al_source) == ffi::AL_PLAYING {
+        al::alSourcePause(al_source);
+        member.paused = true;
+    }
+    bus.inner.borrow_mut().members.push(member);
+}
+
+/// Remove a source from a Bus and restore its own volume and pitch.
+#[doc(hidden)]
+pub fn remove_source(bus: &Bus, al_source: u32) -> () {
+    match bus.find_member(al_source) {
+        Some(idx) => {
+            let member = bus.inner.borrow_mut().members.remove(idx).unwrap();
+            apply_member(&member, 1., 1.);
+        },
+        None      => {}
+    }
+}
+
+/// Set the volume of a source inside its Bus.
+#[doc(hidden)]
+pub fn set_source_volume(bus: &Bus, al_source: u32, volume: f32) -> () {
+    if volume < 0. {
+        println!("Error : the volume of a source cannot be negative.");
+        return;
+    }
+    match bus.find_member(al_source) {
+        Some(idx) => {
+            let (bus_volume, bus_pitch) = (bus.get_effective_volume(),
+                                           bus.get_effective_pitch());
+            let mut data = bus.inner.borrow_mut();
+            let member = data.members.get_mut(idx);
+            member.volume = volume;
+            apply_member(member, bus_volume, bus_pitch);
+        },
+        None      => {}
+    }
+}
+
+/// Get the volume of a source inside its Bus.
+#[doc(hidden)]
+pub fn get_source_volume(bus: &Bus, al_source: u32) -> f32 {
+    match bus.find_member(al_source) {
+        Some(idx) => bus.inner.borrow().members[idx].volume,
+        None      => 0.
+    }
+}
+
+/// Set the pitch of a source inside its Bus.
+#[doc(hidden)]
+pub fn set_source_pitch(bus: &Bus, al_source: u32, pitch: f32) -> () {
+    if pitch <= 0. {
+        println!("Error : the pitch of a source must be positive.");
+        return;
+    }
+    match bus.find_member(al_source) {
+        Some(idx) => {
+            let (bus_volume, bus_pitch) = (bus.get_effective_volume(),
+                                           bus.get_effective_pitch());
+            let mut data = bus.inner.borrow_mut();
+            let member = data.members.get_mut(idx);
+            member.pitch = pitch;
+            apply_member(member, bus_volume, bus_pitch);
+        },
+        None      => {}
+    }
+}
+
+/// Get the pitch of a source inside its Bus.
+#[doc(hidden)]
+pub fn get_source_pitch(bus: &Bus, al_source: u32) -> f32 {
+    match bus.find_member(al_source) {
+        Some(idx) => bus.inner.borrow().members[idx].pitch,
+        None      => 1.
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use bus::Bus;
+    use sound::Sound;
+    use states::{Playing, Paused};
+    use audio_controller::AudioController;
+
+    #[test]
+    fn bus_effective_volume_OK() -> () {
+        let master = Bus::new();
+        let sfx = Bus::new_child(&master);
+
+        master.set_volume(0.5);
+        sfx.set_volume(0.5);
+        assert_eq!(sfx.get_effective_volume(), 0.25);
+    }
+
+    #[test]
+    fn bus_muted_OK() -> () {
+        let master = Bus::new();
+        let sfx = Bus::new_child(&master);
+
+        master.set_muted(true);
+        assert_eq!(sfx.get_effective_volume(), 0.);
+    }
+
+    #[test]
+    fn bus_solo_OK() -> () {
+        let master = Bus::new();
+        let sfx = Bus::new_child(&master);
+        let music = Bus::new_child(&master);
+
+        sfx.set_solo(true);
+        assert_eq!(sfx.get_effective_volume(), 1.);
+        assert_eq!(master.get_effective_volume(), 1.);
+        assert_eq!(music.get_effective_volume(), 0.);
+    }
+
+    #[test]
+    fn bus_sound_volume_OK() -> () {
+        let master = Bus::new();
+        let mut snd = Sound::new("res/shot.wav").expect("Cannot create sound");
+
+        snd.set_bus(&master);
+        master.set_volume(0.5);
+        snd.set_volume(0.7);
+        assert_eq!(snd.get_volume(), 0.7);
+    }
+
+    #[test]
+    fn bus_add_source_to_paused_bus_OK() -> () {
+        let master = Bus::new();
+        let sfx = Bus::new_child(&master);
+        let mut snd = Sound::new("res/shot.wav").expect("Cannot create sound");
+
+        master.pause();
+        snd.play();
+        snd.set_bus(&sfx);
+        assert_eq!(snd.get_state() as i32, Paused as i32);
+
+        master.resume();
+        assert_eq!(snd.get_state() as i32, Playing as i32);
+    }
+}
@@ -0,0 +1,410 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Sources of the samples captured by a Recorder.
+
+#![allow(raw_pointer_deriving)]
+
+use std::num::Float;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use time;
+
+use libc::c_void;
+use openal::{ffi, al};
+use io::SoundReader;
+use resampler::{Resampler, ResampleSinc};
+use generator::{Generator, SineWave, WhiteNoise, Silence};
+use stream_source::SampleSource;
+
+/**
+ * The sample rate of the captured samples.
+ *
+ * The capture device is opened at this rate, and the file and signal
+ * sources deliver their samples at this rate too.
+ */
+pub static CAPTURE_SAMPLERATE: i32 = 44100;
+
+/**
+ * A signal generated by a fake input device.
+ *
+ * The signals are generated by a Generator, the amplitudes go from 0 to 1.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum CaptureSignal {
+    /// A sine wave, with its frequency in Hz and its amplitude.
+    CaptureSine(f32, f32),
+    /// A white noise, with its amplitude.
+    CaptureNoise(f32),
+    /// Silence.
+    CaptureSilence
+}
+
+/**
+ * A source of captured samples.
+ *
+ * The samples are mono, signed 16 bit, at 44100 Hz. The Recorder starts the
+ * source, then reads the available samples until it is stopped. A source
+ * must deliver its samples in real time, like a microphone.
+ */
+pub trait CaptureSource {
+    /// Start the capture.
+    fn start(&mut self) -> ();
+
+    /// Get the count of samples captured since the last read.
+    fn available(&mut self) -> uint;
+
+    /**
+     * Read captured samples.
+     *
+     * # Argument
+     * * `samples` - The buffer to fill, no longer than the available samples
+     */
+    fn read(&mut self, samples: &mut [i16]) -> ();
+
+    /// Stop the capture.
+    fn stop(&mut self) -> ();
+}
+
+/**
+ * Capture the samples of an OpenAL capture device.
+ *
+ * The device is opened by ears::init_in, mono, signed 16 bit, at 44100 Hz.
+ */
+pub struct DeviceCapture {
+    device: *mut ffi::ALCdevice
+}
+
+impl DeviceCapture {
+    /**
+     * Capture an opened capture device.
+     *
+     * # Argument
+     * * `device` - The OpenAL capture device
+     */
+    pub fn new(device: *mut ffi::ALCdevice) -> DeviceCapture {
+        DeviceCapture {
+            device: device
+        }
+    }
+}
+
+impl CaptureSource for DeviceCapture {
+    fn start(&mut self) -> () {
+        al::alcCaptureStart(self.device);
+    }
+
+    fn available(&mut self) -> uint {
+        al::alcGetCaptureSamples(self.device) as uint
+    }
+
+    fn read(&mut self, samples: &mut [i16]) -> () {
+        if samples.len() != 0 {
+            al::alcCaptureSamples(self.device,
+                                  samples.as_mut_ptr() as *mut c_void,
+                                  samples.len() as i32);
+        }
+    }
+
+    fn stop(&mut self) -> () {
+        al::alcCaptureStop(self.device);
+    }
+}
+
+/// Count the samples a real time source has produced.
+struct RealTime {
+    start: u64,
+    delivered: u64
+}
+
+impl RealTime {
+    fn new() -> RealTime {
+        RealTime {
+            start: time::precise_time_ns(),
+            delivered: 0
+        }
+    }
+
+    fn available(&self) -> uint {
+        let elapsed = time::precise_time_ns() - self.start;
+        let produced = elapsed * CAPTURE_SAMPLERATE as u64 / 1000000000;
+        if produced > self.delivered { (produced - self.delivered) as uint } else { 0 }
+    }
+
+    fn consume(&mut self, count: uint) -> () {
+        self.delivered += count as u64;
+    }
+}
+
+/**
+ * Capture the samples of an audio file.
+ *
+ * The file is mixed down to mono and converted to 44100 Hz, then played in
+ * real time. Silence is captured after the end of the file.
+ */
+pub struct FileCapture {
+    samples: Vec<i16>,
+    position: uint,
+    clock: RealTime
+}
+
+impl FileCapture {
+    /**
+     * Read the samples of an audio file.
+     *
+     * # Argument
+     * * `path` - The path of the file
+     *
+     * # Return
+     * Some(FileCapture) if the file can be read, None otherwise.
+     */
+    pub fn new(path: &str) -> Option<FileCapture> {
+        let mut reader = match SoundReader::open(path) {
+            Ok(reader) => reader,
+            Err(err)   => { println!("{}", err); return None; }
+        };
+        let channels = reader.get_channels();
+        let samples: Vec<f32> = match reader.read_all() {
+            Ok(samples) => samples,
+            Err(err)    => { println!("{}", err); return None; }
+        };
+        let mono: Vec<f32> = samples.as_slice().chunks(channels).map(|frame| {
+            frame.iter().fold(0f32, |sum, s| sum + *s) / channels as f32
+        }).collect();
+        let resampler = Resampler::new(reader.get_info().samplerate,
+                                       CAPTURE_SAMPLERATE,
+                                       1,
+                                       ResampleSinc);
+
+        Some(FileCapture {
+            samples: resampler.process(mono.as_slice()).iter().map(|s| {
+                (s.max(-1.).min(1.) * 32767.) as i16
+            }).collect(),
+            position: 0,
+            clock: RealTime::new()
+        })
+    }
+}
+
+impl CaptureSource for FileCapture {
+    fn start(&mut self) -> () {
+        self.position = 0;
+        self.clock = RealTime::new();
+    }
+
+    fn available(&mut self) -> uint {
+        self.clock.available()
+    }
+
+    fn read(&mut self, samples: &mut [i16]) -> () {
+        for sample in samples.iter_mut() {
+            *sample = if self.position < self.samples.len() {
+                self.samples.as_slice()[self.position]
+            } else {
+                0
+            };
+            self.position += 1;
+        }
+        self.clock.consume(samples.len());
+    }
+
+    fn stop(&mut self) -> () {}
+}
+
+/**
+ * Capture a generated signal.
+ *
+ * The signal is generated in real time, like a microphone delivering its
+ * samples.
+ */
+pub struct SignalCapture {
+    generator: Generator,
+    clock: RealTime
+}
+
+impl SignalCapture {
+    /**
+     * Capture a signal.
+     *
+     * # Argument
+     * * `signal` - The signal to generate
+     */
+    pub fn new(signal: CaptureSignal) -> SignalCapture {
+        let generator = match signal {
+            CaptureSine(frequency, amplitude) => {
+                Generator::new_endless(SineWave, frequency, amplitude)
+            },
+            CaptureNoise(amplitude)           => Generator::new_endless(WhiteNoise, 0., amplitude),
+            CaptureSilence                    => Generator::new_endless(Silence, 0., 0.)
+        };
+        SignalCapture {
+            generator: generator,
+            clock: RealTime::new()
+        }
+    }
+}
+
+impl CaptureSource for SignalCapture {
+    fn start(&mut self) -> () {
+        self.generator.rewind();
+        self.clock = RealTime::new();
+    }
+
+    fn available(&mut self) -> uint {
+        self.clock.available()
+    }
+
+    fn read(&mut self, samples: &mut [i16]) -> () {
+        let mut signal = Vec::from_elem(samples.len(), 0f32);
+        self.generator.fill(signal.as_mut_slice(), CAPTURE_SAMPLERATE);
+        for (sample, value) in samples.iter_mut().zip(signal.iter()) {
+            *sample = (*value * 32767.) as i16;
+        }
+        self.clock.consume(samples.len());
+    }
+
+    fn stop(&mut self) -> () {}
+}
+
+/**
+ * Capture a source given by the user.
+ *
+ * The source is shared by the clones of its RecordContext, so the Recorders
+ * created from them capture the same source one after the other.
+ */
+#[deriving(Clone)]
+pub struct SharedCapture {
+    source: Arc<Mutex<Box<CaptureSource + Send>>>
+}
+
+impl SharedCapture {
+    /**
+     * Share a source.
+     *
+     * # Argument
+     * * `source` - The source to capture
+     */
+    pub fn new(source: Box<CaptureSource + Send>) -> SharedCapture {
+        SharedCapture {
+            source: Arc::new(Mutex::new(source))
+        }
+    }
+}
+
+impl CaptureSource for SharedCapture {
+    fn start(&mut self) -> () {
+        self.source.lock().start()
+    }
+
+    fn available(&mut self) -> uint {
+        self.source.lock().available()
+    }
+
+    fn read(&mut self, samples: &mut [i16]) -> () {
+        self.source.lock().read(samples)
+    }
+
+    fn stop(&mut self) -> () {
+        self.source.lock().stop()
+    }
+}
+
+impl PartialEq for SharedCapture {
+    /// Two SharedCaptures are equal if they share the same source.
+    fn eq(&self, other: &SharedCapture) -> bool {
+        &*self.source as *const Mutex<Box<CaptureSource + Send>>
+            == &*other.source as *const Mutex<Box<CaptureSource + Send>>
+    }
+}
+
+impl fmt::Show for SharedCapture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SharedCapture")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use capture::{CaptureSource, SignalCapture, FileCapture, SharedCapture};
+    use capture::{CaptureSine, CaptureNoise, CaptureSilence};
+
+    #[test]
+    fn capture_sine_OK() -> () {
+        let mut source = SignalCapture::new(CaptureSine(441., 1.));
+        let mut samples = [1i16, ..100];
+        source.read(samples.as_mut_slice());
+        assert_eq!(samples[0], 0);
+        assert!(samples[25] > 32700);
+        assert!(samples[50] > -10 && samples[50] < 10);
+    }
+
+    #[test]
+    fn capture_noise_clamped_OK() -> () {
+        let mut source = SignalCapture::new(CaptureNoise(4.));
+        let mut samples = [0i16, ..1000];
+        source.read(samples.as_mut_slice());
+        assert!(samples.iter().any(|s| *s != 0));
+        // The amplitude is clamped to 1, the samples don't wrap around
+        let mut other = SignalCapture::new(CaptureNoise(1.));
+        let mut expected = [0i16, ..1000];
+        other.read(expected.as_mut_slice());
+        assert_eq!(samples.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn capture_silence_OK() -> () {
+        let mut source = SignalCapture::new(CaptureSilence);
+        let mut samples = [1i16, ..16];
+        source.read(samples.as_mut_slice());
+        assert!(samples.iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn capture_file_OK() -> () {
+        let mut source = FileCapture::new("res/shot.wav").unwrap();
+        let len = source.samples.len();
+        assert!(len != 0);
+        source.position = len - 8;
+        let mut samples = [1i16, ..16];
+        source.read(samples.as_mut_slice());
+        assert!(samples.slice_from(8).iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn capture_shared_OK() -> () {
+        let source = SharedCapture::new(box SignalCapture::new(CaptureSine(441., 1.))
+                                        as Box<CaptureSource + Send>);
+        let mut first = source.clone();
+        let mut second = source.clone();
+        assert!(first == second);
+        let mut samples = [1i16, ..25];
+        first.read(samples.as_mut_slice());
+        second.read(samples.as_mut_slice());
+        assert!(samples[0] > 32700);
+    }
+
+    #[test]
+    fn capture_file_FAIL() -> () {
+        assert!(FileCapture::new("toto.wav").is_none());
+    }
+}
@@ -0,0 +1,531 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The channel layouts of the sounds.
+
+use std::cmp;
+use std::num::Float;
+
+use openal::al;
+use sample::SampleType;
+use load_options::LoadOptions;
+use decoder::Decoder;
+
+/**
+ * The speaker of a channel, from the channel map of a file.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum Speaker {
+    /// A mono channel
+    SpeakerMono,
+    /// The front left speaker
+    SpeakerLeft,
+    /// The front right speaker
+    SpeakerRight,
+    /// The front center speaker
+    SpeakerCenter,
+    /// The low frequency effects channel
+    SpeakerLfe,
+    /// The rear left speaker
+    SpeakerRearLeft,
+    /// The rear right speaker
+    SpeakerRearRight,
+    /// The rear center speaker
+    SpeakerRearCenter,
+    /// The side left speaker
+    SpeakerSideLeft,
+    /// The side right speaker
+    SpeakerSideRight,
+    /// The W (omnidirectional) component of ambisonic B-format
+    AmbisonicW,
+    /// The X (front-back) component of ambisonic B-format
+    AmbisonicX,
+    /// The Y (left-right) component of ambisonic B-format
+    AmbisonicY,
+    /// The Z (up-down) component of ambisonic B-format
+    AmbisonicZ,
+    /// A speaker OpenAL doesn't know
+    SpeakerOther
+}
+
+/**
+ * The channel layouts OpenAL can play.
+ *
+ * * LayoutMono - 1 channel
+ * * LayoutStereo - 2 channels, front left and right
+ * * LayoutRear - 2 channels, rear left and right
+ * * LayoutQuad - 4 channels
+ * * Layout51 - 6 channels, 5.1 surround
+ * * Layout61 - 7 channels, 6.1 surround
+ * * Layout71 - 8 channels, 7.1 surround
+ * * LayoutBFormat2D - 3 channels, horizontal ambisonic B-format (W, X, Y)
+ * * LayoutBFormat3D - 4 channels, full sphere ambisonic B-format (W, X, Y, Z)
+ *
+ * The multichannel layouts require the AL_EXT_MCFORMATS extension and the
+ * B-format layouts the AL_EXT_BFORMAT extension. The sounds with another
+ * layout are downmixed to stereo.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum ChannelLayout {
+    /// 1 channel
+    LayoutMono,
+    /// 2 channels, front left and right
+    LayoutStereo,
+    /// 2 channels, rear left and right
+    LayoutRear,
+    /// 4 channels
+    LayoutQuad,
+    /// 6 channels, 5.1 surround
+    Layout51,
+    /// 7 channels, 6.1 surround
+    Layout61,
+    /// 8 channels, 7.1 surround
+    Layout71,
+    /// 3 channels, horizontal ambisonic B-format
+    LayoutBFormat2D,
+    /// 4 channels, full sphere ambisonic B-format
+    LayoutBFormat3D
+}
+
+impl ChannelLayout {
+    /// Get the count of channels of the layout.
+    pub fn get_channels(&self) -> uint {
+        self.get_speakers().len()
+    }
+
+    /// Get the speakers of the layout, in the order of OpenAL.
+    pub fn get_speakers(&self) -> Vec<Speaker> {
+        match *self {
+            LayoutMono      => vec![SpeakerMono],
+            LayoutStereo    => vec![SpeakerLeft, SpeakerRight],
+            LayoutRear      => vec![SpeakerRearLeft, SpeakerRearRight],
+            LayoutQuad      => vec![SpeakerLeft, SpeakerRight,
+                                    SpeakerRearLeft, SpeakerRearRight],
+            Layout51        => vec![SpeakerLeft, SpeakerRight, SpeakerCenter,
+                                    SpeakerLfe, SpeakerRearLeft, SpeakerRearRight],
+            Layout61        => vec![SpeakerLeft, SpeakerRight, SpeakerCenter,
+                                    SpeakerLfe, SpeakerRearCenter,
+                                    SpeakerSideLeft, SpeakerSideRight],
+            Layout71        => vec![SpeakerLeft, SpeakerRight, SpeakerCenter,
+                                    SpeakerLfe, SpeakerRearLeft, SpeakerRearRight,
+                                    SpeakerSideLeft, SpeakerSideRight],
+            LayoutBFormat2D => vec![AmbisonicW, AmbisonicX, AmbisonicY],
+            LayoutBFormat3D => vec![AmbisonicW, AmbisonicX, AmbisonicY, AmbisonicZ]
+        }
+    }
+
+    /**
+     * Get the usual layout of a count of channels.
+     *
+     * # Return
+     * Some(ChannelLayout) for 1, 2, 4, 6, 7 and 8 channels, None otherwise.
+     */
+    pub fn from_channels(channels: uint) -> Option<ChannelLayout> {
+        match channels {
+            1 => Some(LayoutMono),
+            2 => Some(LayoutStereo),
+            4 => Some(LayoutQuad),
+            6 => Some(Layout51),
+            7 => Some(Layout61),
+            8 => Some(Layout71),
+            _ => None
+        }
+    }
+}
+
+/// The speakers of a file without channel map.
+fn default_speakers(channels: uint) -> Vec<Speaker> {
+    match channels {
+        1 => vec![SpeakerMono],
+        2 => vec![SpeakerLeft, SpeakerRight],
+        3 => vec![SpeakerLeft, SpeakerRight, SpeakerCenter],
+        5 => vec![SpeakerLeft, SpeakerRight, SpeakerCenter,
+                  SpeakerRearLeft, SpeakerRearRight],
+        _ => match ChannelLayout::from_channels(channels) {
+            Some(layout) => layout.get_speakers(),
+            None         => Vec::from_elem(channels, SpeakerOther)
+        }
+    }
+}
+
+/// Get the speakers of the channels of a file.
+pub fn get_speakers(file: &Decoder) -> Vec<Speaker> {
+    let channels = file.get_sndinfo().channels as uint;
+    match file.get_channel_map() {
+        Some(map) => map,
+        None      => default_speakers(channels)
+    }
+}
+
+/// Check if a speaker of a file can play on a speaker of a layout.
+fn is_equivalent(layout: &Speaker, file: &Speaker) -> bool {
+    match (layout, file) {
+        (&SpeakerMono, &SpeakerCenter)          => true,
+        (&SpeakerRearLeft, &SpeakerSideLeft)    => true,
+        (&SpeakerRearRight, &SpeakerSideRight)  => true,
+        (&SpeakerSideLeft, &SpeakerRearLeft)    => true,
+        (&SpeakerSideRight, &SpeakerRearRight)  => true,
+        _                                       => layout == file
+    }
+}
+
+/// Get the gains of a speaker in a stereo downmix.
+fn stereo_gains(speaker: &Speaker) -> (f32, f32) {
+    let half_power = 0.5f32.sqrt();
+    match *speaker {
+        SpeakerLeft                        => (1., 0.),
+        SpeakerRight                       => (0., 1.),
+        SpeakerMono | SpeakerCenter        => (half_power, half_power),
+        SpeakerRearLeft | SpeakerSideLeft  => (half_power, 0.),
+        SpeakerRearRight | SpeakerSideRight => (0., half_power),
+        SpeakerLfe                         => (0., 0.),
+        AmbisonicW                         => (half_power, half_power),
+        AmbisonicY                         => (0.5, -0.5),
+        AmbisonicX | AmbisonicZ            => (0., 0.),
+        SpeakerRearCenter | SpeakerOther   => (0.5, 0.5)
+    }
+}
+
+/**
+ * How to convert the channels of a file to a layout.
+ *
+ * The channels are reordered if the layout is playable by OpenAL, or mixed
+ * down otherwise.
+ */
+#[doc(hidden)]
+#[deriving(Clone, PartialEq, Show)]
+pub struct ChannelPlan {
+    /// The layout of the samples sent to OpenAL
+    layout: ChannelLayout,
+    /// The count of channels of the file
+    channels: uint,
+    /// The channel of the file for each channel of the layout
+    order: Option<Vec<uint>>,
+    /// The gains of the channels of the file for each channel of the layout
+    mix: Option<Vec<Vec<f32>>>
+}
+
+impl ChannelPlan {
+    /**
+     * Find a layout playing the channels of a file without mixing.
+     *
+     * # Argument
+     * * `speakers` - The speakers of the channels of the file
+     *
+     * # Return
+     * Some(ChannelPlan) if a layout contains exactly these speakers, None
+     * otherwise.
+     */
+    pub fn direct(speakers: &[Speaker]) -> Option<ChannelPlan> {
+        let layouts = [LayoutMono, LayoutStereo, LayoutRear, LayoutQuad,
+                       Layout51, Layout61, Layout71, LayoutBFormat2D,
+                       LayoutBFormat3D];
+
+        for layout in layouts.iter() {
+            let targets = layout.get_speakers();
+            if targets.len() != speakers.len() {
+                continue;
+            }
+            match assign(targets.as_slice(), speakers) {
+                Some(order) => {
+                    let identity = order.iter().enumerate().all(|(i, &c)| i == c);
+                    return Some(ChannelPlan {
+                        layout: layout.clone(),
+                        channels: speakers.len(),
+                        order: if identity { None } else { Some(order) },
+                        mix: None
+                    });
+                },
+                None        => {}
+            }
+        }
+        None
+    }
+
+    /**
+     * Mix down the channels of a file.
+     *
+     * # Arguments
+     * * `speakers` - The speakers of the channels of the file
+     * * `layout` - LayoutMono or LayoutStereo
+     */
+    pub fn downmix(speakers: &[Speaker], layout: ChannelLayout) -> ChannelPlan {
+        let mut mix = match layout {
+            LayoutMono => vec![speakers.iter().map(|s| {
+                let (left, right) = stereo_gains(s);
+                (left + right) * 0.5
+            }).collect()],
+            _          => vec![speakers.iter().map(|s| stereo_gains(s).val0()).collect(),
+                               speakers.iter().map(|s| stereo_gains(s).val1()).collect()]
+        };
+
+        // Avoid the clipping when all the channels are at full scale
+        let loudest = mix.iter().map(|gains| {
+            gains.iter().fold(0f32, |sum, gain| sum + gain.abs())
+        }).fold(0f32, |max, sum| max.max(sum));
+        if loudest > 1. {
+            for gains in mix.iter_mut() {
+                for gain in gains.iter_mut() {
+                    *gain = *gain / loudest;
+                }
+            }
+        }
+
+        ChannelPlan {
+            layout: if layout == LayoutMono { LayoutMono } else { LayoutStereo },
+            channels: speakers.len(),
+            order: None,
+            mix: Some(mix)
+        }
+    }
+
+    /// Get the layout of the samples sent to OpenAL.
+    pub fn get_layout(&self) -> ChannelLayout {
+        self.layout.clone()
+    }
+
+    /// Get the count of channels of the file.
+    pub fn get_file_channels(&self) -> uint {
+        self.channels
+    }
+
+    /// Get the count of channels sent to OpenAL.
+    pub fn get_output_channels(&self) -> uint {
+        self.layout.get_channels()
+    }
+
+    /// Check if the channels are mixed.
+    pub fn is_downmix(&self) -> bool {
+        self.mix.is_some()
+    }
+
+    /// Reorder the channels of interleaved samples in the order of OpenAL.
+    pub fn reorder<T: Copy>(&self, samples: &mut [T]) -> () {
+        let order = match self.order {
+            Some(ref order) => order,
+            None            => return
+        };
+        let mut frame = Vec::with_capacity(self.channels);
+        for chunk in samples.chunks_mut(self.channels) {
+            frame.clear();
+            frame.push_all(chunk);
+            for (i, &channel) in order.iter().enumerate() {
+                chunk[i] = frame.as_slice()[channel];
+            }
+        }
+    }
+
+    /**
+     * Mix interleaved samples of the file.
+     *
+     * # Argument
+     * * `input` - The samples of the file
+     *
+     * # Return
+     * The mixed interleaved samples.
+     */
+    pub fn mix(&self, input: &[f32]) -> Vec<f32> {
+        let mix = match self.mix {
+            Some(ref mix) => mix,
+            None          => return input.to_vec()
+        };
+        let mut output = Vec::with_capacity(input.len() / self.channels * mix.len());
+        for frame in input.chunks(self.channels) {
+            for gains in mix.iter() {
+                output.push(frame.iter().zip(gains.iter())
+                                 .fold(0f32, |sum, (sample, gain)| sum + *sample * *gain));
+            }
+        }
+        output
+    }
+}
+
+/**
+ * Find the channel of the file for each speaker of a layout.
+ *
+ * The speakers with the same position are assigned first, then the
+ * equivalent speakers like the side speakers for the rear speakers.
+ */
+fn assign(targets: &[Speaker], speakers: &[Speaker]) -> Option<Vec<uint>> {
+    let mut order: Vec<Option<uint>> = Vec::from_elem(targets.len(), None);
+    let mut used = Vec::from_elem(speakers.len(), false);
+
+    for exact in [true, false].iter() {
+        for (i, target) in targets.iter().enumerate() {
+            if order.as_slice()[i].is_some() {
+                continue;
+            }
+            let found = range(0, speakers.len()).find(|&c| {
+                !used.as_slice()[c] && if *exact {
+                    speakers[c] == *target
+                } else {
+                    is_equivalent(target, &speakers[c])
+                }
+            });
+            match found {
+                Some(c) => {
+                    order.as_mut_slice()[i] = Some(c);
+                    used.as_mut_slice()[c] = true;
+                },
+                None    => {}
+            }
+        }
+    }
+
+    if order.iter().all(|c| c.is_some()) {
+        Some(order.into_iter().map(|c| c.unwrap()).collect())
+    } else {
+        None
+    }
+}
+
+/**
+ * Choose how to send the channels of a file to OpenAL.
+ *
+ * # Arguments
+ * * `file` - The file to play
+ * * `options` - The wanted type of samples and the mono downmix
+ *
+ * # Return
+ * The OpenAL format, the type of samples and the ChannelPlan, None if the
+ * file has no channel.
+ */
+pub fn negotiate(file: &Decoder,
+                 options: &LoadOptions)
+                 -> Option<(i32, SampleType, ChannelPlan)> {
+    let speakers = get_speakers(file);
+    let sample_type = options.sample_type.clone();
+    if speakers.is_empty() {
+        return None;
+    }
+
+    if options.downmix_to_mono && speakers.len() > 1 {
+        let plan = ChannelPlan::downmix(speakers.as_slice(), LayoutMono);
+        return al::negotiate_format(LayoutMono, sample_type).map(|(format, sample_type)| {
+            (format, sample_type, plan)
+        });
+    }
+
+    match ChannelPlan::direct(speakers.as_slice()) {
+        Some(plan) => match al::negotiate_format(plan.get_layout(), sample_type.clone()) {
+            Some((format, sample_type)) => return Some((format, sample_type, plan)),
+            None                        => {}
+        },
+        None       => {}
+    }
+
+    let channels = cmp::min(speakers.len(), 2);
+    let layout = if channels == 1 { LayoutMono } else { LayoutStereo };
+    println!("ears: {} channels are not supported by OpenAL, mixing down to {}.",
+             speakers.len(), if channels == 1 { "mono" } else { "stereo" });
+    let plan = ChannelPlan::downmix(speakers.as_slice(), layout.clone());
+    al::negotiate_format(layout, sample_type).map(|(format, sample_type)| {
+        (format, sample_type, plan)
+    })
+}
+
+/**
+ * Warn that a sound with several channels is positioned.
+ *
+ * OpenAL plays the sounds with several channels without positioning them.
+ *
+ * # Arguments
+ * * `name` - The path of the sound
+ * * `channels` - The count of channels of the buffer
+ */
+#[doc(hidden)]
+pub fn warn_unpositioned(name: &str, channels: uint) -> () {
+    println!("ears: {} has {} channels and is positioned, but OpenAL only \
+              positions mono sounds. Load it with LoadOptions::downmix_to_mono.",
+             name, channels);
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use channels::{ChannelPlan, default_speakers, LayoutMono, LayoutStereo, Layout51,
+                   Layout71, LayoutBFormat2D, SpeakerLeft, SpeakerRight,
+                   SpeakerCenter, SpeakerLfe, SpeakerSideLeft,
+                   SpeakerSideRight, AmbisonicW, AmbisonicX, AmbisonicY};
+
+    #[test]
+    fn channels_direct_51_OK() -> () {
+        let plan = ChannelPlan::direct(default_speakers(6).as_slice()).unwrap();
+
+        assert_eq!(plan.get_layout(), Layout51);
+        assert_eq!(plan.get_output_channels(), 6);
+        assert!(!plan.is_downmix());
+    }
+
+    #[test]
+    fn channels_direct_71_OK() -> () {
+        let plan = ChannelPlan::direct(default_speakers(8).as_slice()).unwrap();
+
+        assert_eq!(plan.get_layout(), Layout71);
+    }
+
+    #[test]
+    fn channels_direct_side_51_OK() -> () {
+        // 5.1 with side speakers, in the order of a WAVEFORMATEXTENSIBLE
+        let speakers = [SpeakerLeft, SpeakerRight, SpeakerCenter, SpeakerLfe,
+                        SpeakerSideLeft, SpeakerSideRight];
+        let plan = ChannelPlan::direct(speakers.as_slice()).unwrap();
+
+        assert_eq!(plan.get_layout(), Layout51);
+    }
+
+    #[test]
+    fn channels_direct_bformat_OK() -> () {
+        let speakers = [AmbisonicY, AmbisonicW, AmbisonicX];
+        let plan = ChannelPlan::direct(speakers.as_slice()).unwrap();
+        let mut samples = [3i16, 1, 2, 6, 4, 5];
+
+        assert_eq!(plan.get_layout(), LayoutBFormat2D);
+        plan.reorder(samples.as_mut_slice());
+        assert_eq!(samples.as_slice(), [1i16, 2, 3, 4, 5, 6].as_slice());
+    }
+
+    #[test]
+    fn channels_direct_FAIL() -> () {
+        assert!(ChannelPlan::direct(default_speakers(3).as_slice()).is_none());
+        assert!(ChannelPlan::direct(default_speakers(5).as_slice()).is_none());
+    }
+
+    #[test]
+    fn channels_downmix_OK() -> () {
+        let plan = ChannelPlan::downmix(default_speakers(3).as_slice(), LayoutStereo);
+        let output = plan.mix([0.5f32, 0., 0.].as_slice());
+
+        assert_eq!(plan.get_output_channels(), 2);
+        assert!(output.as_slice()[0] > 0.);
+        assert_eq!(output.as_slice()[1], 0.);
+    }
+
+    #[test]
+    fn channels_downmix_mono_OK() -> () {
+        let plan = ChannelPlan::downmix(default_speakers(2).as_slice(), LayoutMono);
+        let output = plan.mix([1f32, 1., 0.5, -0.5].as_slice());
+
+        assert_eq!(plan.get_output_channels(), 1);
+        assert_eq!(output, vec![1f32, 0.]);
+    }
+}
@@ -0,0 +1,378 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The audio clock and the scheduled playback.
+//!
+//! The clock is read from the backend: the device clock when the OpenAL
+//! implementation has the `ALC_SOFT_device_clock` extension, the count of
+//! mixed frames for the SoftwareMixer. Otherwise it is read from a monotonic
+//! system timer.
+//! The scheduled actions are sample accurate when the
+//! `AL_SOFT_source_start_delay` extension is available. Otherwise they are
+//! triggered by `ears::update` and a late start is compensated by moving the
+//! playback position of the source, so the sound stays in sync with the
+//! clock.
+
+use std::cell::RefCell;
+use std::mem;
+use std::time::Duration;
+use time;
+
+use backend;
+use internal::OpenAlData;
+use openal::{ffi, al};
+
+local_data_key!(clock_origin: u64)
+local_data_key!(scheduled_actions: RefCell<Vec<Action>>)
+
+/// What to do at the scheduled time.
+#[deriving(PartialEq, Clone)]
+enum ActionKind {
+    StartAction,
+    StopAction
+}
+
+/// An action waiting for its time.
+struct Action {
+    al_source: u32,
+    time: i64,
+    kind: ActionKind,
+    samplerate: i32,
+    frames: Option<i64>
+}
+
+/**
+ * Get the current time of the audio clock.
+ *
+ * The clock only has a meaning relatively to itself: use it to compute the
+ * time given to `play_at` or `stop_at`.
+ *
+ * # Return
+ * The current time of the audio clock.
+ *
+ * # Example
+ * ```Rust
+ * # use ears::clock;
+ * let start = clock::now() + Duration::milliseconds(500);
+ * snd.play_at(start);
+ * ```
+ */
+pub fn now() -> Duration {
+    check_openal_context!(Duration::nanoseconds(0));
+
+    Duration::nanoseconds(now_ns())
+}
+
+/**
+ * Check if the scheduled playback is sample accurate.
+ *
+ * # Return
+ * true if the OpenAL implementation start the sources itself at the
+ * scheduled time, false if the start depends on the calls to `ears::update`.
+ */
+pub fn is_sample_accurate() -> bool {
+    check_openal_context!(false);
+
+    can_play_at_time()
+}
+
+fn now_ns() -> i64 {
+    match backend::get().get_clock() {
+        Some(time) => time,
+        None       => {
+            let now = time::precise_time_ns();
+            let origin = match clock_origin.get() {
+                Some(origin) => *origin,
+                None         => { clock_origin.replace(Some(now)); now }
+            };
+            (now - origin) as i64
+        }
+    }
+}
+
+fn can_play_at_time() -> bool {
+    // The start time is expressed with the device clock
+    backend::get().get_clock().is_some() &&
+        al::alIsExtensionPresent("AL_SOFT_source_start_delay")
+}
+
+fn with_actions<T>(f: |&mut Vec<Action>| -> T) -> T {
+    if scheduled_actions.get().is_none() {
+        scheduled_actions.replace(Some(RefCell::new(Vec::new())));
+    }
+    let actions = scheduled_actions.get().unwrap();
+    let mut actions = actions.borrow_mut();
+    f(&mut *actions)
+}
+
+/// Number of frames to skip when a source starts `late` nanoseconds late.
+fn late_offset(late: i64, samplerate: i32, pitch: f32) -> i64 {
+    if late <= 0 {
+        0
+    } else {
+        ((late as f64 * samplerate as f64 * pitch as f64) / 1e9) as i64
+    }
+}
+
+fn start(action: &Action, now: i64) -> () {
+    let mut pitch = 1.;
+    al::alGetSourcef(action.al_source, ffi::AL_PITCH, &mut pitch);
+    let offset = late_offset(now - action.time, action.samplerate, pitch);
+
+    match action.frames {
+        Some(frames) if offset >= frames => return,
+        _                                => {}
+    }
+    al::alSourcePlay(action.al_source);
+    if offset > 0 {
+        al::alSourcei(action.al_source, ffi::AL_SAMPLE_OFFSET, offset as i32);
+        // The offset can be out of the queued buffers of a stream,
+        // then the source just plays from its current position.
+        al::openal_has_error();
+    }
+}
+
+/**
+ * Schedule the start of a source.
+ *
+ * # Arguments
+ * * `al_source` - The OpenAL source to start
+ * * `time` - The time of the audio clock to start at
+ * * `samplerate` - The samplerate of the source
+ * * `frames` - The length of the source in frames, if known
+ */
+#[doc(hidden)]
+pub fn schedule_play(al_source: u32,
+                     time: Duration,
+                     samplerate: i32,
+                     frames: Option<i64>) -> () {
+    let time = match time.num_nanoseconds() {
+        Some(time) => time,
+        None       => return
+    };
+    let now = now_ns();
+
+    if time > now && can_play_at_time() {
+        al::alSourcePlayAtTimeSOFT(al_source, time);
+        return;
+    }
+    let action = Action {
+        al_source: al_source,
+        time: time,
+        kind: StartAction,
+        samplerate: samplerate,
+        frames: frames
+    };
+    if time <= now {
+        start(&action, now);
+    } else {
+        with_actions(|actions| actions.push(action));
+    }
+}
+
+/**
+ * Schedule the stop of a source.
+ *
+ * # Arguments
+ * * `al_source` - The OpenAL source to stop
+ * * `time` - The time of the audio clock to stop at
+ */
+#[doc(hidden)]
+pub fn schedule_stop(al_source: u32, time: Duration) -> () {
+    let time = match time.num_nanoseconds() {
+        Some(time) => time,
+        None       => return
+    };
+
+    if time <= now_ns() {
+        al::alSourceStop(al_source);
+    } else {
+        with_actions(|actions| actions.push(Action {
+            al_source: al_source,
+            time: time,
+            kind: StopAction,
+            samplerate: 0,
+            frames: None
+        }));
+    }
+}
+
+/// Remove the pending actions of a source.
+#[doc(hidden)]
+pub fn cancel(al_source: u32) -> () {
+    with_actions(|actions| actions.retain(|a| a.al_source != al_source));
+}
+
+/// Trigger the actions whose time is reached.
+#[doc(hidden)]
+pub fn process() -> () {
+    let now = now_ns();
+    let mut due = with_actions(|actions| {
+        let pending = mem::replace(actions, Vec::new());
+        let (due, waiting) = pending.partition(|a| a.time <= now);
+        *actions = waiting;
+        due
+    });
+    due.sort_by(|a, b| a.time.cmp(&b.time));
+
+    for action in due.iter() {
+        match action.kind {
+            StartAction => start(action, now),
+            StopAction  => al::alSourceStop(action.al_source)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::time::Duration;
+    use libc::c_void;
+    use backend;
+    use backend::AudioBackend;
+    use software_mixer::SoftwareMixer;
+    use openal::{ffi, al};
+    use clock::{late_offset, schedule_play, schedule_stop, cancel, process, with_actions};
+
+    /// Run the task on a mixer at 100 Hz, its clock only moves when it mixes.
+    fn use_mixer() -> SoftwareMixer {
+        let mixer = SoftwareMixer::new(100);
+        backend::set_task_backend(box mixer.clone() as Box<AudioBackend + Send + Sync>);
+        mixer
+    }
+
+    /// Create a source with 1 second of samples.
+    fn create_source(mixer: &SoftwareMixer) -> u32 {
+        let samples = [16384i16, ..100];
+        let mut buffer = 0;
+        let mut source = 0;
+        mixer.gen_buffers(1, &mut buffer);
+        mixer.buffer_data(buffer, ffi::AL_FORMAT_MONO16, samples.as_ptr() as *mut c_void,
+                          200, 100);
+        mixer.gen_sources(1, &mut source);
+        mixer.sourcei(source, ffi::AL_BUFFER, buffer as i32);
+        source
+    }
+
+    /// Move the clock of the mixer.
+    fn advance(mixer: &SoftwareMixer, frames: uint) -> () {
+        let mut output = Vec::from_elem(frames * 2, 0f32);
+        mixer.mix(output.as_mut_slice());
+    }
+
+    fn get_offset(source: u32) -> i32 {
+        let mut offset = 0;
+        al::alGetSourcei(source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+        offset
+    }
+
+    #[test]
+    fn clock_late_offset_OK() -> () {
+        // 10 ms late at 44100 Hz
+        assert_eq!(late_offset(10000000, 44100, 1.), 441);
+        assert_eq!(late_offset(10000000, 44100, 2.), 882);
+    }
+
+    #[test]
+    fn clock_late_offset_on_time_OK() -> () {
+        assert_eq!(late_offset(0, 44100, 1.), 0);
+        assert_eq!(late_offset(-10000000, 44100, 1.), 0);
+    }
+
+    #[test]
+    fn clock_schedule_play_future_OK() -> () {
+        let mixer = use_mixer();
+        let source = create_source(&mixer);
+
+        schedule_play(source, Duration::milliseconds(500), 100, Some(100));
+        assert_eq!(al::alGetState(source), ffi::AL_INITIAL);
+        assert_eq!(with_actions(|actions| actions.len()), 1);
+
+        advance(&mixer, 40);
+        process();
+        assert_eq!(al::alGetState(source), ffi::AL_INITIAL);
+
+        advance(&mixer, 10);
+        process();
+        assert_eq!(al::alGetState(source), ffi::AL_PLAYING);
+        assert_eq!(get_offset(source), 0);
+        assert_eq!(with_actions(|actions| actions.len()), 0);
+    }
+
+    #[test]
+    fn clock_schedule_play_late_OK() -> () {
+        let mixer = use_mixer();
+        let source = create_source(&mixer);
+
+        // Started 200 ms late, the first 20 frames are skipped
+        advance(&mixer, 50);
+        schedule_play(source, Duration::milliseconds(300), 100, Some(100));
+        assert_eq!(al::alGetState(source), ffi::AL_PLAYING);
+        assert_eq!(get_offset(source), 20);
+        assert_eq!(with_actions(|actions| actions.len()), 0);
+    }
+
+    #[test]
+    fn clock_schedule_play_too_late_OK() -> () {
+        let mixer = use_mixer();
+        let source = create_source(&mixer);
+
+        // The source would already be over
+        advance(&mixer, 50);
+        schedule_play(source, Duration::milliseconds(0), 100, Some(40));
+        assert_eq!(al::alGetState(source), ffi::AL_INITIAL);
+    }
+
+    #[test]
+    fn clock_schedule_stop_OK() -> () {
+        let mixer = use_mixer();
+        let source = create_source(&mixer);
+
+        al::alSourcePlay(source);
+        schedule_stop(source, Duration::milliseconds(200));
+        advance(&mixer, 10);
+        process();
+        assert_eq!(al::alGetState(source), ffi::AL_PLAYING);
+
+        advance(&mixer, 10);
+        process();
+        assert_eq!(al::alGetState(source), ffi::AL_STOPPED);
+    }
+
+    #[test]
+    fn clock_cancel_OK() -> () {
+        let mixer = use_mixer();
+        let source = create_source(&mixer);
+        let other = create_source(&mixer);
+
+        schedule_play(source, Duration::milliseconds(100), 100, Some(100));
+        schedule_stop(source, Duration::milliseconds(200));
+        schedule_play(other, Duration::milliseconds(100), 100, Some(100));
+        cancel(source);
+        assert_eq!(with_actions(|actions| actions.len()), 1);
+
+        advance(&mixer, 10);
+        process();
+        assert_eq!(al::alGetState(source), ffi::AL_INITIAL);
+        assert_eq!(al::alGetState(other), ffi::AL_PLAYING);
+    }
+}
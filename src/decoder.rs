@@ -0,0 +1,380 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Decode the audio files, with libsndfile or a dedicated decoder.
+
+use std::io::File;
+
+use sndfile::{SndFile, SndInfo, Read, SeekSet};
+use channels::Speaker;
+use audio_tags::{Tags, get_sound_tags};
+use audio_tags;
+use metadata::{Metadata, get_metadata};
+use metadata;
+use sample;
+
+#[cfg(feature = "mp3")]
+use mp3_decoder::Mp3Decoder;
+
+/// The count of bytes read to detect the format of a file.
+static MAGIC_SIZE: uint = 64;
+
+/**
+ * The format of a file, detected from its first bytes.
+ *
+ * The extension of the file is never used. The Opus and AAC files are
+ * detected to report a clear error, but no decoder supports them: they can't
+ * be opened.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum FileFormat {
+    /// A format for libsndfile: WAV, AIFF, FLAC, Ogg Vorbis...
+    SndFileFormat,
+    /// MPEG audio layer III, with or without ID3 tag
+    Mp3Format,
+    /// Opus in an Ogg container, not supported
+    OpusFormat,
+    /// AAC, raw (ADTS) or in a MP4/M4A container, not supported
+    AacFormat
+}
+
+/**
+ * Something which decodes the samples of an audio file.
+ *
+ * SndFile implements Decoder, the MP3 decoder is enabled by the cargo
+ * feature `mp3`.
+ */
+pub trait Decoder {
+    /// Get the sample rate, the channels and the count of frames.
+    fn get_sndinfo(&self) -> SndInfo;
+
+    /// Get the speaker of each channel, None to use the default layout.
+    fn get_channel_map(&self) -> Option<Vec<Speaker>> {
+        None
+    }
+
+    /**
+     * Read interleaved float frames.
+     *
+     * # Arguments
+     * * `array` - The buffer to fill
+     * * `frames` - The count of frames to read
+     *
+     * # Return
+     * The count of read frames, 0 at the end of the file.
+     */
+    fn readf_f32(&mut self, array: &mut [f32], frames: i64) -> i64;
+
+    /**
+     * Read interleaved 16 bits frames.
+     *
+     * # Arguments
+     * * `array` - The buffer to fill
+     * * `frames` - The count of frames to read
+     *
+     * # Return
+     * The count of read frames, 0 at the end of the file.
+     */
+    fn readf_i16(&mut self, array: &mut [i16], frames: i64) -> i64 {
+        let mut tmp = Vec::from_elem(array.len(), 0f32);
+        let read = self.readf_f32(tmp.as_mut_slice(), frames);
+        let samples = read as uint * self.get_sndinfo().channels as uint;
+        for (dst, src) in array.iter_mut().zip(tmp.slice_to(samples).iter()) {
+            *dst = sample::from_f32(*src);
+        }
+        read
+    }
+
+    /**
+     * Go back to the beginning of the file.
+     *
+     * # Return
+     * true on success, false otherwise.
+     */
+    fn rewind(&mut self) -> bool;
+
+    /// Get the tags of the file.
+    fn get_tags(&self) -> Tags {
+        audio_tags::empty()
+    }
+
+    /// Get the broadcast info, the cue points and the instrument of the file.
+    fn get_metadata(&self) -> Metadata {
+        metadata::empty()
+    }
+}
+
+impl Decoder for SndFile {
+    fn get_sndinfo(&self) -> SndInfo {
+        SndFile::get_sndinfo(self)
+    }
+
+    fn get_channel_map(&self) -> Option<Vec<Speaker>> {
+        SndFile::get_channel_map(self)
+    }
+
+    fn readf_f32(&mut self, array: &mut [f32], frames: i64) -> i64 {
+        SndFile::readf_f32(self, array, frames)
+    }
+
+    fn readf_i16(&mut self, array: &mut [i16], frames: i64) -> i64 {
+        SndFile::readf_i16(self, array, frames)
+    }
+
+    fn rewind(&mut self) -> bool {
+        self.seek(0, SeekSet) != -1
+    }
+
+    fn get_tags(&self) -> Tags {
+        get_sound_tags(self)
+    }
+
+    fn get_metadata(&self) -> Metadata {
+        get_metadata(self)
+    }
+}
+
+/// The magic bytes of the containers read by libsndfile.
+static SNDFILE_MAGICS: [&'static [u8], ..8] = [b"RIFF", b"RIFX", b"RF64", b"fLaC", b"FORM",
+                                               b"OggS", b".snd", b"caff"];
+
+/**
+ * Get the size of an ID3v2 tag from its header.
+ *
+ * # Argument
+ * * `data` - The first bytes of the file
+ *
+ * # Return
+ * The size of the tag with its header and footer, 0 without tag.
+ */
+pub fn id3v2_size(data: &[u8]) -> uint {
+    if data.len() < 10 || !data.starts_with(b"ID3") {
+        return 0;
+    }
+    let size = data.slice(6, 10).iter().fold(0u, |size, &byte| (size << 7) | (byte & 0x7F) as uint);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+/**
+ * Detect the format of a file from its first bytes.
+ *
+ * An ID3v2 tag is skipped and the bytes after it are detected: a FLAC or WAV
+ * file can have one. The file is a MP3 file when these bytes are not a
+ * container read by libsndfile.
+ *
+ * # Argument
+ * * `header` - The first bytes of the file
+ *
+ * # Return
+ * The detected format, SndFileFormat if it's not one of the formats
+ * libsndfile can't read.
+ */
+pub fn detect_format(header: &[u8]) -> FileFormat {
+    let tag = id3v2_size(header);
+    if tag > 0 {
+        if tag >= header.len() {
+            return Mp3Format;
+        }
+        return detect_after_tag(header.slice_from(tag));
+    }
+    if header.len() >= 36 && header.starts_with(b"OggS")
+       && header.slice(28, 36) == b"OpusHead" {
+        return OpusFormat;
+    }
+    if header.len() >= 8 && header.slice(4, 8) == b"ftyp" {
+        return AacFormat;
+    }
+    if header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0 {
+        // The layer bits are 0 in the ADTS header of AAC
+        return if header[1] & 0x06 == 0 { AacFormat } else { Mp3Format };
+    }
+    SndFileFormat
+}
+
+/// Detect the format of the bytes following an ID3v2 tag.
+fn detect_after_tag(data: &[u8]) -> FileFormat {
+    match detect_format(data) {
+        SndFileFormat if !SNDFILE_MAGICS.iter().any(|magic| data.starts_with(*magic)) => Mp3Format,
+        format => format
+    }
+}
+
+/// Read the bytes at the current position of a file, up to the size of the buffer.
+fn read_header(file: &mut File, header: &mut [u8]) -> uint {
+    let mut len = 0;
+    while len < header.len() {
+        match file.read(header.slice_from_mut(len)) {
+            Ok(read) => len += read,
+            Err(_)   => break
+        }
+    }
+    len
+}
+
+/**
+ * Detect the format of a file.
+ *
+ * # Argument
+ * * `path` - The path of the file
+ *
+ * # Return
+ * Some(FileFormat), None if the file can't be read.
+ */
+pub fn detect_file_format(path: &str) -> Option<FileFormat> {
+    let mut file = match File::open(&Path::new(path)) {
+        Ok(file) => file,
+        Err(err) => { println!("Error: can't open {}: {}.", path, err); return None; }
+    };
+    let mut header = Vec::from_elem(MAGIC_SIZE, 0u8);
+    let len = read_header(&mut file, header.as_mut_slice());
+    let tag = id3v2_size(header.slice_to(len));
+    if tag < len {
+        return Some(detect_format(header.slice_to(len)));
+    }
+
+    // Read the bytes after a tag longer than the header
+    if file.seek(tag as i64, std::io::SeekSet).is_err() {
+        return Some(Mp3Format);
+    }
+    let len = read_header(&mut file, header.as_mut_slice());
+    if len == 0 {
+        return Some(Mp3Format);
+    }
+    Some(detect_after_tag(header.slice_to(len)))
+}
+
+/**
+ * Open a file with the decoder of its format.
+ *
+ * # Argument
+ * * `path` - The path of the file
+ *
+ * # Return
+ * The decoder on success, the description of the error otherwise.
+ */
+pub fn open(path: &str) -> Result<Box<Decoder + Send>, String> {
+    let format = match detect_file_format(path) {
+        Some(format) => format,
+        None         => return Err(format!("Can't read {}.", path))
+    };
+    match format {
+        SndFileFormat => SndFile::new(path, Read).map(|file| box file as Box<Decoder + Send>),
+        Mp3Format     => open_mp3(path),
+        OpusFormat    => Err(format!("{} is an Opus file, Opus is not supported.", path)),
+        AacFormat     => Err(format!("{} is an AAC file, AAC is not supported.", path))
+    }
+}
+
+#[cfg(feature = "mp3")]
+fn open_mp3(path: &str) -> Result<Box<Decoder + Send>, String> {
+    Mp3Decoder::new(path).map(|decoder| box decoder as Box<Decoder + Send>)
+}
+
+#[cfg(not(feature = "mp3"))]
+fn open_mp3(path: &str) -> Result<Box<Decoder + Send>, String> {
+    Err(format!("{} is a MP3 file, build ears with the `mp3` feature to read it.", path))
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::{File, TempDir};
+
+    use decoder::{Decoder, detect_format, detect_file_format, open, SndFileFormat, Mp3Format,
+                  OpusFormat, AacFormat};
+
+    #[test]
+    fn decoder_detect_wav_OK() -> () {
+        assert_eq!(detect_format(b"RIFF\x24\x00\x00\x00WAVEfmt "), SndFileFormat);
+        assert_eq!(detect_file_format("res/shot.wav"), Some(SndFileFormat));
+    }
+
+    #[test]
+    fn decoder_detect_mp3_OK() -> () {
+        assert_eq!(detect_format(b"ID3\x04\x00\x00\x00\x00\x00\x00"), Mp3Format);
+        assert_eq!(detect_format([0xFFu8, 0xFB, 0x90, 0x64].as_slice()), Mp3Format);
+        assert_eq!(detect_file_format("res/sine440.mp3"), Some(Mp3Format));
+    }
+
+    #[test]
+    fn decoder_detect_id3_OK() -> () {
+        // An ID3v2 tag of 4 bytes before a FLAC, a WAV and a MP3 file
+        let tag = b"ID3\x04\x00\x00\x00\x00\x00\x04TIT2";
+        let mut header = tag.to_vec();
+        header.push_all(b"fLaC\x00\x00\x00\x22");
+        assert_eq!(detect_format(header.as_slice()), SndFileFormat);
+
+        let mut header = tag.to_vec();
+        header.push_all(b"RIFF\x24\x00\x00\x00WAVEfmt ");
+        assert_eq!(detect_format(header.as_slice()), SndFileFormat);
+
+        let mut header = tag.to_vec();
+        header.push_all([0xFFu8, 0xFB, 0x90, 0x64].as_slice());
+        assert_eq!(detect_format(header.as_slice()), Mp3Format);
+    }
+
+    #[test]
+    fn decoder_detect_id3_file_OK() -> () {
+        let dir = TempDir::new("ears_decoder").unwrap();
+        let path = dir.path().join("tagged.wav");
+        let path = path.as_str().unwrap();
+
+        // A tag longer than the bytes read to detect the format
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x01\x00".to_vec();
+        data.grow(128, 0u8);
+        data.push_all(File::open(&Path::new("res/shot.wav")).read_to_end().unwrap().as_slice());
+        File::create(&Path::new(path)).write(data.as_slice()).unwrap();
+        assert_eq!(detect_file_format(path), Some(SndFileFormat));
+    }
+
+    #[test]
+    fn decoder_detect_opus_OK() -> () {
+        let mut header = b"OggS".to_vec();
+        header.grow(24, 0u8);
+        header.push_all(b"OpusHead");
+        assert_eq!(detect_format(header.as_slice()), OpusFormat);
+
+        // Ogg Vorbis is read by libsndfile
+        let mut header = b"OggS".to_vec();
+        header.grow(24, 0u8);
+        header.push_all(b"\x01vorbis\x00");
+        assert_eq!(detect_format(header.as_slice()), SndFileFormat);
+    }
+
+    #[test]
+    fn decoder_detect_aac_OK() -> () {
+        assert_eq!(detect_format(b"\x00\x00\x00\x20ftypM4A "), AacFormat);
+        assert_eq!(detect_format([0xFFu8, 0xF1, 0x50, 0x80].as_slice()), AacFormat);
+    }
+
+    #[test]
+    fn decoder_open_OK() -> () {
+        let decoder = open("res/shot.wav").unwrap();
+        assert!(decoder.get_sndinfo().frames > 0);
+    }
+
+    #[test]
+    fn decoder_open_FAIL() -> () {
+        assert!(open("toto.wav").is_err());
+    }
+}
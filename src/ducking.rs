@@ -0,0 +1,281 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Automatic volume control between Buses.
+
+use std::cell::RefCell;
+use std::num::Float;
+use std::time::Duration;
+use std::i64;
+
+use bus::Bus;
+use bus;
+
+local_data_key!(ducking_rules: RefCell<Vec<RuleState>>)
+
+/**
+ * A ducking rule between two Buses.
+ *
+ * While a source of the `trigger` Bus is playing, the `target` Bus is
+ * attenuated by `attenuation` dB. The attenuation is reached in `attack`
+ * and is removed in `release` once the `trigger` Bus is silent.
+ *
+ * The rules are evaluated by `ears::update`.
+ *
+ * # Example
+ * ```Rust
+ * let voice = Bus::new();
+ * let music = Bus::new();
+ *
+ * // Duck the music by 10 dB while a dialogue is playing
+ * ears::add_ducking_rule(DuckingRule::new(&voice, &music, 10.,
+ *                                         Duration::milliseconds(200),
+ *                                         Duration::milliseconds(800)));
+ * loop {
+ *     ears::update();
+ *     // ...
+ * }
+ * ```
+ */
+#[deriving(Clone)]
+pub struct DuckingRule {
+    /// The Bus which trigger the ducking
+    pub trigger: Bus,
+    /// The Bus to attenuate
+    pub target: Bus,
+    /// The attenuation of the target Bus in dB
+    pub attenuation: f32,
+    /// The time to reach the attenuation
+    pub attack: Duration,
+    /// The time to come back to the normal volume
+    pub release: Duration
+}
+
+impl DuckingRule {
+    /**
+     * Create a new DuckingRule.
+     *
+     * # Arguments
+     * * `trigger` - The Bus which trigger the ducking
+     * * `target` - The Bus to attenuate
+     * * `attenuation` - The attenuation of the target Bus in dB
+     * * `attack` - The time to reach the attenuation
+     * * `release` - The time to come back to the normal volume
+     */
+    pub fn new(trigger: &Bus,
+               target: &Bus,
+               attenuation: f32,
+               attack: Duration,
+               release: Duration) -> DuckingRule {
+        DuckingRule {
+            trigger: trigger.clone(),
+            target: target.clone(),
+            attenuation: attenuation,
+            attack: attack,
+            release: release
+        }
+    }
+}
+
+/// The identifier of a ducking rule, used to remove it.
+#[deriving(Clone, PartialEq, Show)]
+pub struct DuckingId(uint);
+
+struct RuleState {
+    id: uint,
+    rule: DuckingRule,
+    /// The current attenuation in dB
+    current: f32
+}
+
+fn with_rules<T>(f: |&mut Vec<RuleState>| -> T) -> T {
+    if ducking_rules.get().is_none() {
+        ducking_rules.replace(Some(RefCell::new(Vec::new())));
+    }
+    let rules = ducking_rules.get().unwrap();
+    let mut rules = rules.borrow_mut();
+    f(&mut *rules)
+}
+
+/**
+ * Add a ducking rule.
+ *
+ * # Argument
+ * * `rule` - The rule to evaluate in `ears::update`
+ *
+ * # Return
+ * The identifier of the rule.
+ */
+pub fn add_ducking_rule(rule: DuckingRule) -> DuckingId {
+    with_rules(|rules| {
+        let id = rules.iter().map(|r| r.id + 1).max().unwrap_or(0);
+        rules.push(RuleState {
+            id: id,
+            rule: rule,
+            current: 0.
+        });
+        DuckingId(id)
+    })
+}
+
+/**
+ * Remove a ducking rule.
+ *
+ * The target Bus come back to its normal volume immediately.
+ *
+ * # Argument
+ * * `id` - The identifier of the rule to remove
+ */
+pub fn remove_ducking_rule(id: DuckingId) -> () {
+    let DuckingId(id) = id;
+    let removed = with_rules(|rules| {
+        match rules.iter().position(|r| r.id == id) {
+            Some(idx) => rules.remove(idx),
+            None      => None
+        }
+    });
+    match removed {
+        Some(state) => bus::set_duck_volume(&state.rule.target, 1.),
+        None        => {}
+    }
+    process(Duration::milliseconds(0));
+}
+
+/// Move the current attenuation toward the target attenuation.
+fn step(current: f32, target: f32, range: f32, time: Duration, elapsed: Duration) -> f32 {
+    // In microseconds, the updates are often less than a millisecond apart
+    let time = time.num_microseconds().unwrap_or(i64::MAX) as f32;
+    if time <= 0. {
+        return target;
+    }
+    let delta = range.abs() * elapsed.num_microseconds().unwrap_or(i64::MAX) as f32 / time;
+    if current < target {
+        (current + delta).min(target)
+    } else {
+        (current - delta).max(target)
+    }
+}
+
+/// Evaluate the ducking rules and apply them on the target Buses.
+#[doc(hidden)]
+pub fn process(elapsed: Duration) -> () {
+    if ducking_rules.get().is_none() {
+        return;
+    }
+
+    let mut targets: Vec<Bus> = Vec::new();
+    let mut attenuations: Vec<f32> = Vec::new();
+    with_rules(|rules| {
+        for state in rules.iter_mut() {
+            let rule = &state.rule;
+            state.current = if rule.trigger.is_playing() {
+                step(state.current, rule.attenuation, rule.attenuation,
+                     rule.attack, elapsed)
+            } else {
+                step(state.current, 0., rule.attenuation,
+                     rule.release, elapsed)
+            };
+
+            match targets.iter().position(|b| *b == rule.target) {
+                Some(idx) => *attenuations.get_mut(idx) += state.current,
+                None      => {
+                    targets.push(rule.target.clone());
+                    attenuations.push(state.current);
+                }
+            }
+        }
+    });
+
+    for (target, db) in targets.iter().zip(attenuations.iter()) {
+        bus::set_duck_volume(target, 10f32.powf(-*db / 20.));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::num::Float;
+    use std::time::Duration;
+    use ducking::{step, DuckingRule, add_ducking_rule, remove_ducking_rule};
+    use bus::Bus;
+    use sound::Sound;
+    use audio_controller::AudioController;
+    use update;
+
+    #[test]
+    fn ducking_attack_OK() -> () {
+        let db = step(0., 10., 10., Duration::milliseconds(100),
+                      Duration::milliseconds(50));
+        assert_eq!(db, 5.);
+    }
+
+    #[test]
+    fn ducking_release_OK() -> () {
+        let db = step(10., 0., 10., Duration::milliseconds(100),
+                      Duration::milliseconds(500));
+        assert_eq!(db, 0.);
+    }
+
+    #[test]
+    fn ducking_short_update_OK() -> () {
+        let mut db = 0.;
+        for _ in range(0u, 100) {
+            db = step(db, 10., 10., Duration::milliseconds(100),
+                      Duration::microseconds(500));
+        }
+        assert!((db - 5.).abs() < 0.001);
+    }
+
+    #[test]
+    fn ducking_no_attack_OK() -> () {
+        let db = step(0., 10., 10., Duration::milliseconds(0),
+                      Duration::milliseconds(1));
+        assert_eq!(db, 10.);
+    }
+
+    #[test]
+    fn ducking_update_and_remove_OK() -> () {
+        let voice = Bus::new();
+        let music = Bus::new();
+        let mut snd = Sound::new("res/shot.wav").expect("Cannot create sound");
+        snd.set_bus(&voice);
+        snd.play();
+
+        let id = add_ducking_rule(DuckingRule::new(&voice, &music, 20.,
+                                                   Duration::milliseconds(100),
+                                                   Duration::milliseconds(100)));
+        // Half of the attack, -10 dB
+        update::process(Duration::milliseconds(50));
+        assert!((music.get_effective_volume() - 10f32.powf(-0.5)).abs() < 0.001);
+        update::process(Duration::milliseconds(50));
+        assert!((music.get_effective_volume() - 0.1).abs() < 0.001);
+        assert_eq!(voice.get_effective_volume(), 1.);
+
+        // Half of the release once the trigger is silent
+        snd.stop();
+        update::process(Duration::milliseconds(50));
+        assert!((music.get_effective_volume() - 10f32.powf(-0.5)).abs() < 0.001);
+
+        remove_ducking_rule(id);
+        assert_eq!(music.get_effective_volume(), 1.);
+    }
+}
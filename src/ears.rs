@@ -100,34 +100,100 @@ use ears::Music;
 #![feature(unsafe_destructor)]
 
 extern crate libc;
+extern crate time;
 
 // Reexport public API
-pub use einit::{init, init_in};
+pub use einit::{init, init_in, init_with, set_null_input};
+pub use internal::{OutputBackend, DefaultOutput, NullOutput};
 pub use music::Music;
+pub use stream_source::{SampleSource, StreamSource};
+pub use generator::{Generator, Waveform, SineWave, SquareWave, SawWave, TriangleWave, WhiteNoise,
+                    PinkNoise, BrownNoise, Sweep, Dtmf, Silence};
+pub use playlist::{Playlist, PlaylistMode, NoRepeat, RepeatOne, RepeatAll};
 pub use sound::Sound;
+pub use sound_bank::SoundBank;
+pub use offline::OfflineRenderer;
+pub use software_mixer::SoftwareMixer;
+pub use hot_reload::{set_hot_reload, is_hot_reload_enabled, poll_reload_events,
+                     ReloadEvent, Reloaded, ReloadFailed};
+pub use loader::{AsyncSoundData, AsyncMusic};
+pub use loader::{LoadStatus, LoadPending, LoadReady, LoadFailed, LoadCancelled};
 pub use states::{State, Initial, Playing, Paused, Stopped};
 pub use sound_data::SoundData;
 pub use audio_controller::AudioController;
-pub use audio_tags::{AudioTags, Tags};
+pub use audio_tags::{AudioTags, Tags, write_tags};
+pub use metadata::{AudioMetadata, Metadata, BroadcastInfo, CuePoint, Instrument,
+                   Loop, LoopMode, LoopNone, LoopForward, LoopBackward,
+                   LoopAlternating, write_metadata};
 pub use recorder::Recorder;
 pub use record_context::RecordContext;
+pub use capture::{CaptureSource, CaptureSignal, CaptureSine, CaptureNoise, CaptureSilence};
+pub use oneshot::play_oneshot;
+pub use bus::Bus;
+pub use ducking::{DuckingRule, DuckingId, add_ducking_rule, remove_ducking_rule};
+pub use update::update;
+pub use marker::Marker;
+pub use sample::{SampleType, Int8, Int16, Float32};
+pub use load_options::{LoadOptions, set_default_load_options, get_default_load_options};
+pub use resampler::{Resampler, ResampleQuality, ResampleLinear, ResampleCubic, ResampleSinc,
+                    resample};
+pub use channels::{ChannelLayout, LayoutMono, LayoutStereo, LayoutRear, LayoutQuad,
+                   Layout51, Layout61, Layout71, LayoutBFormat2D, LayoutBFormat3D,
+                   Speaker, SpeakerMono, SpeakerLeft, SpeakerRight, SpeakerCenter,
+                   SpeakerLfe, SpeakerRearLeft, SpeakerRearRight, SpeakerRearCenter,
+                   SpeakerSideLeft, SpeakerSideRight, AmbisonicW, AmbisonicX,
+                   AmbisonicY, AmbisonicZ, SpeakerOther};
+pub use formats::{formats, Formats, FormatDescription, is_format_valid,
+                  get_valid_subtypes};
+pub use decoder::{Decoder, FileFormat, SndFileFormat, Mp3Format, OpusFormat, AacFormat,
+                  detect_file_format};
 
 
 // Hidden internal bindings
 mod internal;
+mod backend;
 mod openal;
 mod sndfile;
+mod decoder;
+#[cfg(feature = "mp3")]
+mod layer3;
+#[cfg(feature = "mp3")]
+mod mp3_decoder;
 
 // The public ears API
 
 #[path = "init.rs"]
 mod einit;
 pub mod listener;
+pub mod clock;
+pub mod io;
 mod sound;
 mod music;
+mod stream_source;
+mod generator;
 mod sound_data;
+mod sound_bank;
+mod loader;
+mod hot_reload;
+mod offline;
+mod software_mixer;
 mod states;
 mod audio_controller;
 mod audio_tags;
+mod metadata;
+mod marker;
+mod formats;
+mod sample;
+mod channels;
+mod resampler;
+mod load_options;
 mod recorder;
 mod record_context;
+mod capture;
+mod oneshot;
+mod bus;
+mod ducking;
+mod update;
+mod stream;
+mod source_control;
+mod playlist;
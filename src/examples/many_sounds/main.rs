@@ -25,22 +25,24 @@ extern crate ears;
 
 use std::io::timer::sleep;
 use std::time::Duration;
-use std::task;
+use std::rc::Rc;
+use std::cell::RefCell;
 
-use ears::{Sound, AudioController};
+use ears::SoundData;
 
 fn main() -> () {
     // call ears_init() function to ensure that the ears context is not destroyed by a task.
     ears::init();
 
+    // Load the samples once, all the shots share them
+    let snd_data = Rc::new(RefCell::new(SoundData::new("../res/shot.wav")
+                                        .expect("Error on SoundData loading.")));
+
     let mut i = 0u;
 
     while i < 20 {
-        task::spawn(proc() {
-            let mut snd2 = Sound::new("../res/shot.wav").expect("Error on Sound loading.");
-            snd2.play();
-            while snd2.is_playing() {}
-        });
+        // No need to keep the Sound alive, ears recycle it at the end of the playback
+        ears::play_oneshot(&snd_data, [0., 0., 0.], 1., 1.);
         i += 1;
         sleep(Duration::milliseconds(150i64));
     }
@@ -48,4 +50,4 @@ fn main() -> () {
     // Wait until the last sound is played, the main task own the ears context,
     // so we should kepp it alive
     sleep(Duration::milliseconds(900i64));
-}
\ No newline at end of file
+}
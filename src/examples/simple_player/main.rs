@@ -26,40 +26,47 @@ extern crate ears;
 use std::io::stdin;
 use std::io::stdio::flush;
 
-use ears::{Music, AudioController, Playing, Stopped, Paused};
+use ears::{Playlist, AudioController, AudioTags, Playing, Stopped, Paused};
 
 fn main() {
 
     // Read the inputs
     let mut stdin = stdin();
 
-    print!("Insert the path to an audio file : ");
+    print!("Insert the paths to audio files, separated by spaces : ");
     flush();
 
     let mut line = stdin.read_line().unwrap();
     unsafe { line.as_mut_vec().pop(); }
 
-    // Try to create the music
-    let mut music = match Music::new(line.as_slice()) {
+    // Try to create the playlist
+    let mut music = match Playlist::new() {
         Some(music) => music,
-        None        => panic!("Cannot load the music.")
+        None        => panic!("Cannot create the playlist.")
     };
+    for path in line.as_slice().words() {
+        if !music.add(path) {
+            println!("Cannot load {}.", path);
+        }
+    }
 
     // Play it
     music.play();
 
     loop {
         // Make your choice
-        println!("Commands :\n\tPlay  : l\n\tPause : p\n\tStop  : s\n\tExit  : x\n");
+        println!("Commands :\n\tPlay  : l\n\tPause : p\n\tStop  : s\n\tNext  : n\n\tPrev  : b\n\tExit  : x\n");
         match stdin.read_line().unwrap().as_slice() {
             "l\n"    => music.play(),
             "p\n"    => music.pause(),
             "s\n"    => music.stop(),
+            "n\n"    => { music.next(); },
+            "b\n"    => { music.previous(); },
             "x\n"    => { music.stop(); break; },
             _       => println!("Unknwon command.")
         }
         match music.get_state() {
-            Playing => println!("State : Playing"),
+            Playing => println!("State : Playing {}", music.get_tags().title),
             Stopped => println!("State : Stopped"),
             Paused  => println!("State : Paused"),
             _       => unreachable!()
@@ -0,0 +1,160 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Discover the audio formats supported by libsndfile.
+
+use sndfile::{SndFile, SndInfo, FormatType};
+
+/// The description of a major format or of a subtype format.
+#[deriving(Clone, PartialEq, Show)]
+pub struct FormatDescription {
+    /// The libsndfile identifier of the format
+    pub id: i32,
+    /// The format as a FormatType, None if ears doesn't know it
+    pub format: Option<FormatType>,
+    /// The name of the format, like "WAV (Microsoft)"
+    pub name: String,
+    /// The usual file extension of a major format, empty for a subtype
+    pub extension: String
+}
+
+/// The formats supported by libsndfile.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Formats {
+    /// The major formats, the containers
+    pub major: Vec<FormatDescription>,
+    /// The subtype formats, the encodings of the samples
+    pub subtypes: Vec<FormatDescription>
+}
+
+/**
+ * Get the formats supported by the linked libsndfile.
+ *
+ * # Return
+ * The major and the subtype formats with their names and extensions.
+ *
+ * # Example
+ * ```Rust
+ * for format in ears::formats().major.iter() {
+ *     println!("{} (.{})", format.name, format.extension);
+ * }
+ * ```
+ */
+pub fn formats() -> Formats {
+    Formats {
+        major: SndFile::get_formats(true),
+        subtypes: SndFile::get_formats(false)
+    }
+}
+
+/**
+ * Check if a file can be written with a combination of formats.
+ *
+ * # Arguments
+ * * `major` - The major format, like FormatWav
+ * * `subtype` - The subtype format, like FormatPcm16
+ * * `channels` - The count of channels
+ * * `samplerate` - The sample rate
+ *
+ * # Return
+ * true if libsndfile can write this combination, false otherwise.
+ */
+pub fn is_format_valid(major: FormatType,
+                       subtype: FormatType,
+                       channels: i32,
+                       samplerate: i32) -> bool {
+    is_id_valid(major as i32 | subtype as i32, channels, samplerate)
+}
+
+/**
+ * Get the subtype formats which can be written in a major format.
+ *
+ * # Arguments
+ * * `major` - The major format, like FormatWav
+ * * `channels` - The count of channels
+ * * `samplerate` - The sample rate
+ *
+ * # Return
+ * The subtypes libsndfile can write with this major format.
+ *
+ * # Example
+ * ```Rust
+ * use ears::io::FormatFlac;
+ *
+ * for subtype in ears::get_valid_subtypes(FormatFlac, 2, 44100).iter() {
+ *     println!("{}", subtype.name);
+ * }
+ * ```
+ */
+pub fn get_valid_subtypes(major: FormatType,
+                          channels: i32,
+                          samplerate: i32) -> Vec<FormatDescription> {
+    SndFile::get_formats(false).into_iter().filter(|subtype| {
+        is_id_valid(major as i32 | subtype.id, channels, samplerate)
+    }).collect()
+}
+
+fn is_id_valid(format: i32, channels: i32, samplerate: i32) -> bool {
+    let mut info = SndInfo {
+        frames: 0,
+        samplerate: samplerate,
+        channels: channels,
+        format: format,
+        sections: 0,
+        seekable: 0
+    };
+    SndFile::check_format(&mut info)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use formats::{formats, is_format_valid, get_valid_subtypes};
+    use sndfile::{FormatWav, FormatFlac, FormatPcm16, FormatVorbis};
+
+    #[test]
+    fn formats_list_OK() -> () {
+        let formats = formats();
+
+        assert!(formats.major.iter().any(|f| f.format == Some(FormatWav)));
+        assert!(formats.subtypes.iter().any(|f| f.format == Some(FormatPcm16)));
+    }
+
+    #[test]
+    fn formats_is_valid_OK() -> () {
+        assert!(is_format_valid(FormatWav, FormatPcm16, 2, 44100));
+    }
+
+    #[test]
+    fn formats_is_valid_FAIL() -> () {
+        assert!(!is_format_valid(FormatWav, FormatVorbis, 2, 44100));
+        assert!(!is_format_valid(FormatWav, FormatPcm16, 0, 44100));
+    }
+
+    #[test]
+    fn formats_valid_subtypes_OK() -> () {
+        let subtypes = get_valid_subtypes(FormatFlac, 2, 44100);
+
+        assert!(subtypes.iter().any(|f| f.format == Some(FormatPcm16)));
+        assert!(!subtypes.iter().any(|f| f.format == Some(FormatVorbis)));
+    }
+}
@@ -0,0 +1,494 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Generate test tones, beeps and noises.
+
+use std::cmp;
+use std::num::Float;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use sound_data::SoundData;
+use stream_source::{SampleSource, StreamSource};
+
+/// The length of the fades of the finite signals, in seconds.
+static FADE_SECONDS: f64 = 0.005;
+
+/// The length of a sweep without duration, in seconds.
+static ENDLESS_SWEEP_SECONDS: f64 = 1.;
+
+/// The seed of the noises, so the same noise is generated each time.
+static NOISE_SEED: u32 = 0x9E3779B9;
+
+/**
+ * The shape of a generated signal.
+ *
+ * The frequency of a Generator is ignored by the noises, the DTMF tones
+ * and the silence.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum Waveform {
+    /// A sine wave
+    SineWave,
+    /// A square wave
+    SquareWave,
+    /// A sawtooth wave, rising from -1 to 1
+    SawWave,
+    /// A triangle wave
+    TriangleWave,
+    /// A white noise, the same power at every frequency
+    WhiteNoise,
+    /// A pink noise, the same power in every octave
+    PinkNoise,
+    /// A brown noise, with more power in the low frequencies
+    BrownNoise,
+    /// A sine sweeping exponentially from the frequency of the Generator to
+    /// this frequency, during the duration of the Generator or 1 second.
+    /// The sweep is linear if one of the frequencies is 0 or negative
+    Sweep(f32),
+    /// The two tones of a key of a phone keypad: 0-9, *, # and A-D
+    Dtmf(char),
+    /// Nothing
+    Silence
+}
+
+/**
+ * Generate a signal.
+ *
+ * A Generator renders its signal in a SoundData, or streams it with a
+ * StreamSource. The samples are mono, from -amplitude to amplitude. The
+ * signals with a duration fade in and out during 5 milliseconds to avoid
+ * clicks.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use std::time::Duration;
+ * use ears::{Generator, SineWave, Dtmf, Sound, AudioController};
+ *
+ * fn main() -> () {
+ *     // A beep of 100 ms
+ *     let beep = Generator::new(SineWave, 880., 0.5, Duration::milliseconds(100));
+ *     let mut snd = Sound::new_with_data(Rc::new(RefCell::new(
+ *         beep.to_sound_data(44100).unwrap()))).unwrap();
+ *     snd.play();
+ *
+ *     // The tone of the key 5 of a phone, until stopped
+ *     let mut tone = Generator::new_endless(Dtmf('5'), 0., 0.5).to_stream().unwrap();
+ *     tone.play();
+ * }
+ * ```
+ */
+#[deriving(Clone)]
+pub struct Generator {
+    waveform: Waveform,
+    frequency: f32,
+    amplitude: f32,
+    duration: Option<Duration>,
+    /// The count of generated frames
+    position: u64,
+    /// The phases of the oscillators, from 0 to 1
+    phases: [f64, ..2],
+    /// The state of the pseudo random generator
+    seed: u32,
+    /// The state of the pink noise filter
+    pink: [f32, ..7],
+    /// The state of the brown noise
+    brown: f32
+}
+
+impl Generator {
+    /**
+     * Create a Generator with a duration.
+     *
+     * # Arguments
+     * * `waveform` - The shape of the signal
+     * * `frequency` - The frequency of the signal in Hz
+     * * `amplitude` - The amplitude of the signal, from 0 to 1
+     * * `duration` - The duration of the signal
+     */
+    pub fn new(waveform: Waveform,
+               frequency: f32,
+               amplitude: f32,
+               duration: Duration) -> Generator {
+        let mut generator = Generator::new_endless(waveform, frequency, amplitude);
+        generator.duration = Some(duration);
+        generator
+    }
+
+    /**
+     * Create a Generator without end.
+     *
+     * An endless Generator can only be streamed.
+     *
+     * # Arguments
+     * * `waveform` - The shape of the signal
+     * * `frequency` - The frequency of the signal in Hz
+     * * `amplitude` - The amplitude of the signal, from 0 to 1
+     */
+    pub fn new_endless(waveform: Waveform, frequency: f32, amplitude: f32) -> Generator {
+        match waveform {
+            Dtmf(key) if dtmf_frequencies(key).is_none() => {
+                println!("Error: {} is not a DTMF key, the Generator is silent.", key);
+            },
+            _                                            => {}
+        }
+        Generator {
+            waveform: waveform,
+            frequency: frequency,
+            amplitude: amplitude.max(0.).min(1.),
+            duration: None,
+            position: 0,
+            phases: [0., ..2],
+            seed: NOISE_SEED,
+            pink: [0., ..7],
+            brown: 0.
+        }
+    }
+
+    /// Get the shape of the signal.
+    pub fn get_waveform(&self) -> Waveform {
+        self.waveform.clone()
+    }
+
+    /// Get the frequency of the signal in Hz.
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Get the amplitude of the signal.
+    pub fn get_amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    /// Get the duration of the signal, None if it's endless.
+    pub fn get_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /**
+     * Get the count of frames of the signal.
+     *
+     * # Argument
+     * * `samplerate` - The sample rate of the frames
+     *
+     * # Return
+     * Some(frames), None if the signal is endless.
+     */
+    pub fn get_frames(&self, samplerate: i32) -> Option<u64> {
+        self.duration.map(|duration| {
+            let ms = cmp::max(duration.num_milliseconds(), 0) as u64;
+            ms * samplerate as u64 / 1000
+        })
+    }
+
+    /**
+     * Render the whole signal.
+     *
+     * # Argument
+     * * `samplerate` - The sample rate of the samples
+     *
+     * # Return
+     * Some(samples) from the beginning of the signal, None if the signal is
+     * endless.
+     */
+    pub fn render(&self, samplerate: i32) -> Option<Vec<f32>> {
+        let frames = match self.get_frames(samplerate) {
+            Some(frames) => frames as uint,
+            None         => {
+                println!("Error: an endless Generator can't be rendered.");
+                return None;
+            }
+        };
+        let mut generator = self.clone();
+        generator.rewind();
+        let mut samples = Vec::from_elem(frames, 0f32);
+        generator.fill(samples.as_mut_slice(), samplerate);
+        Some(samples)
+    }
+
+    /**
+     * Render the whole signal in a SoundData.
+     *
+     * # Argument
+     * * `samplerate` - The sample rate of the samples
+     *
+     * # Return
+     * Some(SoundData), None if the signal is endless or if the SoundData
+     * can't be created.
+     */
+    pub fn to_sound_data(&self, samplerate: i32) -> Option<SoundData> {
+        match self.render(samplerate) {
+            Some(samples) => SoundData::from_samples(samples.as_slice(), 1, samplerate),
+            None          => None
+        }
+    }
+
+    /**
+     * Stream the signal at 44100 Hz.
+     *
+     * # Return
+     * Some(StreamSource), None if the StreamSource can't be created.
+     */
+    pub fn to_stream(self) -> Option<StreamSource> {
+        StreamSource::new(box self)
+    }
+
+    /// Get the next pseudo random value, from -1 to 1.
+    fn next_random(&mut self) -> f32 {
+        // xorshift32
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed as f64 / 4294967295. * 2. - 1.) as f32
+    }
+
+    /// Advance an oscillator, return its phase before the step.
+    fn step(&mut self, oscillator: uint, frequency: f64, samplerate: i32) -> f64 {
+        let phase = self.phases[oscillator];
+        self.phases[oscillator] = (phase + frequency / samplerate as f64) % 1.;
+        phase
+    }
+
+    /// Generate the next sample, without the amplitude and the fades.
+    fn next_sample(&mut self, samplerate: i32) -> f32 {
+        let frequency = self.frequency as f64;
+        match self.waveform.clone() {
+            SineWave     => (self.step(0, frequency, samplerate) * 2. * PI).sin() as f32,
+            SquareWave   => if self.step(0, frequency, samplerate) < 0.5 { 1. } else { -1. },
+            SawWave      => (self.step(0, frequency, samplerate) * 2. - 1.) as f32,
+            TriangleWave => (1. - 4. * (self.step(0, frequency, samplerate) - 0.5).abs()) as f32,
+            WhiteNoise   => self.next_random(),
+            PinkNoise    => {
+                // Paul Kellet's refined filter
+                let white = self.next_random();
+                let b = &mut self.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.1538520;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+                (pink * 0.11).max(-1.).min(1.)
+            },
+            BrownNoise   => {
+                let white = self.next_random();
+                self.brown = ((self.brown + 0.02 * white) / 1.02).max(-1.).min(1.);
+                (self.brown * 3.5).max(-1.).min(1.)
+            },
+            Sweep(end)   => {
+                let length = match self.get_frames(samplerate) {
+                    Some(frames) if frames > 0 => frames as f64 / samplerate as f64,
+                    _                          => ENDLESS_SWEEP_SECONDS
+                };
+                let time = (self.position as f64 / samplerate as f64) % length;
+                let end = end as f64;
+                let current = if frequency > 0. && end > 0. {
+                    frequency * (end / frequency).powf(time / length)
+                } else {
+                    // An exponential sweep can't start or end at 0 Hz
+                    let start = frequency.max(0.);
+                    start + (end.max(0.) - start) * time / length
+                };
+                (self.step(0, current, samplerate) * 2. * PI).sin() as f32
+            },
+            Dtmf(key)    => match dtmf_frequencies(key) {
+                Some((low, high)) => {
+                    let low = (self.step(0, low, samplerate) * 2. * PI).sin();
+                    let high = (self.step(1, high, samplerate) * 2. * PI).sin();
+                    ((low + high) * 0.5) as f32
+                },
+                None              => 0.
+            },
+            Silence      => 0.
+        }
+    }
+
+    /// Get the gain of the fades at the current position.
+    fn get_fade(&self, samplerate: i32) -> f32 {
+        let frames = match self.get_frames(samplerate) {
+            Some(frames) => frames as f64,
+            None         => return 1.
+        };
+        let fade = FADE_SECONDS * samplerate as f64;
+        let position = self.position as f64;
+        let gain = (position / fade).min((frames - position) / fade).min(1.);
+        gain.max(0.) as f32
+    }
+}
+
+impl SampleSource for Generator {
+    fn fill(&mut self, buffer: &mut [f32], samplerate: i32) -> uint {
+        let frames = match self.get_frames(samplerate) {
+            Some(total) => cmp::min(buffer.len() as u64, total - cmp::min(total, self.position)) as uint,
+            None        => buffer.len()
+        };
+        for sample in buffer.slice_to_mut(frames).iter_mut() {
+            let fade = self.get_fade(samplerate);
+            *sample = self.next_sample(samplerate) * self.amplitude * fade;
+            self.position += 1;
+        }
+        frames
+    }
+
+    fn rewind(&mut self) -> () {
+        self.position = 0;
+        self.phases = [0., ..2];
+        self.seed = NOISE_SEED;
+        self.pink = [0., ..7];
+        self.brown = 0.;
+    }
+}
+
+/**
+ * Get the frequencies of a key of a phone keypad.
+ *
+ * # Return
+ * Some((low, high)) in Hz, None if the key is not on the keypad.
+ */
+fn dtmf_frequencies(key: char) -> Option<(f64, f64)> {
+    let (row, column) = match key {
+        '1' => (0u, 0u), '2' => (0, 1), '3' => (0, 2), 'A' => (0, 3),
+        '4' => (1, 0),   '5' => (1, 1), '6' => (1, 2), 'B' => (1, 3),
+        '7' => (2, 0),   '8' => (2, 1), '9' => (2, 2), 'C' => (2, 3),
+        '*' => (3, 0),   '0' => (3, 1), '#' => (3, 2), 'D' => (3, 3),
+        _   => return None
+    };
+    let rows = [697., 770., 852., 941.];
+    let columns = [1209., 1336., 1477., 1633.];
+    Some((rows[row], columns[column]))
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::num::Float;
+    use std::time::Duration;
+    use stream_source::SampleSource;
+    use generator::{Generator, dtmf_frequencies, SineWave, SquareWave, WhiteNoise, PinkNoise,
+                    BrownNoise, Sweep, Dtmf, Silence};
+
+    #[test]
+    fn generator_sine_OK() -> () {
+        let mut generator = Generator::new_endless(SineWave, 100., 0.5);
+        let mut samples = [0f32, ..100];
+        assert_eq!(generator.fill(samples.as_mut_slice(), 400), 100);
+        assert!(samples[0].abs() < 0.0001);
+        assert!((samples[1] - 0.5).abs() < 0.0001);
+        assert!((samples[3] + 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn generator_amplitude_clamped_OK() -> () {
+        assert_eq!(Generator::new_endless(SineWave, 100., 2.).get_amplitude(), 1.);
+        assert_eq!(Generator::new_endless(SineWave, 100., -0.5).get_amplitude(), 0.);
+        let generator = Generator::new(SquareWave, 100., 1.5, Duration::milliseconds(10));
+        assert_eq!(generator.get_amplitude(), 1.);
+    }
+
+    #[test]
+    fn generator_square_OK() -> () {
+        let mut generator = Generator::new_endless(SquareWave, 100., 1.);
+        let mut samples = [0f32, ..4];
+        generator.fill(samples.as_mut_slice(), 400);
+        assert_eq!(samples, [1., 1., -1., -1.]);
+    }
+
+    #[test]
+    fn generator_duration_OK() -> () {
+        let generator = Generator::new(SineWave, 440., 1., Duration::milliseconds(250));
+        let samples = generator.render(1000).unwrap();
+        assert_eq!(samples.len(), 250);
+        // Faded in and out
+        assert_eq!(samples[0], 0.);
+        assert!(samples[249].abs() < 0.25);
+    }
+
+    #[test]
+    fn generator_endless_render_FAIL() -> () {
+        assert!(Generator::new_endless(SineWave, 440., 1.).render(44100).is_none());
+    }
+
+    #[test]
+    fn generator_noises_bounded_OK() -> () {
+        for waveform in [WhiteNoise, PinkNoise, BrownNoise].iter() {
+            let mut generator = Generator::new_endless(waveform.clone(), 0., 1.);
+            let mut samples = [0f32, ..4096];
+            generator.fill(samples.as_mut_slice(), 44100);
+            assert!(samples.iter().all(|s| *s >= -1. && *s <= 1.));
+            assert!(samples.iter().any(|s| *s != 0.));
+        }
+    }
+
+    #[test]
+    fn generator_noise_rewind_OK() -> () {
+        let mut generator = Generator::new_endless(WhiteNoise, 0., 1.);
+        let mut first = [0f32, ..16];
+        let mut second = [0f32, ..16];
+        generator.fill(first.as_mut_slice(), 44100);
+        generator.rewind();
+        generator.fill(second.as_mut_slice(), 44100);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn generator_sweep_OK() -> () {
+        let generator = Generator::new(Sweep(4000.), 100., 1., Duration::seconds(1));
+        let samples = generator.render(8000).unwrap();
+        let crossings = |s: &[f32]| s.windows(2).filter(|w| w[0] < 0. && w[1] >= 0.).count();
+        assert!(crossings(samples.slice(0, 800)) < crossings(samples.slice(7200, 8000)));
+    }
+
+    #[test]
+    fn generator_sweep_from_zero_OK() -> () {
+        let generator = Generator::new(Sweep(4000.), 0., 1., Duration::seconds(1));
+        let samples = generator.render(8000).unwrap();
+        assert!(samples.iter().all(|s| !s.is_nan()));
+        let crossings = |s: &[f32]| s.windows(2).filter(|w| w[0] < 0. && w[1] >= 0.).count();
+        assert!(crossings(samples.slice(0, 800)) < crossings(samples.slice(7200, 8000)));
+    }
+
+    #[test]
+    fn generator_dtmf_OK() -> () {
+        assert_eq!(dtmf_frequencies('5'), Some((770., 1336.)));
+        assert_eq!(dtmf_frequencies('#'), Some((941., 1477.)));
+        assert_eq!(dtmf_frequencies('x'), None);
+        let mut generator = Generator::new_endless(Dtmf('1'), 0., 1.);
+        let mut samples = [0f32, ..64];
+        generator.fill(samples.as_mut_slice(), 8000);
+        assert!(samples.iter().any(|s| *s != 0.));
+    }
+
+    #[test]
+    fn generator_silence_OK() -> () {
+        let generator = Generator::new(Silence, 440., 1., Duration::milliseconds(10));
+        assert!(generator.render(44100).unwrap().iter().all(|s| *s == 0.));
+    }
+
+    #[test]
+    fn generator_to_sound_data_OK() -> () {
+        let generator = Generator::new(SineWave, 440., 0.5, Duration::milliseconds(100));
+        assert!(generator.to_sound_data(44100).is_some());
+    }
+}
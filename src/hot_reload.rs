@@ -0,0 +1,516 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Reload the sounds when their files change.
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::io::fs;
+use std::mem;
+use time;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use sound_data::SoundData;
+use sound_data;
+use stream::{StreamProvider, FileProvider, ReloadedFile};
+use marker::{load_markers, sidecar_path};
+use decoder::Decoder;
+use decoder;
+use load_options::LoadOptions;
+
+/// The minimum time between two checks of the files, in nanoseconds.
+static POLL_INTERVAL: u64 = 500000000;
+
+local_data_key!(watcher: RefCell<Watcher>)
+
+/**
+ * A reload of a watched file.
+ *
+ * * Reloaded - The file was reloaded, with its path
+ * * ReloadFailed - The file can't be reloaded, with its path and the error.
+ * The previous samples keep playing.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum ReloadEvent {
+    /// The file was reloaded
+    Reloaded(String),
+    /// The file can't be reloaded
+    ReloadFailed(String, String)
+}
+
+/// A SoundData to reload.
+struct WatchedData {
+    data: Weak<RefCell<SoundData>>
+}
+
+/// A Music to reload.
+struct WatchedMusic {
+    options: LoadOptions,
+    sender: Sender<ReloadedFile>
+}
+
+/// The modification time and the size of a file and of its markers sidecar.
+#[deriving(Clone, PartialEq)]
+struct Stamp {
+    file: (u64, u64),
+    sidecar: Option<(u64, u64)>
+}
+
+/// A watched file.
+struct WatchedFile {
+    path: String,
+    /// The stamp of the loaded file
+    stamp: Option<Stamp>,
+    /// The stamp of a change, reloaded once the file stops changing
+    change: Option<Stamp>,
+    datas: Vec<WatchedData>,
+    musics: Vec<WatchedMusic>
+}
+
+struct Watcher {
+    enabled: bool,
+    last_poll: u64,
+    files: Vec<WatchedFile>,
+    events: Vec<ReloadEvent>
+}
+
+/// Call a function with the watcher of the task.
+fn with_watcher<T>(f: |&mut Watcher| -> T) -> T {
+    if watcher.get().is_none() {
+        watcher.replace(Some(RefCell::new(Watcher {
+            enabled: false,
+            last_poll: 0,
+            files: Vec::new(),
+            events: Vec::new()
+        })));
+    }
+    let state = watcher.get().unwrap();
+    let mut state = state.borrow_mut();
+    f(&mut *state)
+}
+
+/// Get the modification time and the size of a file.
+fn stat(path: &str) -> Option<(u64, u64)> {
+    match fs::stat(&Path::new(path)) {
+        Ok(stat) => Some((stat.modified, stat.size)),
+        Err(_)   => None
+    }
+}
+
+/// Get the stamp of a file and of its markers sidecar, None without file.
+fn stamp(path: &str) -> Option<Stamp> {
+    stat(path).map(|file| Stamp {
+        file: file,
+        sidecar: stat(sidecar_path(path).as_slice())
+    })
+}
+
+impl Watcher {
+    /// Get the watched file of a path, watch it if needed.
+    fn file<'r>(&'r mut self, path: &str) -> &'r mut WatchedFile {
+        let index = match self.files.iter().position(|file| file.path.as_slice() == path) {
+            Some(index) => index,
+            None        => {
+                self.files.push(WatchedFile {
+                    path: path.to_string(),
+                    stamp: stamp(path),
+                    change: None,
+                    datas: Vec::new(),
+                    musics: Vec::new()
+                });
+                self.files.len() - 1
+            }
+        };
+        &mut self.files.as_mut_slice()[index]
+    }
+}
+
+/**
+ * Enable the reload of the sounds when their files change.
+ *
+ * When enabled, the files of the Sounds and the Musics created afterwards
+ * are checked by `ears::update` twice a second, with their markers sidecar
+ * files. The samples and the markers of the Sounds are replaced under all
+ * the sources playing them, one-shot sounds included, and the Sounds playing
+ * or paused keep their position. The Musics continue with the new file from its beginning. The
+ * reloads are reported by `poll_reload_events`.
+ *
+ * Hot reload is meant for development, it's disabled by default.
+ *
+ * # Argument
+ * * `enabled` - true to watch the files, false to stop watching them
+ *
+ * # Example
+ * ```Rust
+ * ears::set_hot_reload(true);
+ * let mut snd = Sound::new("res/shot.wav").unwrap();
+ *
+ * loop {
+ *     ears::update();
+ *     for event in ears::poll_reload_events().iter() {
+ *         println!("{}", event);
+ *     }
+ * }
+ * ```
+ */
+pub fn set_hot_reload(enabled: bool) -> () {
+    with_watcher(|watcher| {
+        watcher.enabled = enabled;
+        if !enabled {
+            watcher.files.clear();
+        }
+    })
+}
+
+/// Check if the files are watched.
+pub fn is_hot_reload_enabled() -> bool {
+    with_watcher(|watcher| watcher.enabled)
+}
+
+/**
+ * Get the reloads since the last call.
+ *
+ * # Return
+ * The ReloadEvents, oldest first.
+ */
+pub fn poll_reload_events() -> Vec<ReloadEvent> {
+    with_watcher(|watcher| {
+        let events = watcher.events.clone();
+        watcher.events.clear();
+        events
+    })
+}
+
+/**
+ * Watch the file of a SoundData.
+ */
+#[doc(hidden)]
+pub fn watch_sound(data: &Rc<RefCell<SoundData>>) -> () {
+    with_watcher(|watcher| {
+        if !watcher.enabled {
+            return;
+        }
+        let path = sound_data::get_path(&*data.borrow()).to_string();
+        // The SoundDatas created from samples have no file
+        if path.is_empty() {
+            return;
+        }
+        let ptr = &**data as *const RefCell<SoundData>;
+        let file = watcher.file(path.as_slice());
+        let watched = file.datas.iter().any(|watched| match watched.data.upgrade() {
+            Some(ref d) => &**d as *const RefCell<SoundData> == ptr,
+            None        => false
+        });
+        if !watched {
+            file.datas.push(WatchedData { data: data.downgrade() });
+        }
+    })
+}
+
+/**
+ * Watch the file of a Music.
+ *
+ * # Return
+ * Some(Receiver) to receive the providers and the markers of the reloaded
+ * file if hot reload is enabled, None otherwise.
+ */
+#[doc(hidden)]
+pub fn watch_music(path: &str,
+                   options: &LoadOptions) -> Option<Receiver<ReloadedFile>> {
+    with_watcher(|watcher| {
+        if !watcher.enabled {
+            return None;
+        }
+        let (sender, receiver) = channel();
+        watcher.file(path).musics.push(WatchedMusic {
+            options: options.clone(),
+            sender: sender
+        });
+        Some(receiver)
+    })
+}
+
+/// Replace the samples of a SoundData under all the sources bound to it.
+fn reload_data(data: &Rc<RefCell<SoundData>>) -> Result<(), String> {
+    let (path, options) = {
+        let data = data.borrow();
+        (sound_data::get_path(&*data).to_string(), sound_data::get_options(&*data))
+    };
+    let new_data = match SoundData::new_with_options(path.as_slice(), &options) {
+        Some(new_data) => new_data,
+        None           => return Err(format!("Cannot load {}.", path))
+    };
+    let buffer = sound_data::get_buffer(&new_data);
+
+    let frames = sound_data::get_sndinfo(&new_data).frames;
+
+    // The buffer of a source can only change when the source is stopped
+    let sources = sound_data::get_sources(&*data.borrow()).clone();
+    let mut states = Vec::new();
+    for &source in sources.iter() {
+        let state = al::alGetState(source);
+        let mut offset = 0;
+        al::alGetSourcei(source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+        al::alSourceStop(source);
+        al::alSourcei(source, ffi::AL_BUFFER, buffer as i32);
+        states.push((source, state, offset));
+    }
+    // Drop the previous samples, now detached from the sources
+    sound_data::replace(&mut *data.borrow_mut(), new_data);
+
+    // Continue at the same offset, the paused sources stay paused
+    for &(source, state, offset) in states.iter() {
+        if state != ffi::AL_PLAYING && state != ffi::AL_PAUSED {
+            continue;
+        }
+        if offset as i64 >= frames {
+            if state == ffi::AL_PAUSED {
+                al::alSourceRewind(source);
+            }
+            continue;
+        }
+        al::alSourcePlay(source);
+        al::alSourcei(source, ffi::AL_SAMPLE_OFFSET, offset);
+        if state == ffi::AL_PAUSED {
+            al::alSourcePause(source);
+        }
+    }
+    Ok(())
+}
+
+/// Reload a changed file.
+fn reload(file: &mut WatchedFile) -> Vec<ReloadEvent> {
+    let mut events = Vec::new();
+    let path = file.path.clone();
+
+    file.datas.retain(|watched| watched.data.upgrade().is_some());
+    for watched in file.datas.iter() {
+        let data = match watched.data.upgrade() {
+            Some(data) => data,
+            None       => continue
+        };
+        events.push(match reload_data(&data) {
+            Ok(_)    => Reloaded(path.clone()),
+            Err(err) => ReloadFailed(path.clone(), err)
+        });
+    }
+
+    let mut musics = Vec::new();
+    for music in mem::replace(&mut file.musics, Vec::new()).into_iter() {
+        let (provider, markers) = match decoder::open(path.as_slice()) {
+            Ok(file)     => {
+                let markers = load_markers(path.as_slice(), &file.get_metadata());
+                (FileProvider::new(file, &music.options), markers)
+            },
+            Err(err)     => {
+                events.push(ReloadFailed(path.clone(), err));
+                musics.push(music);
+                continue;
+            }
+        };
+        match provider {
+            Some(provider) => {
+                // The Music is dropped if its stream is gone
+                let provider = box provider as Box<StreamProvider + Send>;
+                if music.sender.send_opt((provider, markers)).is_ok() {
+                    events.push(Reloaded(path.clone()));
+                    musics.push(music);
+                }
+            },
+            None           => {
+                events.push(ReloadFailed(path.clone(),
+                                         "Internal error : unrecognized format.".to_string()));
+                musics.push(music);
+            }
+        }
+    }
+    file.musics = musics;
+    events
+}
+
+/**
+ * Reload the watched files changed since the last check.
+ *
+ * Called by `ears::update`.
+ */
+#[doc(hidden)]
+pub fn process() -> () {
+    check_openal_context!(());
+
+    let now = time::precise_time_ns();
+    let events = with_watcher(|watcher| {
+        let mut events = Vec::new();
+        if !watcher.enabled || now - watcher.last_poll < POLL_INTERVAL {
+            return events;
+        }
+        watcher.last_poll = now;
+
+        for file in watcher.files.iter_mut() {
+            let current = stamp(file.path.as_slice());
+            if current.is_none() || current == file.stamp {
+                file.change = None;
+                continue;
+            }
+            // Wait for the file to stop changing before reloading it
+            if file.change != current {
+                file.change = current;
+                continue;
+            }
+            file.stamp = current;
+            file.change = None;
+            events.push_all(reload(file).as_slice());
+        }
+        watcher.files.retain(|file| !file.datas.is_empty() || !file.musics.is_empty());
+        events
+    });
+
+    for event in events.iter() {
+        match *event {
+            ReloadFailed(ref path, ref err) => println!("ears: cannot reload {} : {}", path, err),
+            _                               => {}
+        }
+    }
+    with_watcher(|watcher| watcher.events.push_all(events.as_slice()));
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::{File, TempDir};
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    use hot_reload::{stamp, set_hot_reload, is_hot_reload_enabled,
+                     poll_reload_events, process, with_watcher, Reloaded};
+    use openal::{ffi, al};
+    use sound_data::SoundData;
+    use oneshot::play_oneshot;
+    use io::{SoundReader, SoundWriter, FormatWav, FormatPcm16};
+    use sound::Sound;
+    use sound_data;
+    use marker::Marker;
+    use states::Paused;
+    use audio_controller::AudioController;
+
+    fn create_file(path: &str, frames: uint) -> () {
+        let mut info = SoundReader::open("res/shot.wav").unwrap().get_info();
+        info.channels = 1;
+        info.format = (FormatWav | FormatPcm16) as i32;
+        let mut writer = SoundWriter::create(path, info).unwrap();
+        writer.write(Vec::from_elem(frames, 0i16).as_slice()).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn hot_reload_stamp_OK() -> () {
+        assert!(stamp("res/shot.wav").is_some());
+        assert!(stamp("toto.wav").is_none());
+    }
+
+    #[test]
+    fn hot_reload_enable_OK() -> () {
+        assert!(!is_hot_reload_enabled());
+        set_hot_reload(true);
+        assert!(is_hot_reload_enabled());
+        assert!(poll_reload_events().is_empty());
+    }
+
+    #[test]
+    fn hot_reload_reload_OK() -> () {
+        let dir = TempDir::new("ears_hot_reload").unwrap();
+        let path = dir.path().join("sound.wav");
+        let path = path.as_str().unwrap();
+        create_file(path, 1000);
+
+        set_hot_reload(true);
+        let mut snd = Sound::new(path).unwrap();
+        snd.play();
+        snd.pause();
+
+        create_file(path, 2000);
+        File::create(&dir.path().join("sound.wav.markers")).write_str("50 hit\n").unwrap();
+        // The change is reloaded once the file stops changing
+        process();
+        with_watcher(|watcher| watcher.last_poll = 0);
+        process();
+
+        assert_eq!(poll_reload_events(), vec![Reloaded(path.to_string())]);
+        assert_eq!(sound_data::get_sndinfo(&*snd.get_datas().borrow()).frames, 2000);
+        assert_eq!(snd.get_markers(), vec![Marker::new(50, "hit")]);
+        assert_eq!(snd.get_state() as i32, Paused as i32);
+    }
+
+    #[test]
+    fn hot_reload_sidecar_OK() -> () {
+        let dir = TempDir::new("ears_hot_reload").unwrap();
+        let path = dir.path().join("sound.wav");
+        let path = path.as_str().unwrap();
+        create_file(path, 1000);
+
+        set_hot_reload(true);
+        let snd = Sound::new(path).unwrap();
+
+        // Only the markers change
+        File::create(&dir.path().join("sound.wav.markers")).write_str("50 hit\n").unwrap();
+        process();
+        with_watcher(|watcher| watcher.last_poll = 0);
+        process();
+
+        assert_eq!(poll_reload_events(), vec![Reloaded(path.to_string())]);
+        assert_eq!(snd.get_markers(), vec![Marker::new(50, "hit")]);
+    }
+
+    #[test]
+    fn hot_reload_all_sources_OK() -> () {
+        let dir = TempDir::new("ears_hot_reload").unwrap();
+        let path = dir.path().join("sound.wav");
+        let path = path.as_str().unwrap();
+        create_file(path, 1000);
+
+        let data = Rc::new(RefCell::new(SoundData::new(path).unwrap()));
+        // Created before hot reload is enabled
+        let mut before = Sound::new_with_data(data.clone()).unwrap();
+        set_hot_reload(true);
+        let after = Sound::new_with_data(data.clone()).unwrap();
+        assert!(play_oneshot(&data, [0., 0., 0.], 1., 1.));
+        before.play();
+        assert_eq!(sound_data::get_sources(&*data.borrow()).len(), 3);
+
+        create_file(path, 2000);
+        process();
+        with_watcher(|watcher| watcher.last_poll = 0);
+        process();
+
+        assert_eq!(poll_reload_events(), vec![Reloaded(path.to_string())]);
+        // The previous buffer was deleted without error
+        assert!(al::openal_has_error().is_none());
+        let buffer = sound_data::get_buffer(&*data.borrow());
+        for &source in sound_data::get_sources(&*data.borrow()).iter() {
+            let mut bound = 0;
+            al::alGetSourcei(source, ffi::AL_BUFFER, &mut bound);
+            assert_eq!(bound as u32, buffer);
+        }
+        assert!(SoundData::new(path).is_some());
+        drop(after);
+    }
+}
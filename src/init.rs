@@ -29,7 +29,8 @@
  */
 
 use record_context::RecordContext;
-use internal::OpenAlData;
+use internal::{OpenAlData, OutputBackend};
+use internal;
 
 /**
  * Initialize the internal context
@@ -52,6 +53,63 @@ pub fn init() -> bool {
     }
 }
 
+/**
+ * Initialize the internal context on a specific backend
+ *
+ * Use NullOutput to run ears without audio hardware: the sounds are mixed
+ * then discarded in real time, and the input context captures silence or
+ * the input given to ears::set_null_input.
+ * Must be called before any other use of ears in the task.
+ *
+ * # Argument
+ * * `backend` - The backend of the output device
+ *
+ * # Return
+ * true if initialization is made with success, false otherwise
+ *
+ * # Example
+ * ```Rust
+ * match ears::init_with(ears::NullOutput) {
+ *     true  => { do_stuff() },
+ *     false => panic!("ears init error")
+ * }
+ * ```
+ */
+pub fn init_with(backend: OutputBackend) -> bool {
+    match internal::get_context_backend() {
+        Some(ref current) if *current != backend => {
+            println!("Error: ears is already initialized with the {} backend.", current);
+            return false;
+        },
+        Some(_)                                  => return true,
+        None                                     => {}
+    }
+    internal::set_output_backend(backend);
+    init()
+}
+
+/**
+ * Set the input captured with the NullOutput backend
+ *
+ * By default the input context of the NullOutput backend captures silence,
+ * or the file of the EARS_INPUT environment variable if it is set. Use this
+ * function to capture an audio file or a generated signal instead.
+ * Must be called before ears::init_in.
+ *
+ * # Argument
+ * * `context` - The context to return from ears::init_in
+ *
+ * # Example
+ * ```Rust
+ * ears::init_with(ears::NullOutput);
+ * ears::set_null_input(ears::RecordContext::from_signal(ears::CaptureSine(440., 0.5)));
+ * let context = ears::init_in().unwrap();
+ * ```
+ */
+pub fn set_null_input(context: RecordContext) -> () {
+    internal::set_null_input(context)
+}
+
 /**
  * Initialize the input device context
  *
@@ -79,6 +137,12 @@ mod test {
 
     use init;
     use init_in;
+    use init_with;
+    use set_null_input;
+    use internal::{NullOutput, DefaultOutput};
+    use recorder::Recorder;
+    use record_context::RecordContext;
+    use capture::CaptureSine;
 
     #[test]
     fn test_init_ears_OK() -> () {
@@ -86,14 +150,12 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn test_init_in_with_normal_init_OK() -> () {
         init();
         assert!(init_in().is_some())
     }
 
     #[test]
-    #[ignore]
     fn test_init_in_alone_OK() -> () {
         assert!(init_in().is_some())
     }
@@ -105,4 +167,37 @@ mod test {
             assert_eq!(init_in(), None)
         })
     }
+
+    #[test]
+    fn test_init_with_null_output_OK() -> () {
+        assert_eq!(init_with(NullOutput), true)
+    }
+
+    #[test]
+    fn test_init_with_other_backend_FAIL() -> () {
+        init_with(NullOutput);
+        assert_eq!(init_with(DefaultOutput), false)
+    }
+
+    #[test]
+    fn test_init_in_with_null_output_OK() -> () {
+        init_with(NullOutput);
+        assert!(init_in().is_some())
+    }
+
+    #[test]
+    fn test_set_null_input_OK() -> () {
+        let context = RecordContext::from_signal(CaptureSine(440., 0.5));
+        init_with(NullOutput);
+        set_null_input(context.clone());
+        assert_eq!(init_in(), Some(context))
+    }
+
+    #[test]
+    fn test_record_with_null_output_OK() -> () {
+        init_with(NullOutput);
+        let mut recorder = Recorder::new(init_in().unwrap());
+        recorder.start();
+        assert_eq!(recorder.stop(), true)
+    }
 }
@@ -30,18 +30,200 @@
 #![allow(raw_pointer_deriving)]
 
 use std::cell::RefCell;
-use std::ptr;
-use openal::ffi;
+use std::{ptr, mem, os};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, SeqCst};
+use std::io::timer::sleep;
+use std::time::Duration;
+use libc::{c_char, c_void};
+use time;
+use openal::{ffi, al};
 use record_context;
+use backend;
 use record_context::RecordContext;
+use capture::CaptureSilence;
 
 local_data_key!(al_context: RefCell<Box<OpenAlData>>)
+local_data_key!(requested_backend: OutputBackend)
+local_data_key!(requested_null_input: RecordContext)
+
+type LoopbackOpenDeviceSOFT = extern "C" fn(*const c_char) -> *mut ffi::ALCdevice;
+pub type RenderSamplesSOFT = extern "C" fn(*mut ffi::ALCdevice, *mut c_void, i32);
+
+/// The sample rate of the null output.
+static NULL_SAMPLERATE: i32 = 44100;
+
+/// The count of frames the null output renders at once, 10 milliseconds.
+static NULL_PERIOD_FRAMES: uint = 441;
+
+/**
+ * The device used to create the OpenAL context.
+ *
+ * The NullOutput backend doesn't need any audio hardware, the samples are
+ * mixed then discarded in real time. It allows to use ears on machines
+ * without sound card, like the continuous integration servers.
+ *
+ * The NullOutput backend is also used when the EARS_OUTPUT environment
+ * variable is set to "null", and by default in the tests of ears.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum OutputBackend {
+    /// The default output device of the system.
+    DefaultOutput,
+    /// A device which discards the samples.
+    NullOutput
+}
 
-#[deriving(Clone)]
 pub struct OpenAlData {
     al_context: *mut ffi::ALCcontext,
     al_device: *mut ffi::ALCdevice,
-    al_capt_device: *mut ffi::ALCdevice
+    al_capt_device: *mut ffi::ALCdevice,
+    backend: OutputBackend,
+    pacer: Option<NullPacer>
+}
+
+/**
+ * Consume the samples of a loopback device in real time.
+ *
+ * Used by the null output when the OpenAL implementation has no null device,
+ * so the sources advance like on a sound card.
+ */
+struct NullPacer {
+    stop: Arc<AtomicBool>,
+    running: Arc<AtomicBool>
+}
+
+impl NullPacer {
+    fn new(device: *mut ffi::ALCdevice, render_samples: RenderSamplesSOFT) -> NullPacer {
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        let t_stop = stop.clone();
+        let t_running = running.clone();
+
+        spawn(proc() {
+            let mut samples = Vec::from_elem(NULL_PERIOD_FRAMES * 2, 0i16);
+            let start = time::precise_time_ns();
+            let mut rendered = 0u64;
+            while !t_stop.load(SeqCst) {
+                // Render every frame due since the start, so the time spent
+                // mixing doesn't accumulate as a drift.
+                let elapsed = time::precise_time_ns() - start;
+                let due = elapsed * NULL_SAMPLERATE as u64 / 1_000_000_000;
+                while rendered + NULL_PERIOD_FRAMES as u64 <= due {
+                    render_samples(device,
+                                   samples.as_mut_ptr() as *mut c_void,
+                                   NULL_PERIOD_FRAMES as i32);
+                    rendered += NULL_PERIOD_FRAMES as u64;
+                }
+
+                // Sleep until the next period is due.
+                let next = (rendered + NULL_PERIOD_FRAMES as u64) * 1_000_000_000
+                    / NULL_SAMPLERATE as u64;
+                let now = time::precise_time_ns() - start;
+                if next > now {
+                    sleep(Duration::microseconds(((next - now) / 1000) as i64));
+                }
+            }
+            t_running.store(false, SeqCst);
+        });
+
+        NullPacer {
+            stop: stop,
+            running: running
+        }
+    }
+}
+
+impl Drop for NullPacer {
+    fn drop(&mut self) {
+        self.stop.store(true, SeqCst);
+        while self.running.load(SeqCst) {
+            sleep(Duration::milliseconds(1i64));
+        }
+    }
+}
+
+/**
+ * Set the backend used when the context is created.
+ *
+ * Private function, use ears::init_with.
+ */
+pub fn set_output_backend(backend: OutputBackend) -> () {
+    requested_backend.replace(Some(backend));
+}
+
+/**
+ * Set the input captured by the input context of the null output.
+ *
+ * Private function, use ears::set_null_input.
+ */
+pub fn set_null_input(context: RecordContext) -> () {
+    requested_null_input.replace(Some(context));
+}
+
+/**
+ * Get the input captured by the input context of the null output.
+ *
+ * # Return
+ * The context given to ears::set_null_input, otherwise the file of the
+ * EARS_INPUT environment variable if it is set, silence otherwise.
+ */
+pub fn get_null_input() -> RecordContext {
+    match requested_null_input.get() {
+        Some(context) => return (*context).clone(),
+        None          => {}
+    }
+    match os::getenv("EARS_INPUT") {
+        Some(ref path) => match RecordContext::from_file(path.as_slice()) {
+            Some(context) => context,
+            None          => RecordContext::from_signal(CaptureSilence)
+        },
+        None           => RecordContext::from_signal(CaptureSilence)
+    }
+}
+
+/**
+ * Get the backend requested for the context of the task.
+ *
+ * # Return
+ * The backend given to ears::init_with, otherwise the one named by the
+ * EARS_OUTPUT environment variable, "null" or "default", otherwise the
+ * default backend.
+ */
+pub fn get_output_backend() -> OutputBackend {
+    match requested_backend.get() {
+        Some(backend) => return (*backend).clone(),
+        None          => {}
+    }
+    match os::getenv("EARS_OUTPUT") {
+        Some(ref value) if value.as_slice() == "null"    => NullOutput,
+        Some(ref value) if value.as_slice() == "default" => DefaultOutput,
+        _                                                => default_backend()
+    }
+}
+
+/// The tests run on the NullOutput, so they don't need audio hardware.
+#[cfg(test)]
+fn default_backend() -> OutputBackend {
+    NullOutput
+}
+
+#[cfg(not(test))]
+fn default_backend() -> OutputBackend {
+    DefaultOutput
+}
+
+/**
+ * Get the backend of the context of the task.
+ *
+ * # Return
+ * Some(backend) if the context is created, None otherwise.
+ */
+pub fn get_context_backend() -> Option<OutputBackend> {
+    match al_context.get() {
+        Some(data) => Some(data.borrow().backend.clone()),
+        None       => None
+    }
 }
 
 impl OpenAlData {
@@ -50,16 +232,71 @@ impl OpenAlData {
      *
      * Private method.
      */
-    fn new() -> Result<OpenAlData, String> {
-        let device = unsafe { ffi::alcOpenDevice(ptr::null_mut()) };
-        if device.is_null() {
-            return Err("Internal error: cannot open the default device.".to_string());
+    fn new(backend: OutputBackend) -> Result<OpenAlData, String> {
+        match backend {
+            DefaultOutput => {
+                let device = unsafe { ffi::alcOpenDevice(ptr::null_mut()) };
+                if device.is_null() {
+                    return Err("Internal error: cannot open the default device.".to_string());
+                }
+                OpenAlData::with_device(device, ptr::null_mut(), DefaultOutput)
+            },
+            NullOutput    => OpenAlData::new_null()
+        }
+    }
+
+    /**
+     * Create the context on a device discarding the samples.
+     *
+     * Use the "No Output" device of OpenAL Soft if it exists, otherwise a
+     * loopback device consumed in real time by a NullPacer.
+     */
+    fn new_null() -> Result<OpenAlData, String> {
+        let device = "No Output".with_c_str(|c_str| unsafe {
+            ffi::alcOpenDevice(c_str as *mut c_char)
+        });
+        if device.is_not_null() {
+            return OpenAlData::with_device(device, ptr::null_mut(), NullOutput);
         }
-        let context = unsafe { ffi::alcCreateContext(device, ptr::null_mut()) };
+
+        let attributes = [ffi::ALC_FREQUENCY, NULL_SAMPLERATE,
+                          ffi::ALC_FORMAT_CHANNELS_SOFT, ffi::ALC_STEREO_SOFT,
+                          ffi::ALC_FORMAT_TYPE_SOFT, ffi::ALC_SHORT_SOFT,
+                          0];
+        let device = match OpenAlData::open_loopback() {
+            Ok(device) => device,
+            Err(err)   => {
+                return Err(format!("Error: no null output available: {}", err));
+            }
+        };
+        let render_samples = match OpenAlData::get_render_samples(device) {
+            Ok(render_samples) => render_samples,
+            Err(err)           => { unsafe { ffi::alcCloseDevice(device); } return Err(err); }
+        };
+        let mut attributes = attributes.to_vec();
+        let mut data = try!(OpenAlData::with_device(device, attributes.as_mut_ptr(), NullOutput));
+        data.pacer = Some(NullPacer::new(device, render_samples));
+        Ok(data)
+    }
+
+    /**
+     * Create the context on an opened device and make it current.
+     *
+     * The device is closed if the context can't be created or made current.
+     */
+    fn with_device(device: *mut ffi::ALCdevice,
+                   attributes: *mut i32,
+                   backend: OutputBackend) -> Result<OpenAlData, String> {
+        let context = unsafe { ffi::alcCreateContext(device, attributes) };
         if context.is_null() {
+            unsafe { ffi::alcCloseDevice(device); }
             return Err("Internal error: cannot create the OpenAL context.".to_string());
         }
         if unsafe { ffi::alcMakeContextCurrent(context) } == ffi::ALC_FALSE {
+            unsafe {
+                ffi::alcDestroyContext(context);
+                ffi::alcCloseDevice(device);
+            }
             return Err("Internal error: cannot make the OpenAL context current.".to_string());
         }
 
@@ -67,11 +304,95 @@ impl OpenAlData {
             OpenAlData {
                 al_context: context,
                 al_device: device,
-                al_capt_device: ptr::null_mut()
+                al_capt_device: ptr::null_mut(),
+                backend: backend,
+                pacer: None
             }
         )
     }
 
+    /// Open a loopback device with the ALC_SOFT_loopback extension.
+    fn open_loopback() -> Result<*mut ffi::ALCdevice, String> {
+        if !al::alcIsExtensionPresent(ptr::null_mut(), "ALC_SOFT_loopback") {
+            return Err("Error: the ALC_SOFT_loopback extension is not available.".to_string());
+        }
+        let func = "alcLoopbackOpenDeviceSOFT".with_c_str(|c_str| unsafe {
+            ffi::alcGetProcAddress(ptr::null_mut(), c_str)
+        });
+        if func.is_null() {
+            return Err("Internal error: cannot find alcLoopbackOpenDeviceSOFT.".to_string());
+        }
+        let open_device: LoopbackOpenDeviceSOFT = unsafe { mem::transmute(func) };
+
+        let device = open_device(ptr::null());
+        if device.is_null() {
+            return Err("Internal error: cannot open the loopback device.".to_string());
+        }
+        Ok(device)
+    }
+
+    /// Get alcRenderSamplesSOFT, the function rendering the samples of a loopback device.
+    fn get_render_samples(device: *mut ffi::ALCdevice) -> Result<RenderSamplesSOFT, String> {
+        let func = "alcRenderSamplesSOFT".with_c_str(|c_str| unsafe {
+            ffi::alcGetProcAddress(device, c_str)
+        });
+        if func.is_null() {
+            Err("Internal error: cannot find alcRenderSamplesSOFT.".to_string())
+        } else {
+            Ok(unsafe { mem::transmute(func) })
+        }
+    }
+
+    /**
+     * Create the context on a loopback device.
+     *
+     * The loopback device renders the samples on demand instead of playing
+     * them on a sound card. It needs the ALC_SOFT_loopback extension and
+     * must be created before any other use of ears in the task.
+     *
+     * # Argument
+     * * `attributes` - The attributes of the context, with the format of
+     * the rendered samples, terminated by 0
+     *
+     * # Return
+     * A result containing the loopback device and its alcRenderSamplesSOFT,
+     * otherwise an error message.
+     */
+    pub fn init_loopback(attributes: &[i32])
+                         -> Result<(*mut ffi::ALCdevice, RenderSamplesSOFT), String> {
+        if unsafe { ffi::alcGetCurrentContext().is_not_null() } || al_context.get().is_some() {
+            return Err("Error: the loopback device must be created before any other \
+                        use of ears.".to_string());
+        }
+        let device = try!(OpenAlData::open_loopback());
+        let render_samples = match OpenAlData::get_render_samples(device) {
+            Ok(render_samples) => render_samples,
+            Err(err)           => { unsafe { ffi::alcCloseDevice(device); } return Err(err); }
+        };
+        let mut attributes = attributes.to_vec();
+        let context = unsafe { ffi::alcCreateContext(device, attributes.as_mut_ptr()) };
+        if context.is_null() {
+            unsafe { ffi::alcCloseDevice(device); }
+            return Err("Error: the loopback device doesn't support this format.".to_string());
+        }
+        if unsafe { ffi::alcMakeContextCurrent(context) } == ffi::ALC_FALSE {
+            unsafe {
+                ffi::alcDestroyContext(context);
+                ffi::alcCloseDevice(device);
+            }
+            return Err("Internal error: cannot make the OpenAL context current.".to_string());
+        }
+
+        al_context.replace(Some(RefCell::new(box OpenAlData {
+            al_context: context,
+            al_device: device,
+            al_capt_device: ptr::null_mut(),
+            backend: NullOutput,
+            pacer: None
+        })));
+        Ok((device, render_samples))
+    }
+
     /**
      * Check if the context is created.
      *
@@ -84,13 +405,16 @@ impl OpenAlData {
      * otherwise an error message.
      */
     pub fn check_al_context() -> Result<(), String> {
+        if !backend::get().needs_context() {
+            return Ok(())
+        }
         if unsafe { ffi::alcGetCurrentContext().is_not_null() } {
             return Ok(())
         }
         match al_context.get() {
             Some(_) => Ok(()),
             None    => {
-                match OpenAlData::new() {
+                match OpenAlData::new(get_output_backend()) {
                     Ok(al_data) => {
                         al_context.replace(Some(RefCell::new(box al_data))); Ok(())
                     },
@@ -101,33 +425,29 @@ impl OpenAlData {
     }
 
     fn is_input_context_init() -> Result<RecordContext, String> {
-        let is_some = al_context.get().is_some();
-        if is_some {
-            let mut new_context = *(*al_context.get().unwrap()).borrow().clone();
-            if new_context.al_capt_device.is_not_null() {
-                Ok(record_context::new(new_context.al_capt_device))
-            } else {
-                if "ALC_EXT_CAPTURE".with_c_str(|c_str| unsafe {
-                    ffi::alcIsExtensionPresent(new_context.al_device, c_str) }) == ffi::ALC_FALSE {
-                    return Err("Error: no input device available on your system.".to_string())
-                } else {
-                    new_context.al_capt_device = unsafe {
-                        ffi::alcCaptureOpenDevice(ptr::null_mut(),
-                                                  44100,
-                                                  ffi::AL_FORMAT_MONO16,
-                                                  44100) };
-                    if new_context.al_capt_device.is_null() {
-                        Err("Internal error: cannot open the default capture device.".to_string())
-                    } else {
-                        let cap_device = new_context.al_capt_device;
-                        al_context.replace(Some(RefCell::new(box new_context)));
-                        Ok(record_context::new(cap_device))
-                    }
-                }
+        let data = match al_context.get() {
+            Some(data) => data,
+            None       => {
+                return Err("Error: you must request the input context, \
+                            in the task where you initialize ears.".to_string())
             }
+        };
+        let mut data = data.borrow_mut();
+        if data.backend == NullOutput {
+            return Ok(get_null_input());
+        }
+        if data.al_capt_device.is_not_null() {
+            return Ok(record_context::new(data.al_capt_device));
+        }
+        if "ALC_EXT_CAPTURE".with_c_str(|c_str| unsafe {
+            ffi::alcIsExtensionPresent(data.al_device, c_str) }) == ffi::ALC_FALSE {
+            return Err("Error: no input device available on your system.".to_string())
+        }
+        data.al_capt_device = al::alcCaptureOpenDevice(44100, ffi::AL_FORMAT_MONO16, 44100);
+        if data.al_capt_device.is_null() {
+            Err("Internal error: cannot open the default capture device.".to_string())
         } else {
-            Err("Error: you must request the input context, \
-                           in the task where you initialize ears.".to_string())
+            Ok(record_context::new(data.al_capt_device))
         }
     }
 
@@ -143,6 +463,9 @@ impl OpenAlData {
      * otherwise an error message.
      */
     pub fn check_al_input_context() -> Result<RecordContext, String> {
+        if !backend::get().needs_context() {
+            return Ok(get_null_input());
+        }
         if unsafe { ffi::alcGetCurrentContext().is_not_null() } {
             OpenAlData::is_input_context_init()
         } else {
@@ -156,10 +479,12 @@ impl OpenAlData {
 
 impl Drop for OpenAlData {
     fn drop(&mut self) {
+        // Stop consuming the samples before the device is closed.
+        self.pacer = None;
         unsafe {
             ffi::alcDestroyContext(self.al_context);
             if self.al_capt_device.is_not_null() {
-                ffi::alcCaptureCloseDevice(self.al_capt_device);
+                al::alcCaptureCloseDevice(self.al_capt_device);
             }
             ffi::alcCloseDevice(self.al_device);
         }
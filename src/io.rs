@@ -0,0 +1,523 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+/*!
+ * Read and write audio files.
+ *
+ * SoundReader and SoundWriter are safe wrappers over libsndfile: the file is
+ * closed when they are dropped and all the errors are returned through a
+ * `SoundResult`.
+ *
+ * Unlike Sound and Music, SoundReader only uses libsndfile: it doesn't read
+ * the MP3 files of the `mp3` feature.
+ *
+ * # Example
+ * ```Rust
+ * use ears::io::{SoundReader, SoundWriter};
+ *
+ * let mut reader = SoundReader::open("path/to/in.ogg").unwrap();
+ * let info = reader.get_info();
+ * let samples: Vec<f32> = reader.read_all().unwrap();
+ *
+ * let mut writer = SoundWriter::create("path/to/out.wav", info).unwrap();
+ * writer.write(samples.as_slice()).unwrap();
+ * writer.finish().unwrap();
+ * ```
+ */
+
+use std::default::Default;
+
+use sndfile::{SndFile, Read, Write};
+use audio_tags::Tags;
+use audio_tags;
+use metadata::Metadata;
+use metadata;
+use resampler::Resampler;
+
+pub use resampler::{ResampleQuality, ResampleLinear, ResampleCubic, ResampleSinc};
+pub use sndfile::{SndInfo, StringSoundType, Title, Copyright, Software, Artist,
+                  Comment, Date, Album, License, TrackNumber, Genre};
+pub use sndfile::{SeekMode, SeekSet, SeekCur, SeekEnd};
+pub use sndfile::{Error, NoError, UnrecognizedFormat, SystemError,
+                  MalformedFile, UnsupportedEncoding};
+pub use sndfile::{FormatType, FormatWav, FormatAiff, FormatAu, FormatRaw,
+                  FormatPaf, FormatSvx, FormatNist, FormatVoc, FormatIrcam,
+                  FormatW64, FormatMat4, FormatMat5, FormatPvf, FormatXi,
+                  FormatHtk, FormatSds, FormatAvr, FormatWavex, FormatSd2,
+                  FormatFlac, FormatCaf, FormatWve, FormatOgg, FormatMpc2k,
+                  FormatRf64, FormatPcmS8, FormatPcm16, FormatPcm24,
+                  FormatPcm32, FormatPcmU8, FormatFloat, FormatDouble,
+                  FormatUlaw, FormatAlaw, FormatImaAdpcm, FormatApcm,
+                  FormatGsm610, FormatVoxAdpcm, FormatG72132, FormatG72324,
+                  FormatG72340, FormatDww12, FormatDww16, FormatDww24,
+                  FormatDwwN, FormatDpcm8, FormatDpcm16, FormatVorbis,
+                  EndianFile, EndianLittle, EndianBig, EndianCpu};
+
+/// Number of frames read at once by the Frames iterator.
+static FRAMES_CHUNK: uint = 1024;
+
+/// An error of libsndfile.
+#[deriving(Clone, PartialEq, Show)]
+pub struct SoundError {
+    /// The error code, SystemError when the code is unknown
+    pub error: Error,
+    /// The description of the error
+    pub message: String
+}
+
+/// The result of the io operations.
+pub type SoundResult<T> = Result<T, SoundError>;
+
+impl SoundError {
+    fn new(error: Error, message: String) -> SoundError {
+        SoundError {
+            error: error,
+            message: message
+        }
+    }
+
+    /// Build the error of the last operation on the file.
+    fn from_file(file: &SndFile) -> SoundError {
+        SoundError::new(file.error(), file.string_error())
+    }
+}
+
+/**
+ * A type of sample libsndfile can read and write.
+ *
+ * It is implemented for i16, i32, f32 and f64. The samples are converted
+ * from the format of the file by libsndfile.
+ */
+pub trait Sample: Copy + Default {
+    /// Read `frames` frames into `buffer`, return the count of read frames.
+    fn read_frames(reader: &mut SoundReader, buffer: &mut [Self], frames: i64) -> i64;
+
+    /// Write `frames` frames from `buffer`, return the count of written frames.
+    fn write_frames(writer: &mut SoundWriter, buffer: &[Self], frames: i64) -> i64;
+}
+
+impl Sample for i16 {
+    fn read_frames(reader: &mut SoundReader, buffer: &mut [i16], frames: i64) -> i64 {
+        reader.file.readf_i16(buffer, frames)
+    }
+
+    fn write_frames(writer: &mut SoundWriter, buffer: &[i16], frames: i64) -> i64 {
+        writer.file.writef_i16(buffer, frames)
+    }
+}
+
+impl Sample for i32 {
+    fn read_frames(reader: &mut SoundReader, buffer: &mut [i32], frames: i64) -> i64 {
+        reader.file.readf_i32(buffer, frames)
+    }
+
+    fn write_frames(writer: &mut SoundWriter, buffer: &[i32], frames: i64) -> i64 {
+        writer.file.writef_i32(buffer, frames)
+    }
+}
+
+impl Sample for f32 {
+    fn read_frames(reader: &mut SoundReader, buffer: &mut [f32], frames: i64) -> i64 {
+        reader.file.readf_f32(buffer, frames)
+    }
+
+    fn write_frames(writer: &mut SoundWriter, buffer: &[f32], frames: i64) -> i64 {
+        writer.file.writef_f32(buffer, frames)
+    }
+}
+
+impl Sample for f64 {
+    fn read_frames(reader: &mut SoundReader, buffer: &mut [f64], frames: i64) -> i64 {
+        reader.file.readf_f64(buffer, frames)
+    }
+
+    fn write_frames(writer: &mut SoundWriter, buffer: &[f64], frames: i64) -> i64 {
+        writer.file.writef_f64(buffer, frames)
+    }
+}
+
+/**
+ * Read an audio file.
+ *
+ * The file is closed when the SoundReader is dropped.
+ */
+pub struct SoundReader {
+    file: SndFile
+}
+
+impl SoundReader {
+    /**
+     * Open an audio file for reading.
+     *
+     * # Argument
+     * * `path` - The path of the file
+     *
+     * # Return
+     * The SoundReader on success, the error of libsndfile otherwise.
+     */
+    pub fn open(path: &str) -> SoundResult<SoundReader> {
+        match SndFile::new(path, Read) {
+            Ok(file)     => Ok(SoundReader { file: file }),
+            Err(message) => Err(SoundError::new(SndFile::open_error(), message))
+        }
+    }
+
+    /// Get the informations of the file.
+    pub fn get_info(&self) -> SndInfo {
+        self.file.get_sndinfo()
+    }
+
+    /// Get the number of channels of the file.
+    pub fn get_channels(&self) -> uint {
+        self.file.get_sndinfo().channels as uint
+    }
+
+    /**
+     * Get a tag of the file.
+     *
+     * # Argument
+     * * `tag` - The type of the tag
+     *
+     * # Return
+     * Some(String) if the tag is set in the file, None otherwise.
+     */
+    pub fn get_tag(&self, tag: StringSoundType) -> Option<String> {
+        self.file.get_string(tag)
+    }
+
+    /// Get the broadcast info, the cue points and the instrument of the file.
+    pub fn get_metadata(&self) -> Metadata {
+        metadata::get_metadata(&self.file)
+    }
+
+    /**
+     * Read interleaved frames.
+     *
+     * # Argument
+     * * `buffer` - The buffer to fill, as many whole frames as possible are
+     * read
+     *
+     * # Return
+     * The count of read frames, 0 at the end of the file.
+     */
+    pub fn read<S: Sample>(&mut self, buffer: &mut [S]) -> SoundResult<uint> {
+        let frames = (buffer.len() / self.get_channels()) as i64;
+        let read = Sample::read_frames(self, buffer, frames);
+        match self.file.error() {
+            NoError => Ok(read as uint),
+            _       => Err(SoundError::from_file(&self.file))
+        }
+    }
+
+    /**
+     * Read all the remaining frames of the file.
+     *
+     * # Return
+     * The interleaved samples.
+     */
+    pub fn read_all<S: Sample>(&mut self) -> SoundResult<Vec<S>> {
+        let mut samples = Vec::new();
+        for frame in self.frames() {
+            match frame {
+                Ok(frame) => samples.push_all(frame.as_slice()),
+                Err(err)  => return Err(err)
+            }
+        }
+        Ok(samples)
+    }
+
+    /**
+     * Read all the remaining frames of the file at another sample rate.
+     *
+     * # Arguments
+     * * `samplerate` - The sample rate of the returned samples
+     * * `quality` - The quality of the conversion
+     *
+     * # Return
+     * The interleaved samples at `samplerate`.
+     */
+    pub fn read_all_resampled(&mut self,
+                              samplerate: i32,
+                              quality: ResampleQuality) -> SoundResult<Vec<f32>> {
+        let samples: Vec<f32> = try!(self.read_all());
+        let resampler = Resampler::new(self.get_info().samplerate,
+                                       samplerate,
+                                       self.get_channels(),
+                                       quality);
+        Ok(resampler.process(samples.as_slice()))
+    }
+
+    /**
+     * Iterate over the remaining frames of the file.
+     *
+     * Each item contains one sample per channel.
+     */
+    pub fn frames<'a, S: Sample>(&'a mut self) -> Frames<'a, S> {
+        let channels = self.get_channels();
+        Frames {
+            reader: self,
+            channels: channels,
+            buffer: Vec::from_elem(FRAMES_CHUNK * channels, Default::default()),
+            position: 0,
+            len: 0,
+            done: false
+        }
+    }
+
+    /**
+     * Move the read position.
+     *
+     * # Arguments
+     * * `frames` - The offset in frames
+     * * `whence` - The origin of the offset
+     *
+     * # Return
+     * The new position in frames from the start of the file.
+     */
+    pub fn seek(&mut self, frames: i64, whence: SeekMode) -> SoundResult<i64> {
+        match self.file.seek(frames, whence) {
+            -1       => Err(SoundError::from_file(&self.file)),
+            position => Ok(position)
+        }
+    }
+}
+
+/// An iterator over the frames of a SoundReader.
+pub struct Frames<'a, S> {
+    reader: &'a mut SoundReader,
+    channels: uint,
+    buffer: Vec<S>,
+    position: uint,
+    len: uint,
+    done: bool
+}
+
+impl<'a, S: Sample> Iterator<SoundResult<Vec<S>>> for Frames<'a, S> {
+    fn next(&mut self) -> Option<SoundResult<Vec<S>>> {
+        if self.done || self.channels == 0 {
+            return None;
+        }
+        if self.position == self.len {
+            match self.reader.read(self.buffer.as_mut_slice()) {
+                Ok(0)    => { self.done = true; return None; },
+                Ok(read) => { self.position = 0; self.len = read; },
+                Err(err) => { self.done = true; return Some(Err(err)); }
+            }
+        }
+        let start = self.position * self.channels;
+        self.position += 1;
+        Some(Ok(self.buffer.slice(start, start + self.channels).to_vec()))
+    }
+}
+
+/**
+ * Write an audio file.
+ *
+ * The file is closed when the SoundWriter is dropped, use `finish` to check
+ * the errors of the closing.
+ */
+pub struct SoundWriter {
+    file: SndFile
+}
+
+impl SoundWriter {
+    /**
+     * Create an audio file.
+     *
+     * # Arguments
+     * * `path` - The path of the file
+     * * `info` - The samplerate, the channels and the format of the file,
+     * like `(FormatWav | FormatPcm16) as i32`
+     *
+     * # Return
+     * The SoundWriter on success, the error of libsndfile otherwise.
+     */
+    pub fn create(path: &str, info: SndInfo) -> SoundResult<SoundWriter> {
+        let mut info = box info;
+        if !SndFile::check_format(&mut *info) {
+            return Err(SoundError::new(UnrecognizedFormat,
+                                       "Invalid format for writing.".to_string()));
+        }
+        match SndFile::new_with_info(path, Write, info) {
+            Ok(file)     => Ok(SoundWriter { file: file }),
+            Err(message) => Err(SoundError::new(SndFile::open_error(), message))
+        }
+    }
+
+    /**
+     * Write interleaved frames.
+     *
+     * # Argument
+     * * `buffer` - The samples to write, only the whole frames are written
+     *
+     * # Return
+     * The count of written frames.
+     */
+    pub fn write<S: Sample>(&mut self, buffer: &[S]) -> SoundResult<uint> {
+        let channels = self.file.get_sndinfo().channels as uint;
+        let frames = (buffer.len() / channels) as i64;
+        let written = Sample::write_frames(self, buffer, frames);
+        if written == frames {
+            Ok(written as uint)
+        } else {
+            Err(SoundError::from_file(&self.file))
+        }
+    }
+
+    /**
+     * Set a tag of the file.
+     *
+     * The tags must be set before writing the samples for most formats.
+     *
+     * # Arguments
+     * * `tag` - The type of the tag
+     * * `value` - The value of the tag
+     */
+    pub fn set_tag(&mut self, tag: StringSoundType, value: &str) -> SoundResult<()> {
+        match self.file.set_string(tag, value.to_string()) {
+            NoError => Ok(()),
+            error   => Err(SoundError::new(error, SndFile::error_number(error)))
+        }
+    }
+
+    /**
+     * Set all the non empty fields of the tags.
+     *
+     * # Argument
+     * * `tags` - The tags to set
+     *
+     * # Return
+     * The fields the format of the file can store.
+     */
+    pub fn set_tags(&mut self, tags: &Tags) -> Vec<StringSoundType> {
+        audio_tags::set_sound_tags(&mut self.file, tags)
+    }
+
+    /**
+     * Set the broadcast info, the cue points and the instrument of the file.
+     *
+     * The empty fields are not set.
+     *
+     * # Argument
+     * * `metadata` - The metadata to set
+     *
+     * # Return
+     * Ok(()) if the format of the file can store all the fields.
+     */
+    pub fn set_metadata(&mut self, metadata: &Metadata) -> SoundResult<()> {
+        match metadata::set_metadata(&mut self.file, metadata) {
+            Ok(())       => Ok(()),
+            Err(message) => Err(SoundError::new(UnsupportedEncoding, message))
+        }
+    }
+
+    /// Force the writing of the cached data to the disk.
+    pub fn sync(&mut self) -> () {
+        self.file.write_sync()
+    }
+
+    /**
+     * Close the file.
+     *
+     * # Return
+     * Ok(()) if the file is correctly closed, the error of libsndfile
+     * otherwise.
+     */
+    pub fn finish(mut self) -> SoundResult<()> {
+        match self.file.close() {
+            NoError => Ok(()),
+            error   => Err(SoundError::new(error, SndFile::error_number(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::TempDir;
+
+    use io::{SoundReader, SoundWriter, FormatWav, FormatPcm16};
+    use io::{SystemError, UnrecognizedFormat};
+
+    #[test]
+    fn io_reader_open_OK() -> () {
+        let reader = SoundReader::open("res/shot.wav");
+
+        match reader {
+            Ok(_)  => {},
+            Err(_) => panic!()
+        }
+    }
+
+    #[test]
+    fn io_reader_open_FAIL() -> () {
+        let reader = SoundReader::open("toto.wav");
+
+        match reader {
+            Ok(_)    => panic!(),
+            Err(err) => assert_eq!(err.error, SystemError)
+        }
+    }
+
+    #[test]
+    fn io_reader_open_format_FAIL() -> () {
+        let reader = SoundReader::open("README.md");
+
+        match reader {
+            Ok(_)    => panic!(),
+            Err(err) => assert_eq!(err.error, UnrecognizedFormat)
+        }
+    }
+
+    #[test]
+    fn io_reader_frames_OK() -> () {
+        let mut reader = SoundReader::open("res/shot.wav").unwrap();
+        let info = reader.get_info();
+        let mut count = 0i64;
+
+        for frame in reader.frames::<i16>() {
+            assert_eq!(frame.unwrap().len(), info.channels as uint);
+            count += 1;
+        }
+        assert_eq!(count, info.frames);
+    }
+
+    #[test]
+    fn io_write_read_OK() -> () {
+        let mut info = SoundReader::open("res/shot.wav").unwrap().get_info();
+        info.channels = 1;
+        info.format = (FormatWav | FormatPcm16) as i32;
+
+        let dir = TempDir::new("ears_io").unwrap();
+        let path = dir.path().join("test.wav");
+        let path = path.as_str().unwrap();
+
+        let samples = [0i16, 1000, -1000, 32767];
+        let mut writer = SoundWriter::create(path, info).unwrap();
+        assert_eq!(writer.write(samples.as_slice()), Ok(4));
+        writer.finish().unwrap();
+
+        let mut reader = SoundReader::open(path).unwrap();
+        let read: Vec<i16> = reader.read_all().unwrap();
+        assert_eq!(read.as_slice(), samples.as_slice());
+    }
+}
@@ -0,0 +1,125 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Options to load the samples of a sound.
+
+use std::default::Default;
+
+use sample::SampleType;
+use resampler::{ResampleQuality, ResampleSinc};
+
+local_data_key!(default_options: LoadOptions)
+
+/**
+ * How to load the samples of a Sound or a Music.
+ *
+ * # Example
+ * ```Rust
+ * use ears::{LoadOptions, Sound, Int8};
+ *
+ * // Keep the memory low for this big ambience
+ * let options = LoadOptions { sample_type: Some(Int8), ..Default::default() };
+ * let snd = Sound::new_with_options("path/to/ambience.flac", &options).unwrap();
+ * ```
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub struct LoadOptions {
+    /// The type of the samples in the OpenAL buffers, None for the most
+    /// precise type supported by OpenAL
+    pub sample_type: Option<SampleType>,
+    /// Mix down the channels to mono, OpenAL only positions the mono
+    /// sounds in space
+    pub downmix_to_mono: bool,
+    /// Also load a copy of the channels mixed down to mono, used when a
+    /// Sound is positioned. Without it, the copy is loaded from the file
+    /// the first time a Sound of the SoundData is positioned.
+    pub mono_copy: bool,
+    /// The sample rate to convert the samples of a SoundData to, None to
+    /// keep the rate of the file. Music streams keep the rate of the file.
+    pub sample_rate: Option<i32>,
+    /// The quality of the sample rate conversion
+    pub resample_quality: ResampleQuality
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            sample_type: None,
+            downmix_to_mono: false,
+            mono_copy: false,
+            sample_rate: None,
+            resample_quality: ResampleSinc
+        }
+    }
+}
+
+/**
+ * Set the options used by `Sound::new`, `SoundData::new` and `Music::new`.
+ *
+ * The options are set for the current task.
+ *
+ * # Argument
+ * * `options` - The new default options
+ *
+ * # Example
+ * ```Rust
+ * // Convert all the assets to 48 kHz at load time
+ * ears::set_default_load_options(&LoadOptions {
+ *     sample_rate: Some(48000),
+ *     ..Default::default()
+ * });
+ * ```
+ */
+pub fn set_default_load_options(options: &LoadOptions) -> () {
+    default_options.replace(Some(options.clone()));
+}
+
+/**
+ * Get the options used by `Sound::new`, `SoundData::new` and `Music::new`.
+ *
+ * # Return
+ * The options set by `set_default_load_options`, `Default::default()`
+ * otherwise.
+ */
+pub fn get_default_load_options() -> LoadOptions {
+    match default_options.get() {
+        Some(options) => (*options).clone(),
+        None          => Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::default::Default;
+    use load_options::{LoadOptions, set_default_load_options,
+                       get_default_load_options};
+
+    #[test]
+    fn load_options_default_OK() -> () {
+        let options = LoadOptions { sample_rate: Some(48000), ..Default::default() };
+
+        assert_eq!(get_default_load_options(), Default::default());
+        set_default_load_options(&options);
+        assert_eq!(get_default_load_options(), options);
+    }
+}
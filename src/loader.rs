@@ -0,0 +1,614 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Load the SoundDatas and open the Musics in the background.
+
+use std::task;
+use std::num::Float;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::comm::{Empty, Disconnected};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUint, AtomicBool, SeqCst};
+
+use openal::{ffi, al};
+use sound_data::{SoundData, PreparedSound, DecodedSound};
+use sound_data;
+use load_options::LoadOptions;
+use music::{Music, MusicFile};
+use music;
+
+/// The count of worker tasks decoding the files.
+static WORKERS: uint = 4;
+
+local_data_key!(worker_pool: WorkerPool)
+local_data_key!(pending_loads: RefCell<Vec<Rc<RefCell<PendingLoad>>>>)
+
+/**
+ * The status of a SoundData or a Music loaded in the background.
+ *
+ * * LoadPending - The file is being decoded
+ * * LoadReady - The samples are uploaded to OpenAL, or the Music is opened
+ * * LoadFailed - The file can't be loaded, with the error
+ * * LoadCancelled - The loading was cancelled
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum LoadStatus {
+    /// The file is being decoded
+    LoadPending,
+    /// The samples are uploaded to OpenAL
+    LoadReady,
+    /// The file can't be loaded
+    LoadFailed(String),
+    /// The loading was cancelled
+    LoadCancelled
+}
+
+/// The progress of a decoding, shared with the worker task.
+#[doc(hidden)]
+pub struct LoadProgress {
+    frames: AtomicUint,
+    cancelled: AtomicBool
+}
+
+impl LoadProgress {
+    pub fn new() -> LoadProgress {
+        LoadProgress {
+            frames: AtomicUint::new(0),
+            cancelled: AtomicBool::new(false)
+        }
+    }
+
+    pub fn add_frames(&self, frames: uint) -> () {
+        self.frames.fetch_add(frames, SeqCst);
+    }
+
+    pub fn get_frames(&self) -> uint {
+        self.frames.load(SeqCst)
+    }
+
+    pub fn cancel(&self) -> () {
+        self.cancelled.store(true, SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(SeqCst)
+    }
+}
+
+/// The worker tasks, taking the decoding jobs from a shared channel.
+struct WorkerPool {
+    jobs: Sender<proc(): Send>
+}
+
+impl WorkerPool {
+    fn new(workers: uint) -> WorkerPool {
+        let (sender, receiver) = channel::<proc(): Send>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in range(0, workers) {
+            let receiver = receiver.clone();
+            task::spawn(proc() {
+                loop {
+                    let job = receiver.lock().recv_opt();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_)  => break
+                    }
+                }
+            });
+        }
+        WorkerPool { jobs: sender }
+    }
+}
+
+/// Run a job on the worker pool of the task.
+fn execute(job: proc(): Send) -> () {
+    if worker_pool.get().is_none() {
+        worker_pool.replace(Some(WorkerPool::new(WORKERS)));
+    }
+    worker_pool.get().unwrap().jobs.send(job);
+}
+
+/// A SoundData loading in the background.
+struct PendingLoad {
+    /// The file and its format
+    prepared: Option<PreparedSound>,
+    /// The SoundData shared with the Sounds, replaced once loaded
+    data: Rc<RefCell<SoundData>>,
+    /// The progress of the decoding
+    progress: Arc<LoadProgress>,
+    /// Receive the decoded samples from the worker task
+    receiver: Option<Receiver<Result<DecodedSound, String>>>,
+    /// The status of the loading
+    status: LoadStatus,
+    /// The sources waiting for the samples, and if they must play
+    sources: Vec<(u32, bool)>
+}
+
+impl PendingLoad {
+    /// Upload the decoded samples and start the waiting sources.
+    fn finish(&mut self, result: Result<DecodedSound, String>) -> () {
+        self.receiver = None;
+        let decoded = match result {
+            Ok(decoded) => decoded,
+            Err(err)    => {
+                self.sources.clear();
+                self.status = if self.progress.is_cancelled() {
+                    LoadCancelled
+                } else {
+                    println!("{}", err);
+                    LoadFailed(err)
+                };
+                return;
+            }
+        };
+
+        let data = match self.prepared {
+            Some(ref prepared) => sound_data::upload(decoded, prepared),
+            None               => None
+        };
+        match data {
+            Some(data) => {
+                // The Sounds take the markers of the new SoundData at their
+                // next use of the markers
+                sound_data::replace(&mut *self.data.borrow_mut(), data);
+                let buffer = sound_data::get_buffer(&*self.data.borrow());
+                for &(source, play) in self.sources.iter() {
+                    al::alSourcei(source, ffi::AL_BUFFER, buffer as i32);
+                    if play {
+                        al::alSourcePlay(source);
+                    }
+                }
+                self.sources.clear();
+                self.status = LoadReady;
+            },
+            None       => {
+                self.sources.clear();
+                self.status = LoadFailed("Cannot upload the samples to OpenAL.".to_string());
+            }
+        }
+    }
+
+    /// Check if the worker task has sent the samples.
+    fn poll(&mut self) -> () {
+        let result = match self.receiver {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result)        => result,
+                Err(Empty)        => return,
+                Err(Disconnected) => Err("Internal error : the loading task failed.".to_string())
+            },
+            None               => return
+        };
+        self.finish(result);
+    }
+}
+
+/**
+ * A handle on a SoundData loaded in the background.
+ *
+ * The handle is created by `SoundData::load_async`. Cloning it gives another
+ * handle on the same loading.
+ */
+#[deriving(Clone)]
+pub struct AsyncSoundData {
+    load: Rc<RefCell<PendingLoad>>
+}
+
+impl AsyncSoundData {
+    /**
+     * Get the SoundData of the handle.
+     *
+     * Until the loading is done the SoundData has no sample, the Sounds
+     * created with it start to play once the samples are loaded.
+     */
+    pub fn get_data(&self) -> Rc<RefCell<SoundData>> {
+        self.load.borrow().data.clone()
+    }
+
+    /// Get the status of the loading.
+    pub fn get_status(&self) -> LoadStatus {
+        self.load.borrow().status.clone()
+    }
+
+    /// Check if the samples are loaded.
+    pub fn is_ready(&self) -> bool {
+        self.load.borrow().status == LoadReady
+    }
+
+    /**
+     * Get the progress of the loading.
+     *
+     * # Return
+     * The part of the file decoded, from 0 to 1.
+     */
+    pub fn get_progress(&self) -> f32 {
+        let load = self.load.borrow();
+        match (&load.status, &load.prepared) {
+            (&LoadReady, _)                          => 1.,
+            (&LoadPending, &Some(ref prepared)) if prepared.frames > 0 => {
+                let frames = load.progress.get_frames() as f32;
+                (frames / prepared.frames as f32).min(1.)
+            },
+            _                                        => 0.
+        }
+    }
+
+    /**
+     * Cancel the loading.
+     *
+     * The Sounds waiting for the samples stay silent.
+     */
+    pub fn cancel(&self) -> () {
+        let mut load = self.load.borrow_mut();
+        if load.status == LoadPending {
+            load.progress.cancel();
+            load.receiver = None;
+            load.sources.clear();
+            load.status = LoadCancelled;
+        }
+    }
+
+    /**
+     * Wait for the end of the loading.
+     *
+     * # Return
+     * Some(SoundData) if the samples are loaded, None otherwise.
+     */
+    pub fn wait(&self) -> Option<Rc<RefCell<SoundData>>> {
+        let result = match self.load.borrow().receiver {
+            Some(ref receiver) => receiver.recv_opt().ok(),
+            None               => None
+        };
+        let mut load = self.load.borrow_mut();
+        match result {
+            Some(result) => load.finish(result),
+            None if load.status == LoadPending => {
+                load.finish(Err("Internal error : the loading task failed.".to_string()))
+            },
+            None         => {}
+        }
+
+        match load.status {
+            LoadReady => Some(load.data.clone()),
+            _         => None
+        }
+    }
+}
+
+/**
+ * Start to load a SoundData in the background.
+ */
+#[doc(hidden)]
+pub fn load(path: &str, options: &LoadOptions) -> AsyncSoundData {
+    let data = Rc::new(RefCell::new(sound_data::placeholder(path, options)));
+    let progress = Arc::new(LoadProgress::new());
+    let prepared = sound_data::prepare(path, options);
+
+    let (receiver, status) = match prepared {
+        Some(ref prepared) => {
+            let (sender, receiver) = channel();
+            let job_prepared = prepared.clone();
+            let job_progress = progress.clone();
+            execute(proc() {
+                sender.send_opt(sound_data::decode(&job_prepared,
+                                                   Some(&*job_progress))).ok();
+            });
+            (Some(receiver), LoadPending)
+        },
+        None               => {
+            (None, LoadFailed(format!("Cannot load {}.", path)))
+        }
+    };
+
+    let load = Rc::new(RefCell::new(PendingLoad {
+        prepared: prepared,
+        data: data,
+        progress: progress,
+        receiver: receiver,
+        status: status,
+        sources: Vec::new()
+    }));
+    with_pending_loads(|loads| loads.push(load.clone()));
+    AsyncSoundData { load: load }
+}
+
+/**
+ * A handle on a Music opened in the background.
+ *
+ * The handle is created by `Music::load_async`. The Music is created in the
+ * task of the handle once the file is opened.
+ */
+pub struct AsyncMusic {
+    /// Receive the opened file from the worker task
+    receiver: RefCell<Option<Receiver<Result<MusicFile, String>>>>,
+    /// The opened file, until the Music is taken
+    file: RefCell<Option<MusicFile>>,
+    /// The status of the loading
+    status: RefCell<LoadStatus>,
+    /// Tell the worker task the loading is cancelled
+    progress: Arc<LoadProgress>
+}
+
+impl AsyncMusic {
+    /// Store the result of the worker task.
+    fn finish(&self, result: Result<MusicFile, String>) -> () {
+        *self.receiver.borrow_mut() = None;
+        match result {
+            Ok(file) => {
+                *self.file.borrow_mut() = Some(file);
+                *self.status.borrow_mut() = LoadReady;
+            },
+            Err(err) => {
+                println!("{}", err);
+                *self.status.borrow_mut() = LoadFailed(err);
+            }
+        }
+    }
+
+    /// Check if the worker task has sent the file.
+    fn poll(&self) -> () {
+        let result = match *self.receiver.borrow() {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result)        => result,
+                Err(Empty)        => return,
+                Err(Disconnected) => Err("Internal error : the loading task failed.".to_string())
+            },
+            None               => return
+        };
+        self.finish(result);
+    }
+
+    /// Get the status of the loading.
+    pub fn get_status(&self) -> LoadStatus {
+        self.poll();
+        self.status.borrow().clone()
+    }
+
+    /// Check if the Music is opened.
+    pub fn is_ready(&self) -> bool {
+        self.get_status() == LoadReady
+    }
+
+    /// Cancel the loading.
+    pub fn cancel(&self) -> () {
+        if *self.status.borrow() == LoadPending {
+            self.progress.cancel();
+            *self.receiver.borrow_mut() = None;
+            *self.status.borrow_mut() = LoadCancelled;
+        }
+    }
+
+    /**
+     * Take the Music once it's opened.
+     *
+     * # Return
+     * Some(Music) the first time it's called once the file is opened, None
+     * otherwise.
+     */
+    pub fn take(&self) -> Option<Music> {
+        self.poll();
+        match self.file.borrow_mut().take() {
+            Some(file) => music::from_file(file),
+            None       => None
+        }
+    }
+
+    /**
+     * Wait for the end of the loading then take the Music.
+     *
+     * # Return
+     * Some(Music) if the file is opened, None otherwise.
+     */
+    pub fn wait(&self) -> Option<Music> {
+        let result = match *self.receiver.borrow() {
+            Some(ref receiver) => Some(receiver.recv_opt().unwrap_or_else(|_| {
+                Err("Internal error : the loading task failed.".to_string())
+            })),
+            None               => None
+        };
+        match result {
+            Some(result) => self.finish(result),
+            None         => {}
+        }
+        self.take()
+    }
+}
+
+/**
+ * Start to open a Music in the background.
+ */
+#[doc(hidden)]
+pub fn load_music(path: &str, options: &LoadOptions) -> AsyncMusic {
+    let (sender, receiver) = channel();
+    let progress = Arc::new(LoadProgress::new());
+    let job_path = path.to_string();
+    let job_options = options.clone();
+    let job_progress = progress.clone();
+    execute(proc() {
+        if !job_progress.is_cancelled() {
+            sender.send_opt(music::open_file(job_path.as_slice(), &job_options)).ok();
+        }
+    });
+
+    AsyncMusic {
+        receiver: RefCell::new(Some(receiver)),
+        file: RefCell::new(None),
+        status: RefCell::new(LoadPending),
+        progress: progress
+    }
+}
+
+/// Call a function with the pending loads of the task.
+fn with_pending_loads<T>(f: |&mut Vec<Rc<RefCell<PendingLoad>>>| -> T) -> T {
+    if pending_loads.get().is_none() {
+        pending_loads.replace(Some(RefCell::new(Vec::new())));
+    }
+    let loads = pending_loads.get().unwrap();
+    let mut loads = loads.borrow_mut();
+    f(&mut *loads)
+}
+
+/// Find the pending load of a SoundData.
+fn find_load(data: &Rc<RefCell<SoundData>>) -> Option<Rc<RefCell<PendingLoad>>> {
+    let ptr = &**data as *const RefCell<SoundData>;
+    with_pending_loads(|loads| {
+        loads.iter().find(|load| {
+            let load = load.borrow();
+            load.status == LoadPending &&
+                &*load.data as *const RefCell<SoundData> == ptr
+        }).map(|load| load.clone())
+    })
+}
+
+/**
+ * Register a source playing a SoundData, to set its buffer once loaded.
+ */
+#[doc(hidden)]
+pub fn add_source(data: &Rc<RefCell<SoundData>>, source: u32) -> () {
+    match find_load(data) {
+        Some(load) => load.borrow_mut().sources.push((source, false)),
+        None       => {}
+    }
+}
+
+/**
+ * Play a source when its samples are loaded.
+ *
+ * # Return
+ * true if the source is waiting for its samples, false otherwise.
+ */
+#[doc(hidden)]
+pub fn play_when_ready(source: u32, play: bool) -> bool {
+    with_pending_loads(|loads| {
+        for load in loads.iter() {
+            let mut load = load.borrow_mut();
+            for waiting in load.sources.iter_mut() {
+                if waiting.val0() == source {
+                    *waiting = (source, play);
+                    return true;
+                }
+            }
+        }
+        false
+    })
+}
+
+/**
+ * Forget a source deleted before its samples are loaded.
+ */
+#[doc(hidden)]
+pub fn remove_source(source: u32) -> () {
+    with_pending_loads(|loads| {
+        for load in loads.iter() {
+            load.borrow_mut().sources.retain(|&(s, _)| s != source);
+        }
+    })
+}
+
+/**
+ * Upload the samples decoded since the last call.
+ *
+ * Called by `ears::update`.
+ */
+#[doc(hidden)]
+pub fn process() -> () {
+    let loads = with_pending_loads(|loads| loads.clone());
+    for load in loads.iter() {
+        load.borrow_mut().poll();
+    }
+    with_pending_loads(|loads| loads.retain(|load| load.borrow().status == LoadPending));
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::{File, TempDir};
+    use std::io::fs::copy;
+
+    use sound_data::SoundData;
+    use sound::Sound;
+    use marker::Marker;
+    use sound_data;
+    use loader::{LoadReady, LoadFailed};
+    use music::Music;
+
+    #[test]
+    fn loader_load_async_OK() -> () {
+        let handle = SoundData::load_async("res/shot.wav");
+        let data = handle.get_data();
+
+        assert!(handle.wait().is_some());
+        assert_eq!(handle.get_status(), LoadReady);
+        assert_eq!(handle.get_progress(), 1.);
+        // The placeholder shared with the Sounds is replaced
+        assert_eq!(sound_data::get_channels(&*data.borrow()), 1);
+    }
+
+    #[test]
+    fn loader_load_async_markers_OK() -> () {
+        let dir = TempDir::new("ears_loader").unwrap();
+        let path = dir.path().join("shot.wav");
+        copy(&Path::new("res/shot.wav"), &path).unwrap();
+        File::create(&dir.path().join("shot.wav.markers")).write_str("10 hit\n").unwrap();
+
+        let handle = SoundData::load_async(path.as_str().unwrap());
+        let mut snd = Sound::new_with_data(handle.get_data()).unwrap();
+        snd.add_marker(20, "added");
+        assert!(handle.wait().is_some());
+        // The Sound takes the markers of the loaded file
+        let markers = snd.get_markers();
+        assert_eq!(markers, vec![Marker::new(10, "hit"), Marker::new(20, "added")]);
+    }
+
+    #[test]
+    fn loader_load_music_async_OK() -> () {
+        let handle = Music::load_async("res/shot.wav");
+
+        assert!(handle.wait().is_some());
+        assert_eq!(handle.get_status(), LoadReady);
+        // The Music is taken once
+        assert!(handle.take().is_none());
+    }
+
+    #[test]
+    fn loader_load_music_async_FAIL() -> () {
+        let handle = Music::load_async("toto.wav");
+
+        assert!(handle.wait().is_none());
+        match handle.get_status() {
+            LoadFailed(_) => {},
+            status        => panic!("Unexpected status {}", status)
+        }
+    }
+
+    #[test]
+    fn loader_load_async_FAIL() -> () {
+        let handle = SoundData::load_async("toto.wav");
+
+        assert!(handle.wait().is_none());
+        match handle.get_status() {
+            LoadFailed(_) => {},
+            status        => panic!("Unexpected status {}", status)
+        }
+    }
+}
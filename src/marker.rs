@@ -0,0 +1,200 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Timeline markers of Sounds and Musics.
+
+use std::io::{File, BufferedReader};
+
+use metadata::Metadata;
+
+/**
+ * A named position in a Sound or a Music.
+ *
+ * The markers are loaded from the cue points of the file and from a sidecar
+ * file named like the sound file with the `.markers` extension appended, for
+ * example `voice.wav.markers`. Each line of the sidecar file contains a
+ * position in frames and a name:
+ *
+ * ```Text
+ * # position name
+ * 0 intro
+ * 44100 mouth_open
+ * ```
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub struct Marker {
+    /// The position of the marker in frames
+    pub position: u64,
+    /// The name of the marker
+    pub name: String
+}
+
+impl Marker {
+    /**
+     * Create a new Marker.
+     *
+     * # Arguments
+     * * `position` - The position of the marker in frames
+     * * `name` - The name of the marker
+     */
+    pub fn new(position: u64, name: &str) -> Marker {
+        Marker {
+            position: position,
+            name: name.to_string()
+        }
+    }
+}
+
+/// Load the markers of a file from its cue points and its sidecar file.
+pub fn load_markers(path: &str, metadata: &Metadata) -> Vec<Marker> {
+    let mut markers: Vec<Marker> = metadata.cues.iter().map(|cue| {
+        if cue.name.is_empty() {
+            Marker::new(cue.position as u64, format!("cue {}", cue.id).as_slice())
+        } else {
+            Marker::new(cue.position as u64, cue.name.as_slice())
+        }
+    }).collect();
+    markers.push_all(load_sidecar(sidecar_path(path).as_slice()).as_slice());
+    markers.sort_by(|a, b| a.position.cmp(&b.position));
+    markers
+}
+
+/// Get the path of the sidecar marker file of a file.
+pub fn sidecar_path(path: &str) -> String {
+    format!("{}.markers", path)
+}
+
+/// Read a sidecar marker file, empty if the file doesn't exist.
+fn load_sidecar(path: &str) -> Vec<Marker> {
+    let path = Path::new(path);
+    if !path.exists() {
+        return Vec::new();
+    }
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) => { println!("{}", err); return Vec::new(); }
+    };
+
+    let mut markers = Vec::new();
+    for line in BufferedReader::new(file).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_)   => break
+        };
+        match parse_line(line.as_slice()) {
+            Some(marker) => markers.push(marker),
+            None         => {}
+        }
+    }
+    markers
+}
+
+/// Parse a line of a sidecar file: a position in frames and a name.
+fn parse_line(line: &str) -> Option<Marker> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with("#") {
+        return None;
+    }
+    let (position, name) = match line.find(|c: char| c.is_whitespace()) {
+        Some(i) => (line.slice_to(i), line.slice_from(i).trim()),
+        None    => (line, "")
+    };
+    match from_str::<u64>(position) {
+        Some(position) => Some(Marker::new(position, name)),
+        None           => {
+            println!("Invalid marker: {}", line);
+            None
+        }
+    }
+}
+
+/// Insert a marker keeping the markers sorted by position.
+pub fn insert(markers: &mut Vec<Marker>, marker: Marker) -> () {
+    let index = markers.iter()
+                       .position(|m| m.position > marker.position)
+                       .unwrap_or(markers.len());
+    markers.insert(index, marker);
+}
+
+/**
+ * Get the markers crossed by the playback.
+ *
+ * # Arguments
+ * * `markers` - The sorted markers
+ * * `from` - The last position checked, None if the playback just started
+ * * `to` - The current position
+ *
+ * # Return
+ * The markers after `from` and until `to`.
+ */
+pub fn crossed(markers: &[Marker], from: Option<u64>, to: u64) -> Vec<Marker> {
+    markers.iter().filter(|m| {
+        let after = match from {
+            Some(from) => m.position > from,
+            None       => true
+        };
+        after && m.position <= to
+    }).map(|m| m.clone()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use marker::{Marker, crossed, insert, parse_line};
+
+    fn markers() -> Vec<Marker> {
+        vec![Marker::new(0, "start"),
+             Marker::new(100, "a"),
+             Marker::new(200, "b")]
+    }
+
+    #[test]
+    fn marker_crossed_OK() -> () {
+        let markers = markers();
+
+        assert_eq!(crossed(markers.as_slice(), None, 50), vec![Marker::new(0, "start")]);
+        assert_eq!(crossed(markers.as_slice(), Some(50), 100), vec![Marker::new(100, "a")]);
+        assert_eq!(crossed(markers.as_slice(), Some(100), 150), vec![]);
+        assert_eq!(crossed(markers.as_slice(), Some(100), 1000).len(), 1);
+    }
+
+    #[test]
+    fn marker_insert_OK() -> () {
+        let mut markers = markers();
+        insert(&mut markers, Marker::new(150, "c"));
+
+        assert_eq!(markers.as_slice()[2], Marker::new(150, "c"));
+    }
+
+    #[test]
+    fn marker_parse_line_OK() -> () {
+        assert_eq!(parse_line("44100 mouth open"), Some(Marker::new(44100, "mouth open")));
+        assert_eq!(parse_line("  12  "), Some(Marker::new(12, "")));
+    }
+
+    #[test]
+    fn marker_parse_line_FAIL() -> () {
+        assert_eq!(parse_line("# comment"), None);
+        assert_eq!(parse_line(""), None);
+        assert_eq!(parse_line("abc name"), None);
+    }
+}
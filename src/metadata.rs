@@ -0,0 +1,289 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The extended metadata of the audio files: broadcast info, cue points and
+//! instrument.
+
+use sndfile::{SndFile, ReadWrite, NoError};
+
+/**
+ * The Broadcast Wave Format informations of a file (the `bext` chunk).
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub struct BroadcastInfo {
+    /// A description of the sound, 256 characters max
+    pub description: String,
+    /// The name of the originator, 32 characters max
+    pub originator: String,
+    /// The reference of the originator, 32 characters max
+    pub originator_reference: String,
+    /// The creation date, as yyyy-mm-dd
+    pub origination_date: String,
+    /// The creation time, as hh-mm-ss
+    pub origination_time: String,
+    /// The first sample since midnight
+    pub time_reference: u64,
+    /// The version of the BWF
+    pub version: i16,
+    /// The SMPTE UMID, 64 bytes max
+    pub umid: String,
+    /// The coding history, 256 characters max
+    pub coding_history: String
+}
+
+/// A cue point of a file.
+#[deriving(Clone, PartialEq, Show)]
+pub struct CuePoint {
+    /// The identifier of the cue point
+    pub id: i32,
+    /// The position of the cue point in frames
+    pub position: u32,
+    /// The name of the cue point
+    pub name: String
+}
+
+/// The way a loop of an instrument is played.
+#[deriving(Clone, PartialEq, Show)]
+pub enum LoopMode {
+    /// Not looped
+    LoopNone,
+    /// Played forward
+    LoopForward,
+    /// Played backward
+    LoopBackward,
+    /// Played forward then backward
+    LoopAlternating
+}
+
+/// A loop of an instrument.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Loop {
+    /// The way the loop is played
+    pub mode: LoopMode,
+    /// The first frame of the loop
+    pub start: u32,
+    /// The frame after the end of the loop
+    pub end: u32,
+    /// The count of repetitions, 0 for infinite
+    pub count: u32
+}
+
+/// The instrument informations of a sampled sound.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Instrument {
+    /// The gain in dB
+    pub gain: i32,
+    /// The MIDI root note
+    pub base_note: i8,
+    /// The detune of the root note in cents
+    pub detune: i8,
+    /// The lowest velocity played by the sample
+    pub velocity_low: i8,
+    /// The highest velocity played by the sample
+    pub velocity_high: i8,
+    /// The lowest MIDI note played by the sample
+    pub key_low: i8,
+    /// The highest MIDI note played by the sample
+    pub key_high: i8,
+    /// The loops of the sample, 16 max
+    pub loops: Vec<Loop>
+}
+
+/**
+ * The extended metadata of a file.
+ *
+ * The fields are empty if the format of the file doesn't store them.
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub struct Metadata {
+    /// The broadcast informations
+    pub broadcast_info: Option<BroadcastInfo>,
+    /// The cue points, 100 max
+    pub cues: Vec<CuePoint>,
+    /// The instrument informations
+    pub instrument: Option<Instrument>
+}
+
+/// Metadata without any field.
+pub fn empty() -> Metadata {
+    Metadata {
+        broadcast_info: None,
+        cues: Vec::new(),
+        instrument: None
+    }
+}
+
+/// Read the extended metadata of an open file.
+pub fn get_metadata(file: &SndFile) -> Metadata {
+    Metadata {
+        broadcast_info: file.get_broadcast_info(),
+        cues: file.get_cues(),
+        instrument: file.get_instrument()
+    }
+}
+
+/**
+ * Set the extended metadata of an open file.
+ *
+ * The empty fields are not set.
+ *
+ * # Return
+ * Ok(()) if all the fields are set, Err with the fields the format of the
+ * file can't store otherwise.
+ */
+pub fn set_metadata(file: &mut SndFile, metadata: &Metadata) -> Result<(), String> {
+    let mut failed = Vec::new();
+
+    match metadata.broadcast_info {
+        Some(ref info) if !file.set_broadcast_info(info) => failed.push("broadcast info"),
+        _                                                => {}
+    }
+    if !metadata.cues.is_empty() && !file.set_cues(metadata.cues.as_slice()) {
+        failed.push("cue points");
+    }
+    match metadata.instrument {
+        Some(ref instrument) if !file.set_instrument(instrument) => failed.push("instrument"),
+        _                                                        => {}
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Can't write the {} in this file.", failed.as_slice().connect(", ")))
+    }
+}
+
+/**
+ * Write extended metadata in an audio file.
+ *
+ * The empty fields of the metadata are not written. libsndfile can only
+ * write them in a file which can be opened in read/write mode, like WAV or
+ * AIFF.
+ *
+ * # Arguments
+ * * `path` - The path of the file
+ * * `metadata` - The metadata to write
+ *
+ * # Return
+ * Ok(()) on success, Err with a description of the error otherwise.
+ *
+ * # Example
+ * ```Rust
+ * let mut metadata = ears::SoundData::new("path/to/sound.wav").unwrap().get_metadata();
+ * metadata.cues.push(ears::CuePoint { id: 1, position: 44100, name: "hit".to_string() });
+ *
+ * match ears::write_metadata("path/to/sound.wav", &metadata) {
+ *     Ok(_)    => {},
+ *     Err(err) => println!("{}", err)
+ * }
+ * ```
+ */
+pub fn write_metadata(path: &str, metadata: &Metadata) -> Result<(), String> {
+    let mut file = match SndFile::new(path, ReadWrite) {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+    let result = set_metadata(&mut file, metadata);
+    match file.close() {
+        NoError => result,
+        error   => Err(SndFile::error_number(error))
+    }
+}
+
+/// AudioMetadata trait implemented by all struct loaded from a file.
+pub trait AudioMetadata {
+    /// Get the extended metadata of the audio source.
+    fn get_metadata(&self) -> Metadata;
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::TempDir;
+
+    use metadata::{empty, write_metadata, get_metadata, CuePoint, Instrument,
+                   Loop, LoopForward};
+    use io::{SoundReader, SoundWriter, FormatWav, FormatPcm16};
+    use sndfile::{SndFile, Read};
+
+    fn create_file(path: &str) -> () {
+        let mut info = SoundReader::open("res/shot.wav").unwrap().get_info();
+        info.channels = 1;
+        info.format = (FormatWav | FormatPcm16) as i32;
+        let mut writer = SoundWriter::create(path, info).unwrap();
+        writer.write(Vec::from_elem(1000, 0i16).as_slice()).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn metadata_write_cues_OK() -> () {
+        let dir = TempDir::new("ears_cues").unwrap();
+        let path = dir.path().join("test.wav");
+        let path = path.as_str().unwrap();
+        create_file(path);
+        let mut metadata = empty();
+        metadata.cues.push(CuePoint { id: 1, position: 100, name: "".to_string() });
+        metadata.cues.push(CuePoint { id: 2, position: 500, name: "".to_string() });
+
+        write_metadata(path, &metadata).unwrap();
+
+        let file = SndFile::new(path, Read).unwrap();
+        let cues = get_metadata(&file).cues;
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues.as_slice()[1].position, 500);
+    }
+
+    #[test]
+    fn metadata_write_instrument_OK() -> () {
+        let dir = TempDir::new("ears_instrument").unwrap();
+        let path = dir.path().join("test.wav");
+        let path = path.as_str().unwrap();
+        create_file(path);
+        let mut metadata = empty();
+        metadata.instrument = Some(Instrument {
+            gain: 0,
+            base_note: 60,
+            detune: 0,
+            velocity_low: 1,
+            velocity_high: 127,
+            key_low: 0,
+            key_high: 127,
+            loops: vec![Loop { mode: LoopForward, start: 100, end: 900, count: 0 }]
+        });
+
+        write_metadata(path, &metadata).unwrap();
+
+        let file = SndFile::new(path, Read).unwrap();
+        let instrument = get_metadata(&file).instrument.unwrap();
+        assert_eq!(instrument.base_note, 60);
+        assert_eq!(instrument.loops.len(), 1);
+        assert_eq!(instrument.loops.as_slice()[0].start, 100);
+    }
+
+    #[test]
+    fn metadata_write_FAIL() -> () {
+        match write_metadata("toto.wav", &empty()) {
+            Ok(_)  => panic!(),
+            Err(_) => {}
+        }
+    }
+}
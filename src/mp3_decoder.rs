@@ -0,0 +1,431 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+/*!
+ * Decode the MP3 files, enabled by the `mp3` feature.
+ *
+ * The frames are decoded in pure Rust by the layer3 module. The tags are read
+ * from the ID3v1 tag and the text frames of the ID3v2.3 and ID3v2.4 tags.
+ */
+
+use std::cmp;
+use std::str;
+use std::io::File;
+
+use sndfile::SndInfo;
+use decoder::{Decoder, id3v2_size};
+use layer3::{Layer3Decoder, MAX_SAMPLES_PER_FRAME};
+use audio_tags::{Tags, set_comment};
+use audio_tags;
+
+/// Decode a text of an ID3 tag in ISO-8859-1 or UTF-8.
+fn id3_text(bytes: &[u8], encoding: u8) -> Option<String> {
+    let text: String = match encoding {
+        0 => bytes.iter().map(|&byte| byte as char).collect(),
+        3 => match str::from_utf8(bytes) {
+            Some(text) => text.to_string(),
+            None       => return None
+        },
+        // UTF-16 is not supported
+        _ => return None
+    };
+    Some(text.as_slice().trim_right_chars('\0').trim().to_string())
+}
+
+/**
+ * Read the text frames of an ID3v2.3 or ID3v2.4 tag.
+ *
+ * # Argument
+ * * `tag` - The tag, with its header
+ */
+fn read_id3v2(tag: &[u8], tags: &mut Tags) -> () {
+    let version = tag[3];
+    if (version != 3 && version != 4) || tag[5] & 0x40 != 0 {
+        // ID3v2.2 and the extended headers are not supported
+        return;
+    }
+    let mut position = 10;
+    while position + 10 <= tag.len() {
+        let id = tag.slice(position, position + 4);
+        let size_bytes = tag.slice(position + 4, position + 8);
+        let size = if version == 4 {
+            size_bytes.iter().fold(0u, |size, &byte| (size << 7) | (byte & 0x7F) as uint)
+        } else {
+            size_bytes.iter().fold(0u, |size, &byte| (size << 8) | byte as uint)
+        };
+        if id[0] == 0 || size == 0 || position + 10 + size > tag.len() {
+            break;
+        }
+        let content = tag.slice(position + 10, position + 10 + size);
+        let name = match str::from_utf8(id).unwrap_or("") {
+            "TIT2"          => Some("TITLE"),
+            "TPE1"          => Some("ARTIST"),
+            "TALB"          => Some("ALBUM"),
+            "TYER" | "TDRC" => Some("DATE"),
+            "TCON"          => Some("GENRE"),
+            "TRCK"          => Some("TRACKNUMBER"),
+            "TCOP"          => Some("COPYRIGHT"),
+            "TSSE"          => Some("ENCODER"),
+            _               => None
+        };
+        match name.and_then(|name| id3_text(content.slice_from(1), content[0]).map(|text| (name, text))) {
+            Some((name, text)) => set_comment(tags, name, text.as_slice()),
+            None               => {}
+        }
+        position += 10 + size;
+    }
+}
+
+/// Read the ID3v1 tag at the end of the file.
+fn read_id3v1(data: &[u8], tags: &mut Tags) -> () {
+    if data.len() < 128 || !data.slice_from(data.len() - 128).starts_with(b"TAG") {
+        return;
+    }
+    let tag = data.slice_from(data.len() - 128);
+    let fields = [("TITLE", 3u, 33u), ("ARTIST", 33, 63), ("ALBUM", 63, 93),
+                  ("DATE", 93, 97), ("COMMENT", 97, 127)];
+    for &(name, start, end) in fields.iter() {
+        match id3_text(tag.slice(start, end), 0) {
+            Some(ref text) if !text.is_empty() => set_comment(tags, name, text.as_slice()),
+            _                                  => {}
+        }
+    }
+}
+
+/// Read the tags at the beginning and at the end of a file.
+fn read_tags(data: &[u8]) -> Tags {
+    let mut tags = audio_tags::empty();
+    let size = id3v2_size(data);
+    if size > 10 && size <= data.len() {
+        read_id3v2(data.slice_to(size), &mut tags);
+    }
+    read_id3v1(data, &mut tags);
+    tags
+}
+
+/**
+ * Check whether a frame is the Xing or Info frame of a VBR file.
+ *
+ * This frame holds the length of the file and decodes as silence.
+ */
+fn is_xing_frame(frame: &[u8]) -> bool {
+    let mpeg1 = frame[1] & 0x08 != 0;
+    let mono = frame[3] & 0xC0 == 0xC0;
+    let side_info = match (mpeg1, mono) {
+        (true, true)   => 17,
+        (true, false)  => 32,
+        (false, true)  => 9,
+        (false, false) => 17
+    };
+    let offset = 4 + side_info + if frame[1] & 1 == 0 { 2 } else { 0 };
+    frame.len() >= offset + 4 &&
+        (frame.slice(offset, offset + 4) == b"Xing" || frame.slice(offset, offset + 4) == b"Info")
+}
+
+/**
+ * Decode a MP3 file.
+ *
+ * The file is read in memory when it is opened, and its frames are decoded
+ * on demand.
+ */
+pub struct Mp3Decoder {
+    data: Vec<u8>,
+    start: uint,
+    end: uint,
+    position: uint,
+    decoder: Layer3Decoder,
+    pcm: Vec<f32>,
+    pcm_position: uint,
+    pcm_len: uint,
+    frame_samples: uint,
+    infos: SndInfo,
+    tags: Tags
+}
+
+impl Mp3Decoder {
+    /**
+     * Open a MP3 file.
+     *
+     * # Argument
+     * * `path` - The path of the file
+     *
+     * # Return
+     * The Mp3Decoder on success, the description of the error otherwise.
+     */
+    pub fn new(path: &str) -> Result<Mp3Decoder, String> {
+        let data = match File::open(&Path::new(path)).read_to_end() {
+            Ok(data) => data,
+            Err(e)   => return Err(format!("Can't open {}: {}", path, e))
+        };
+        let tags = read_tags(data.as_slice());
+        let mut start = cmp::min(id3v2_size(data.as_slice()), data.len());
+        let mut end = data.len();
+        if end - start >= 128 && data.slice_from(end - 128).starts_with(b"TAG") {
+            end -= 128;
+        }
+
+        // Read the headers of all the frames to get the format and the length
+        let mut scanner = Layer3Decoder::new();
+        let mut position = start;
+        let mut format = None;
+        let mut frames = 0u;
+        while position < end {
+            let (samples, info) = scanner.decode_frame(data.slice(position, end), None);
+            if info.frame_bytes == 0 {
+                break;
+            }
+            if info.layer == 3 {
+                match format {
+                    None => {
+                        let frame = data.slice(position + info.frame_offset,
+                                               position + info.frame_bytes);
+                        if is_xing_frame(frame) {
+                            start = position + info.frame_bytes;
+                        } else {
+                            format = Some((info.channels, info.hz));
+                            frames += samples;
+                        }
+                    },
+                    Some((channels, _)) if channels == info.channels => frames += samples,
+                    Some(_) => {}
+                }
+            }
+            position += info.frame_bytes;
+        }
+
+        let (channels, samplerate) = match format {
+            Some(format) => format,
+            None         => return Err(format!("{} is not a MP3 file.", path))
+        };
+        Ok(Mp3Decoder {
+            data: data,
+            start: start,
+            end: end,
+            position: start,
+            decoder: Layer3Decoder::new(),
+            pcm: Vec::from_elem(MAX_SAMPLES_PER_FRAME, 0f32),
+            pcm_position: 0,
+            pcm_len: 0,
+            frame_samples: if samplerate >= 32000 { 1152 } else { 576 },
+            infos: SndInfo {
+                frames: frames as i64,
+                samplerate: samplerate as i32,
+                channels: channels as i32,
+                format: 0,
+                sections: 1,
+                seekable: 1
+            },
+            tags: tags
+        })
+    }
+
+    /**
+     * Decode the next Layer III frame with the channels of the file.
+     *
+     * The frames without their bit reservoir, like the first frames after
+     * a rewind, are decoded as silence to keep the length of the file.
+     *
+     * # Return
+     * true if a frame is decoded, false at the end of the file.
+     */
+    fn decode_next(&mut self) -> bool {
+        let channels = self.infos.channels as uint;
+        while self.position < self.end {
+            let (samples, info) = self.decoder.decode_frame(self.data.slice(self.position, self.end),
+                                                            Some(self.pcm.as_mut_slice()));
+            if info.frame_bytes == 0 {
+                break;
+            }
+            self.position += info.frame_bytes;
+            if info.layer != 3 || info.channels != channels {
+                continue;
+            }
+            let samples = if samples == 0 {
+                for sample in self.pcm.slice_to_mut(self.frame_samples * channels).iter_mut() {
+                    *sample = 0.;
+                }
+                self.frame_samples
+            } else {
+                samples
+            };
+            self.pcm_position = 0;
+            self.pcm_len = samples * channels;
+            return true;
+        }
+        self.position = self.end;
+        false
+    }
+}
+
+impl Decoder for Mp3Decoder {
+    fn get_sndinfo(&self) -> SndInfo {
+        self.infos.clone()
+    }
+
+    fn readf_f32(&mut self, array: &mut [f32], frames: i64) -> i64 {
+        let channels = self.infos.channels as uint;
+        let wanted = cmp::min(frames as uint, array.len() / channels) * channels;
+        let mut written = 0;
+        while written < wanted {
+            if self.pcm_position == self.pcm_len {
+                if !self.decode_next() {
+                    break;
+                }
+            }
+            let count = cmp::min(wanted - written, self.pcm_len - self.pcm_position);
+            array.slice_mut(written, written + count)
+                 .copy_from(self.pcm.slice(self.pcm_position, self.pcm_position + count));
+            written += count;
+            self.pcm_position += count;
+        }
+        (written / channels) as i64
+    }
+
+    fn rewind(&mut self) -> bool {
+        self.decoder = Layer3Decoder::new();
+        self.position = self.start;
+        self.pcm_position = 0;
+        self.pcm_len = 0;
+        true
+    }
+
+    fn get_tags(&self) -> Tags {
+        self.tags.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::{File, TempDir};
+    use std::num::Float;
+
+    use audio_tags;
+    use decoder::{Decoder, id3v2_size};
+    use mp3_decoder::{Mp3Decoder, read_id3v2, read_id3v1};
+
+    #[test]
+    fn mp3_decoder_id3_OK() -> () {
+        let mut tag = b"ID3\x03\x00\x00\x00\x00\x00\x15".to_vec();
+        tag.push_all(b"TIT2\x00\x00\x00\x05\x00\x00\x00Shot");
+        tag.grow(0x15 - 15, 0u8);
+        assert_eq!(id3v2_size(tag.as_slice()), 0x1F);
+
+        let mut tags = audio_tags::empty();
+        read_id3v2(tag.as_slice(), &mut tags);
+        assert_eq!(tags.title.as_slice(), "Shot");
+
+        let mut data = Vec::from_elem(128, 0u8);
+        data.as_mut_slice().slice_mut(0, 9).copy_from(b"TAGTitle1");
+        data.as_mut_slice().slice_mut(33, 40).copy_from(b"Artist1");
+        let mut tags = audio_tags::empty();
+        read_id3v1(data.as_slice(), &mut tags);
+        assert_eq!(tags.title.as_slice(), "Title1");
+        assert_eq!(tags.artist.as_slice(), "Artist1");
+    }
+
+    #[test]
+    fn mp3_decoder_silence_OK() -> () {
+        let dir = TempDir::new("ears_mp3").unwrap();
+        let path = dir.path().join("test.mp3");
+        let path = path.as_str().unwrap();
+
+        // An ID3v2 tag and 4 frames of 417 bytes at 128 kbps, 44100 Hz
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x00\x00".to_vec();
+        for _ in range(0u, 4) {
+            data.push_all(b"\xFF\xFB\x90\x00");
+            data.grow(417 - 4, 0u8);
+        }
+        File::create(&Path::new(path)).write(data.as_slice()).unwrap();
+
+        let mut decoder = Mp3Decoder::new(path).unwrap();
+        let infos = decoder.get_sndinfo();
+        assert_eq!(infos.samplerate, 44100);
+        assert_eq!(infos.channels, 2);
+        assert_eq!(infos.frames, 4 * 1152);
+
+        let mut samples = Vec::from_elem(1000 * 2, 1f32);
+        let mut frames = 0;
+        loop {
+            let read = decoder.readf_f32(samples.as_mut_slice(), 1000);
+            if read == 0 {
+                break;
+            }
+            assert!(samples.slice_to(read as uint * 2).iter().all(|&sample| sample == 0.));
+            frames += read;
+        }
+        assert_eq!(frames, 4 * 1152);
+        assert!(decoder.rewind());
+        assert_eq!(decoder.readf_f32(samples.as_mut_slice(), 1000), 1000);
+    }
+
+    #[test]
+    fn mp3_decoder_sine_OK() -> () {
+        // 0.5 second of a 440 Hz sine, of amplitude 0.5 on the left and 0.25
+        // on the right, in 21 joint stereo frames at 128 kbps. The expected
+        // values were measured on the output of minimp3.
+        let mut decoder = Mp3Decoder::new("res/sine440.mp3").unwrap();
+        let infos = decoder.get_sndinfo();
+        assert_eq!(infos.samplerate, 44100);
+        assert_eq!(infos.channels, 2);
+        assert_eq!(infos.frames, 21 * 1152);
+        assert_eq!(decoder.get_tags().title.as_slice(), "Sine 440");
+
+        let mut samples = Vec::new();
+        let mut buffer = Vec::from_elem(1000 * 2, 0f32);
+        loop {
+            let read = decoder.readf_f32(buffer.as_mut_slice(), 1000);
+            if read == 0 {
+                break;
+            }
+            samples.push_all(buffer.slice_to(read as uint * 2));
+        }
+        assert_eq!(samples.len(), 21 * 1152 * 2);
+
+        let expected = [(1000u, -0.000023f32, 0.000039f32), (6000, 0.455081, 0.227531),
+                        (11000, 0.479703, 0.239841), (16000, 0.270994, 0.135504),
+                        (21000, -0.069614, -0.034809)];
+        for &(frame, left, right) in expected.iter() {
+            assert!((samples.as_slice()[frame * 2] - left).abs() < 0.001);
+            assert!((samples.as_slice()[frame * 2 + 1] - right).abs() < 0.001);
+        }
+
+        // A quarter of second after the delay of the decoder
+        for channel in range(0u, 2) {
+            let window: Vec<f32> = range(2048u, 2048 + 11025).map(|frame| {
+                samples.as_slice()[frame * 2 + channel]
+            }).collect();
+            let rms = (window.iter().fold(0f32, |sum, sample| sum + *sample * *sample)
+                       / window.len() as f32).sqrt();
+            let crossings = window.as_slice().windows(2).filter(|pair| {
+                pair[0] < 0. && pair[1] >= 0.
+            }).count();
+            assert!((rms - [0.353549f32, 0.176770][channel]).abs() < 0.005);
+            assert_eq!(crossings, 110);
+        }
+    }
+
+    #[test]
+    fn mp3_decoder_open_FAIL() -> () {
+        assert!(Mp3Decoder::new("toto.mp3").is_err());
+    }
+}
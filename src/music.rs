@@ -21,19 +21,32 @@
 
 //! Play Music easily.
 
-use std::io::timer::sleep;
-use std::mem;
-use std::task;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, SeqCst};
 use std::time::Duration;
-use libc::c_void;
-use std::vec::Vec;
 
 use internal::OpenAlData;
+use load_options::{LoadOptions, get_default_load_options};
 use openal::{ffi, al};
-use sndfile::{SndInfo, SndFile, Read, SeekSet};
-use states::{State, Initial, Playing, Paused, Stopped};
+use sndfile::SndInfo;
+use states::{State, Playing, Paused};
 use audio_controller::AudioController;
-use audio_tags::{Tags, AudioTags, get_sound_tags};
+use stream::{Streamer, FileProvider, StreamEvent, MarkerReached};
+use stream;
+use bus::Bus;
+use bus;
+use source_control;
+use clock;
+use audio_tags::{Tags, AudioTags};
+use metadata::{Metadata, AudioMetadata};
+use marker::{Marker, load_markers};
+use marker;
+use channels;
+use hot_reload;
+use decoder::Decoder;
+use decoder;
+use loader::AsyncMusic;
+use loader;
 
 /**
  * Play Music easily.
@@ -63,16 +76,30 @@ pub struct Music {
     al_source: u32,
     /// The internal OpenAL buffers
     al_buffers: [u32, ..2],
-    /// The file open with libmscfile
-    file: Option<Box<SndFile>>,
+    /// The Streamer of the file, when the Music is not streaming
+    streamer: Option<Streamer>,
+    /// Receive the Streamer at the end of the streaming task
+    streamer_receiver: Option<Receiver<Streamer>>,
+    /// Set to terminate the streaming task
+    stop_flag: Arc<AtomicBool>,
     /// Information of the file
     file_infos: SndInfo,
-    /// Quantity of sample to read each time
-    sample_to_read: i32,
-    /// Format of the sample
-    sample_format: i32,
     /// Audio tags
-    sound_tags: Tags
+    sound_tags: Tags,
+    /// The broadcast info, cue points and instrument
+    metadata: Metadata,
+    /// The markers, shared with the streaming task
+    markers: Arc<Mutex<Vec<Marker>>>,
+    /// Receive the events of the streaming task
+    events: Receiver<StreamEvent>,
+    /// The Bus the Music is assigned to
+    bus: Option<Bus>,
+    /// The path of the file
+    path: String,
+    /// The count of channels sent to OpenAL
+    channels: uint,
+    /// The warning about the positioning of several channels was printed
+    unpositioned_warned: bool
 }
 
 impl Music {
@@ -86,129 +113,237 @@ impl Music {
      * An Option containing Some(Music) on success, None otherwise
      */
     pub fn new(path: &str) -> Option<Music> {
+        Music::new_with_options(path, &get_default_load_options())
+    }
+
+    /**
+     * Create a new Music with options
+     *
+     * # Arguments
+     * * `path` - The path of the file to load the music
+     * * `options` - How to load the samples
+     *
+     * # Return
+     * An Option containing Some(Music) on success, None otherwise
+     */
+    pub fn new_with_options(path: &str, options: &LoadOptions) -> Option<Music> {
         // Check that OpenAL is launched
         check_openal_context!(None);
-        // Retrieve File and Music datas
-        let file = match SndFile::new(path, Read) {
-            Ok(file)    => box file,
-            Err(err)    => { println!("{}", err); return None; }
-        };
-        let infos = file.get_sndinfo();
-
-        // create the source and the buffers
-        let mut source_id = 0;
-        let mut buffer_ids = [0, ..2];
-        // create the source
-        al::alGenSources(1, &mut source_id);
-        // create the buffers
-        al::alGenBuffers(2, &mut buffer_ids[0]);
-
-        // Retrieve format informations
-        let format =  match al::get_channels_format(infos.channels) {
-            Some(fmt) => fmt,
-            None => {
-                println!("Internal error : unrecognized format.");
-                return None;
+        match open_file(path, options) {
+            Ok(file) => from_file(file),
+            Err(err) => { println!("{}", err); None }
+        }
+    }
+
+    /**
+     * Open a Music in the background.
+     *
+     * The file is opened and its format negotiated by a worker task, the
+     * Music is created by the handle once it's ready.
+     *
+     * The file is opened with the options of `set_default_load_options`.
+     *
+     * # Argument
+     * * `path` - The path of the file to load the music
+     *
+     * # Return
+     * A handle to follow the loading.
+     *
+     * # Example
+     * ```Rust
+     * let handle = Music::load_async("path/to/my/Music.flac");
+     * let mut msc = handle.wait().unwrap();
+     * msc.play();
+     * ```
+     */
+    pub fn load_async(path: &str) -> AsyncMusic {
+        Music::load_async_with_options(path, &get_default_load_options())
+    }
+
+    /**
+     * Open a Music in the background with options.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load the music
+     * * `options` - How to load the samples
+     *
+     * # Return
+     * A handle to follow the loading.
+     */
+    pub fn load_async_with_options(path: &str, options: &LoadOptions) -> AsyncMusic {
+        loader::load_music(path, options)
+    }
+
+    /// Get back the Streamer, wait for the end of the streaming task if needed.
+    fn take_streamer(&mut self) -> Option<Streamer> {
+        match self.streamer.take() {
+            Some(streamer) => Some(streamer),
+            None           => match self.streamer_receiver.take() {
+                Some(receiver) => {
+                    // The synchronous Streamers stop at their next step
+                    stream::pump();
+                    receiver.recv_opt().ok()
+                },
+                None           => None
             }
-        };
+        }
+    }
 
-        // Check if there is OpenAL internal error
-        match al::openal_has_error() {
-            Some(err) => { println!("{}", err); return None; },
-            None => {}
+    fn process_music(&mut self, start: Option<Duration>) -> () {
+        // Terminate a streaming task still waiting for a scheduled start
+        self.stop_flag.store(true, SeqCst);
+        let mut streamer = match self.take_streamer() {
+            Some(streamer) => streamer,
+            None           => return
         };
 
-        let sound_tags = get_sound_tags(&*file);
-
-        Some( Music {
-            al_source: source_id,
-            al_buffers: buffer_ids,
-            file: Some(file),
-            file_infos: infos,
-            sample_to_read: 50000,
-            sample_format: format,
-            sound_tags: sound_tags
-        })
-    }
-
-    fn process_music(&mut self) -> () {
-        let (chan, port) = channel();
-        let sample_t_r = self.sample_to_read;
-        let sample_rate = self.file_infos.samplerate;
-        let sample_format = self.sample_format;
-        let al_source = self.al_source;
-        let al_buffers = self.al_buffers;
-
-        // create buff
-        let mut samples = Vec::from_elem(sample_t_r as uint, 0i16);
-
-        // full buff1
-        let mut len = mem::size_of::<i16>() *
-            self.file.as_mut().unwrap().read_i16(samples.as_mut_slice(), sample_t_r as i64) as uint;
-        al::alBufferData(al_buffers[0],
-                         sample_format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         sample_rate);
-
-        // full buff2
-        samples.clear();
-        len = mem::size_of::<i16>() *
-            self.file.as_mut().unwrap().read_i16(samples.as_mut_slice(), sample_t_r as i64) as uint;
-        al::alBufferData(al_buffers[1],
-                         sample_format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         sample_rate);
-
-        // Queue the buffers
-        al::alSourceQueueBuffers(al_source, 2, &al_buffers[0]);
+        // Fill the buffers from the beginning of the file
+        streamer.get_provider().rewind();
+        streamer.prefill();
 
         // Launche the Music
-        al::alSourcePlay(al_source);
-
-        task::spawn(proc() {
-            match OpenAlData::check_al_context() {
-                Ok(_)       => {},
-                Err(err)    => { println!("{}", err);}
-            };
-
-            let mut file: Box<SndFile> = port.recv();
-            let mut samples = Vec::from_elem(sample_t_r as uint, 0i16);
-            let mut status = ffi::AL_PLAYING;
-            let mut i = 0;
-            let mut buf = 0;
-            let mut read;
-
-            while status != ffi::AL_STOPPED {
-                // wait a bit
-                sleep(Duration::milliseconds(50i64));
-                if status == ffi::AL_PLAYING {
-                    al::alGetSourcei(al_source,
-                                     ffi::AL_BUFFERS_PROCESSED,
-                                     &mut i);
-                    if i != 0 {
-                        samples.clear();
-                        al::alSourceUnqueueBuffers(al_source, 1, &mut buf);
-                        read = file.read_i16(samples.as_mut_slice(), sample_t_r as i64) *
-                            mem::size_of::<i16>() as i64;
-                        al::alBufferData(buf,
-                                         sample_format,
-                                         samples.as_ptr() as *mut c_void,
-                                         read as i32,
-                                         sample_rate);
-                        al::alSourceQueueBuffers(al_source, 1, &buf);
-                    }
-                }
-                // Get source status
-                status = al::alGetState(al_source);
+        match start {
+            Some(time) => clock::schedule_play(self.al_source,
+                                               time,
+                                               self.file_infos.samplerate,
+                                               None),
+            None       => al::alSourcePlay(self.al_source)
+        }
+
+        self.streamer_receiver = Some(stream::spawn(streamer));
+    }
+
+    /**
+     * Assign the Music to a Bus.
+     *
+     * The Music keeps its volume and its pitch, which are now relative to
+     * the Bus.
+     *
+     * # Argument
+     * `bus` - The Bus to assign the Music to.
+     */
+    pub fn set_bus(&mut self, bus: &Bus) -> () {
+        check_openal_context!(());
+
+        let volume = self.get_volume();
+        let pitch = self.get_pitch();
+        self.remove_bus();
+        bus::add_source(bus, self.al_source, volume, pitch);
+        self.bus = Some(bus.clone());
+    }
+
+    /**
+     * Remove the Music from its Bus.
+     *
+     * The volume and the pitch of the Music are no longer modified by the Bus.
+     */
+    pub fn remove_bus(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+    }
+
+    /**
+     * Get the Bus of the Music.
+     *
+     * # Return
+     * Some(Bus) if the Music is assigned to a Bus, None otherwise.
+     */
+    pub fn get_bus(&self) -> Option<Bus> {
+        self.bus.clone()
+    }
+
+    /**
+     * Add a marker to the Music.
+     *
+     * # Arguments
+     * * `position` - The position of the marker in frames
+     * * `name` - The name of the marker
+     */
+    pub fn add_marker(&mut self, position: u64, name: &str) -> () {
+        marker::insert(&mut *self.markers.lock(), Marker::new(position, name));
+    }
+
+    /**
+     * Get the markers of the Music.
+     *
+     * # Return
+     * The markers loaded from the file and the added markers, sorted by
+     * position.
+     */
+    pub fn get_markers(&self) -> Vec<Marker> {
+        self.markers.lock().clone()
+    }
+
+    /// Remove all the markers of the Music.
+    pub fn clear_markers(&mut self) -> () {
+        self.markers.lock().clear();
+    }
+
+    /**
+     * Get the markers crossed by the playback since the last call.
+     *
+     * The markers are detected by the streaming task, call this function
+     * regularly to be notified of them.
+     *
+     * # Return
+     * The crossed markers, in the order of the playback.
+     */
+    pub fn poll_markers(&mut self) -> Vec<Marker> {
+        let mut crossed = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(MarkerReached(marker)) => crossed.push(marker),
+                Ok(_)                     => {},
+                Err(_)                    => break
             }
-            al::alSourcei(al_source, ffi::AL_BUFFER, 0);
-        });
-        let file = self.file.as_ref().unwrap().clone();
-        chan.send(file);
+        }
+        crossed
     }
 
+    /**
+     * Play the Music at a time of the audio clock.
+     *
+     * The buffers are filled immediately, so the Music is ready to start at
+     * the given time. If the time is already passed, the Music starts
+     * immediately.
+     * Without the `AL_SOFT_source_start_delay` extension, the start is
+     * triggered by `ears::update`.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to play the Music at
+     *
+     * # Example
+     * ```Rust
+     * use ears::clock;
+     *
+     * let mut msc = Music::new("path/to/my/Music.flac").unwrap();
+     * msc.play_at(clock::now() + Duration::seconds(1));
+     * ```
+     */
+    pub fn play_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        self.stop();
+        self.process_music(Some(time));
+    }
+
+    /**
+     * Stop the Music at a time of the audio clock.
+     *
+     * The stop is triggered by `ears::update`.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to stop the Music at
+     */
+    pub fn stop_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        clock::schedule_stop(self.al_source, time);
+    }
 }
 
 impl AudioTags for Music {
@@ -223,6 +358,18 @@ impl AudioTags for Music {
     }
 }
 
+impl AudioMetadata for Music {
+    /**
+     * Get the extended metadata of a Music.
+     *
+     * # Return
+     * A copy of the metadata read from the file
+     */
+    fn get_metadata(&self) -> Metadata {
+        self.metadata.clone()
+    }
+}
+
 impl AudioController for Music {
     /**
      * Play or resume the Music.
@@ -234,12 +381,9 @@ impl AudioController for Music {
             Paused   => { al::alSourcePlay(self.al_source); return; },
             _       => {
                 if self.is_playing() {
-                    al::alSourceStop(self.al_source);
-                    // wait a bit for openal terminate
-                    sleep(Duration::milliseconds(50i64));
+                    self.stop();
                 }
-                self.file.as_mut().unwrap().seek(0, SeekSet);
-                self.process_music();
+                self.process_music(None);
             }
         }
     }
@@ -248,19 +392,23 @@ impl AudioController for Music {
      * Pause the Music.
      */
     fn pause(&mut self) -> () {
-        check_openal_context!(());
-
-        al::alSourcePause(self.al_source)
+        source_control::pause(self.al_source)
     }
 
     /**
      * Stop the Music.
+     *
+     * The scheduled playbacks of the Music are cancelled. The markers
+     * crossed before the stop are still returned by `poll_markers`.
      */
     fn stop(&mut self) -> () {
         check_openal_context!(());
 
+        clock::cancel(self.al_source);
+        self.stop_flag.store(true, SeqCst);
         al::alSourceStop(self.al_source);
-        sleep(Duration::milliseconds(50i64));
+        // wait for the streaming task to terminate
+        self.streamer = self.take_streamer();
     }
 
     /**
@@ -283,17 +431,7 @@ impl AudioController for Music {
      * The state of the music as a variant of the enum State
      */
     fn get_state(&self) -> State {
-        check_openal_context!(Initial);
-
-        let state  = al::alGetState(self.al_source);
-
-        match state {
-            ffi::AL_INITIAL => Initial,
-            ffi::AL_PLAYING => Playing,
-            ffi::AL_PAUSED  => Paused,
-            ffi::AL_STOPPED => Stopped,
-            _               => unreachable!()
-        }
+        source_control::get_state(self.al_source)
     }
 
     /**
@@ -307,9 +445,7 @@ impl AudioController for Music {
      * * `volume` - The volume of the Music, should be between 0. and 1.
      */
     fn set_volume(&mut self, volume: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+        source_control::set_volume(self.al_source, &self.bus, volume)
     }
 
     /**
@@ -319,11 +455,7 @@ impl AudioController for Music {
      * The volume of the Music between 0. and 1.
      */
     fn get_volume(&self) -> f32 {
-        check_openal_context!(0.);
-
-        let mut volume : f32 = 0.;
-        al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
-        volume
+        source_control::get_volume(self.al_source, &self.bus)
     }
 
     /**
@@ -337,9 +469,7 @@ impl AudioController for Music {
      * between 0. and 1.
      */
     fn set_min_volume(&mut self, min_volume: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_MIN_GAIN, min_volume);
+        source_control::set_float(self.al_source, ffi::AL_MIN_GAIN, min_volume)
     }
 
     /**
@@ -349,11 +479,7 @@ impl AudioController for Music {
      * The minimal volume of the Music between 0. and 1.
      */
     fn get_min_volume(&self) -> f32 {
-        check_openal_context!(0.);
-
-        let mut volume : f32 = 0.;
-        al::alGetSourcef(self.al_source, ffi::AL_MIN_GAIN, &mut volume);
-        volume
+        source_control::get_float(self.al_source, ffi::AL_MIN_GAIN, 0.)
     }
 
     /**
@@ -367,9 +493,7 @@ impl AudioController for Music {
      * between 0. and 1.
      */
     fn set_max_volume(&mut self, max_volume: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume);
+        source_control::set_float(self.al_source, ffi::AL_MAX_GAIN, max_volume)
     }
 
     /**
@@ -379,11 +503,7 @@ impl AudioController for Music {
      * The maximal volume of the Music between 0. and 1.
      */
     fn get_max_volume(&self) -> f32 {
-        check_openal_context!(0.);
-
-        let mut volume : f32 = 0.;
-        al::alGetSourcef(self.al_source, ffi::AL_MAX_GAIN, &mut volume);
-        volume
+        source_control::get_float(self.al_source, ffi::AL_MAX_GAIN, 0.)
     }
 
     /**
@@ -395,16 +515,7 @@ impl AudioController for Music {
      * `looping` - The new looping state.
      */
     fn set_looping(&mut self, looping: bool) -> () {
-        check_openal_context!(());
-
-        match looping {
-            true    => al::alSourcei(self.al_source,
-                                     ffi::AL_LOOPING,
-                                     ffi::ALC_TRUE as i32),
-            false   => al::alSourcei(self.al_source,
-                                     ffi::AL_LOOPING,
-                                     ffi::ALC_FALSE as i32)
-        };
+        source_control::set_bool(self.al_source, ffi::AL_LOOPING, looping)
     }
 
     /**
@@ -414,15 +525,7 @@ impl AudioController for Music {
      * True if the Music is looping, false otherwise.
      */
     fn is_looping(&self) -> bool {
-        check_openal_context!(false);
-
-        let mut boolean = 0;
-        al::alGetSourcei(self.al_source, ffi::AL_LOOPING, &mut boolean);
-        match boolean as i8 {
-            ffi::ALC_TRUE  => true,
-            ffi::ALC_FALSE => false,
-            _              => unreachable!()
-        }
+        source_control::get_bool(self.al_source, ffi::AL_LOOPING)
     }
 
     /**
@@ -436,9 +539,7 @@ impl AudioController for Music {
      * * `new_pitch` - The new pitch of the Music in the range [0.5 - 2.0]
      */
     fn set_pitch(&mut self, pitch: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+        source_control::set_pitch(self.al_source, &self.bus, pitch)
     }
 
     /**
@@ -448,11 +549,7 @@ impl AudioController for Music {
      * The pitch of the Music in the range [0.5 - 2.0]
      */
     fn get_pitch(&self) -> f32 {
-        check_openal_context!(0.);
-
-        let mut pitch = 0.;
-        al::alGetSourcef(self.al_source, ffi::AL_PITCH, &mut pitch);
-        pitch
+        source_control::get_pitch(self.al_source, &self.bus)
     }
 
     /**
@@ -465,16 +562,7 @@ impl AudioController for Music {
      * Music position absolute.
      */
     fn set_relative(&mut self, relative: bool) -> () {
-        check_openal_context!(());
-
-        match relative {
-            true    => al::alSourcei(self.al_source,
-                                     ffi::AL_SOURCE_RELATIVE,
-                                     ffi::ALC_TRUE as i32),
-            false   => al::alSourcei(self.al_source,
-                                     ffi::AL_SOURCE_RELATIVE,
-                                     ffi::ALC_FALSE as i32)
-        };
+        source_control::set_bool(self.al_source, ffi::AL_SOURCE_RELATIVE, relative)
     }
 
     /**
@@ -484,15 +572,7 @@ impl AudioController for Music {
      * True if the Music is relative to the listener false otherwise
      */
     fn is_relative(&mut self) -> bool {
-        check_openal_context!(false);
-
-        let mut boolean = 0;
-        al::alGetSourcei(self.al_source, ffi::AL_SOURCE_RELATIVE, &mut boolean);
-        match boolean as i8 {
-            ffi::ALC_TRUE  => true,
-            ffi::ALC_FALSE => false,
-            _              => unreachable!()
-        }
+        source_control::get_bool(self.al_source, ffi::AL_SOURCE_RELATIVE)
     }
 
     /**
@@ -511,9 +591,12 @@ impl AudioController for Music {
      * of the listener [x, y, z].
      */
     fn set_position(&mut self, position: [f32, ..3]) -> () {
-        check_openal_context!(());
+        source_control::set_vector(self.al_source, ffi::AL_POSITION, position);
 
-        al::alSourcefv(self.al_source, ffi::AL_POSITION, &position[0]);
+        if self.channels > 1 && !self.unpositioned_warned && !self.is_relative() {
+            channels::warn_unpositioned(self.path.as_slice(), self.channels);
+            self.unpositioned_warned = true;
+        }
     }
 
     /**
@@ -524,11 +607,7 @@ impl AudioController for Music {
      * listener [x, y, z].
      */
     fn get_position(&self) -> [f32, ..3] {
-        check_openal_context!([0., ..3]);
-
-        let mut position : [f32, ..3] = [0., ..3];
-        al::alGetSourcefv(self.al_source, ffi::AL_POSITION, &mut position[0]);
-        position
+        source_control::get_vector(self.al_source, ffi::AL_POSITION)
     }
 
     /**
@@ -542,9 +621,7 @@ impl AudioController for Music {
      * `direction` - The new direction of the Music.
      */
     fn set_direction(&mut self, direction: [f32, ..3]) -> () {
-        check_openal_context!(());
-
-        al::alSourcefv(self.al_source, ffi::AL_DIRECTION, &direction[0]);
+        source_control::set_vector(self.al_source, ffi::AL_DIRECTION, direction)
     }
 
     /**
@@ -554,11 +631,7 @@ impl AudioController for Music {
      * The current direction of the Music.
      */
     fn get_direction(&self)  -> [f32, ..3] {
-        check_openal_context!([0., ..3]);
-
-        let mut direction : [f32, ..3] = [0., ..3];
-        al::alGetSourcefv(self.al_source, ffi::AL_DIRECTION, &mut direction[0]);
-        direction
+        source_control::get_vector(self.al_source, ffi::AL_DIRECTION)
     }
 
     /**
@@ -574,9 +647,7 @@ impl AudioController for Music {
      * `max_distance` - The new maximum distance in the range [0., +inf]
      */
     fn set_max_distance(&mut self, max_distance: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_MAX_DISTANCE, max_distance);
+        source_control::set_float(self.al_source, ffi::AL_MAX_DISTANCE, max_distance)
     }
 
     /**
@@ -586,11 +657,7 @@ impl AudioController for Music {
      * The maximum distance of the Music in the range [0., +inf]
      */
     fn get_max_distance(&self) -> f32 {
-        check_openal_context!(0.);
-
-        let mut max_distance = 0.;
-        al::alGetSourcef(self.al_source, ffi::AL_MAX_DISTANCE, &mut max_distance);
-        max_distance
+        source_control::get_float(self.al_source, ffi::AL_MAX_DISTANCE, 0.)
     }
 
     /**
@@ -606,9 +673,7 @@ impl AudioController for Music {
      * * `ref_distance` - The new reference distance of the Music.
      */
     fn set_reference_distance(&mut self, ref_distance: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance);
+        source_control::set_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance)
     }
 
     /**
@@ -618,13 +683,7 @@ impl AudioController for Music {
      * The current reference distance of the Music.
      */
     fn get_reference_distance(&self) -> f32 {
-        check_openal_context!(1.);
-
-        let mut ref_distance = 0.;
-        al::alGetSourcef(self.al_source,
-                         ffi::AL_REFERENCE_DISTANCE,
-                         &mut ref_distance);
-        ref_distance
+        source_control::get_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, 1.)
     }
 
     /**
@@ -639,9 +698,7 @@ impl AudioController for Music {
      * `attenuation` - The new attenuation for the Music in the range [0., 1.].
      */
     fn set_attenuation(&mut self, attenuation: f32) -> () {
-        check_openal_context!(());
-
-        al::alSourcef(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation);
+        source_control::set_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation)
     }
 
     /**
@@ -651,13 +708,7 @@ impl AudioController for Music {
      * The current attenuation for the Music in the range [0., 1.].
      */
     fn get_attenuation(&self) -> f32 {
-        check_openal_context!(1.);
-
-        let mut attenuation = 0.;
-        al::alGetSourcef(self.al_source,
-                         ffi::AL_ROLLOFF_FACTOR,
-                         &mut attenuation);
-        attenuation
+        source_control::get_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, 1.)
     }
 }
 
@@ -665,14 +716,118 @@ impl AudioController for Music {
 impl Drop for Music {
     /// Destroy all the resources of the Music.
     fn drop(&mut self) -> () {
-        unsafe {
-            al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
-            ffi::alDeleteBuffers(2, &mut self.al_buffers[0]);
-            ffi::alDeleteSources(1, &mut self.al_source);
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
         }
+        clock::cancel(self.al_source);
+        self.stop_flag.store(true, SeqCst);
+        al::alSourceStop(self.al_source);
+        self.take_streamer();
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        al::alDeleteBuffers(2, &mut self.al_buffers[0]);
+        al::alDeleteSources(1, &mut self.al_source);
     }
 }
 
+/// An opened Music file, waiting for its OpenAL source.
+#[doc(hidden)]
+pub struct MusicFile {
+    provider: Box<FileProvider>,
+    infos: SndInfo,
+    sound_tags: Tags,
+    metadata: Metadata,
+    markers: Vec<Marker>,
+    path: String,
+    options: LoadOptions
+}
+
+/**
+ * Open the file of a Music and negotiate its format.
+ *
+ * Doesn't need the OpenAL context, the file can be opened in another task.
+ */
+#[doc(hidden)]
+pub fn open_file(path: &str, options: &LoadOptions) -> Result<MusicFile, String> {
+    // Retrieve File and Music datas
+    let file = try!(decoder::open(path));
+    let infos = file.get_sndinfo();
+    let sound_tags = file.get_tags();
+    let metadata = file.get_metadata();
+
+    // Retrieve format informations
+    let provider = match FileProvider::new(file, options) {
+        Some(provider) => box provider,
+        None           => return Err(format!("Cannot stream {}.", path))
+    };
+    let markers = load_markers(path, &metadata);
+
+    Ok(MusicFile {
+        provider: provider,
+        infos: infos,
+        sound_tags: sound_tags,
+        metadata: metadata,
+        markers: markers,
+        path: path.to_string(),
+        options: options.clone()
+    })
+}
+
+/**
+ * Create a Music playing an opened file.
+ *
+ * Must be called in the task of the OpenAL context.
+ */
+#[doc(hidden)]
+pub fn from_file(file: MusicFile) -> Option<Music> {
+    let MusicFile { provider, infos, sound_tags, metadata, markers, path, options } = file;
+    let channels = provider.get_channels();
+
+    // create the source and the buffers
+    let mut source_id = 0;
+    let mut buffer_ids = [0, ..2];
+    // create the source
+    al::alGenSources(1, &mut source_id);
+    // create the buffers
+    al::alGenBuffers(2, &mut buffer_ids[0]);
+
+    // Check if there is OpenAL internal error
+    match al::openal_has_error() {
+        Some(err) => { println!("{}", err); return None; },
+        None => {}
+    };
+
+    let mut streamer = Streamer::new(source_id,
+                                     buffer_ids.to_vec(),
+                                     provider,
+                                     50000);
+    let markers = Arc::new(Mutex::new(markers));
+    let (sender, receiver) = channel();
+    streamer.set_markers(markers.clone());
+    streamer.set_events(sender);
+    match hot_reload::watch_music(path.as_slice(), &options) {
+        Some(reload) => streamer.set_reload(reload),
+        None         => {}
+    }
+
+    Some( Music {
+        al_source: source_id,
+        al_buffers: buffer_ids,
+        stop_flag: streamer.get_stop_flag(),
+        streamer: Some(streamer),
+        streamer_receiver: None,
+        file_infos: infos,
+        sound_tags: sound_tags,
+        metadata: metadata,
+        markers: markers,
+        events: receiver,
+        bus: None,
+        path: path,
+        channels: channels,
+        unpositioned_warned: false
+    })
+}
+
 #[cfg(test)]
 mod test {
     #![allow(non_snake_case_functions)]
@@ -702,7 +857,6 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn music_play_OK() -> () {
         let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
 
@@ -712,7 +866,6 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn music_pause_OK() -> () {
         let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
 
@@ -734,7 +887,6 @@ mod test {
 
 
     #[test]
-    #[ignore]
     fn music_is_playing_TRUE() -> () {
         let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
 
@@ -744,7 +896,6 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn music_is_playing_FALSE() -> () {
         let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
 
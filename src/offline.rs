@@ -0,0 +1,309 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Render the sounds faster than real time, without a sound card.
+
+use std::cmp;
+use std::time::Duration;
+use libc::c_void;
+
+use internal::{OpenAlData, RenderSamplesSOFT};
+use openal::ffi;
+use sndfile::{SndInfo, SndFile, Write};
+use stream;
+use update;
+
+/// The count of frames rendered between two updates.
+static RENDER_CHUNK: uint = 1024;
+
+/**
+ * Render the sounds into samples or a file.
+ *
+ * The OfflineRenderer replaces the sound card by a loopback device, using
+ * the `ALC_SOFT_loopback` extension. It must be created before any other use
+ * of ears in the task, all the Sounds and Musics of the task are then
+ * rendered by it.
+ *
+ * Between two chunks of 1024 frames, the renderer refills the Musics and
+ * runs `ears::update` as if the time of the chunk had passed. The Musics
+ * stream in the task of the renderer instead of their own tasks.
+ *
+ * # Example
+ * ```Rust
+ * use ears::{OfflineRenderer, Sound, AudioController, FormatWav, FormatPcm16};
+ *
+ * let mut renderer = OfflineRenderer::new(48000, 2).unwrap();
+ * let mut snd = Sound::new("path/to/the/sound.ogg").unwrap();
+ * snd.set_position([2., 0., 0.]);
+ * snd.play();
+ *
+ * // Render 10 seconds of the scene
+ * renderer.render_to_file("scene.wav", 480000, (FormatWav | FormatPcm16) as i32);
+ * ```
+ */
+pub struct OfflineRenderer {
+    /// The loopback device
+    device: *mut ffi::ALCdevice,
+    /// alcRenderSamplesSOFT
+    render_samples: RenderSamplesSOFT,
+    /// The sample rate of the rendered samples
+    samplerate: i32,
+    /// The count of channels of the rendered samples
+    channels: uint,
+    /// The count of frames rendered
+    position: u64
+}
+
+impl OfflineRenderer {
+    /**
+     * Create the OfflineRenderer.
+     *
+     * # Arguments
+     * * `samplerate` - The sample rate of the rendered samples
+     * * `channels` - The count of channels of the rendered samples: 1, 2, 4,
+     * 6 (5.1), 7 (6.1) or 8 (7.1)
+     *
+     * # Return
+     * Some(OfflineRenderer) if the loopback device is created, None if
+     * ears is already used in the task, if the extension is missing or if
+     * the format is not supported.
+     */
+    pub fn new(samplerate: i32, channels: uint) -> Option<OfflineRenderer> {
+        let layout = match channels {
+            1 => ffi::ALC_MONO_SOFT,
+            2 => ffi::ALC_STEREO_SOFT,
+            4 => ffi::ALC_QUAD_SOFT,
+            6 => ffi::ALC_5POINT1_SOFT,
+            7 => ffi::ALC_6POINT1_SOFT,
+            8 => ffi::ALC_7POINT1_SOFT,
+            _ => {
+                println!("Error: the offline renderer can't render {} channels.", channels);
+                return None;
+            }
+        };
+        let attributes = [ffi::ALC_FREQUENCY, samplerate,
+                          ffi::ALC_FORMAT_CHANNELS_SOFT, layout,
+                          ffi::ALC_FORMAT_TYPE_SOFT, ffi::ALC_FLOAT_SOFT,
+                          0];
+        let (device, render_samples) = match OpenAlData::init_loopback(attributes.as_slice()) {
+            Ok(loopback) => loopback,
+            Err(err)     => { println!("{}", err); return None; }
+        };
+        stream::set_synchronous();
+
+        Some(OfflineRenderer {
+            device: device,
+            render_samples: render_samples,
+            samplerate: samplerate,
+            channels: channels,
+            position: 0
+        })
+    }
+
+    /// Get the sample rate of the rendered samples.
+    pub fn get_samplerate(&self) -> i32 {
+        self.samplerate
+    }
+
+    /// Get the count of channels of the rendered samples.
+    pub fn get_channels(&self) -> uint {
+        self.channels
+    }
+
+    /// Get the count of frames rendered.
+    pub fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    /**
+     * Get the count of frames of a duration.
+     *
+     * # Argument
+     * * `duration` - The duration to render
+     */
+    pub fn get_frames(&self, duration: Duration) -> u64 {
+        (duration.num_milliseconds() * self.samplerate as i64 / 1000) as u64
+    }
+
+    /**
+     * Render frames.
+     *
+     * # Argument
+     * * `frames` - The count of frames to render
+     *
+     * # Return
+     * The interleaved float samples.
+     */
+    pub fn render(&mut self, frames: uint) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(frames * self.channels);
+        let mut remaining = frames;
+        while remaining > 0 {
+            let chunk = cmp::min(remaining, RENDER_CHUNK);
+            samples.push_all(self.render_chunk(chunk).as_slice());
+            remaining -= chunk;
+        }
+        samples
+    }
+
+    /// Update ears and render up to RENDER_CHUNK frames.
+    fn render_chunk(&mut self, frames: uint) -> Vec<f32> {
+        let elapsed = frames as i64 * 1000000000 / self.samplerate as i64;
+        stream::pump();
+        update::process(Duration::nanoseconds(elapsed));
+
+        let mut samples = Vec::from_elem(frames * self.channels, 0f32);
+        (self.render_samples)(self.device,
+                              samples.as_mut_ptr() as *mut c_void,
+                              frames as i32);
+        self.position += frames as u64;
+        samples
+    }
+
+    /**
+     * Render frames into a file.
+     *
+     * # Arguments
+     * * `path` - The path of the file to create
+     * * `frames` - The count of frames to render
+     * * `format` - The major format and the subtype of the file, like
+     * `(FormatWav | FormatPcm16) as i32`
+     *
+     * # Return
+     * true if the file is written, false otherwise.
+     */
+    pub fn render_to_file(&mut self, path: &str, frames: u64, format: i32) -> bool {
+        let mut infos = box SndInfo {
+            frames: frames as i64,
+            samplerate: self.samplerate,
+            channels: self.channels as i32,
+            format: format,
+            sections: 0,
+            seekable: 0
+        };
+        if !SndFile::check_format(&mut *infos) {
+            println!("Error: invalid format for writing.");
+            return false;
+        }
+        let mut file = match SndFile::new_with_info(path, Write, infos) {
+            Ok(file) => file,
+            Err(err) => { println!("{}", err); return false; }
+        };
+
+        let mut remaining = frames;
+        while remaining > 0 {
+            let chunk = cmp::min(remaining, RENDER_CHUNK as u64) as uint;
+            let samples = self.render_chunk(chunk);
+            if file.writef_f32(samples.as_slice(), chunk as i64) != chunk as i64 {
+                println!("{}", file.string_error());
+                file.close();
+                return false;
+            }
+            remaining -= chunk as u64;
+        }
+        file.close();
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::TempDir;
+    use std::num::Float;
+    use offline::OfflineRenderer;
+    use sound::Sound;
+    use music::Music;
+    use audio_controller::AudioController;
+    use io::{SoundReader, FormatWav, FormatPcm16};
+
+    /// Get the sum of the absolute samples of a channel.
+    fn get_energy(samples: &[f32], channel: uint) -> f32 {
+        samples.iter().enumerate()
+               .filter(|&(i, _)| i % 2 == channel)
+               .fold(0., |sum, (_, s)| sum + s.abs())
+    }
+
+    #[test]
+    fn offline_render_OK() -> () {
+        let mut renderer = OfflineRenderer::new(44100, 2).expect("Cannot create the renderer");
+        let samples = renderer.render(4410);
+
+        assert_eq!(samples.len(), 8820);
+        assert_eq!(renderer.get_position(), 4410);
+        // Nothing is playing
+        assert!(samples.iter().all(|s| *s == 0.));
+    }
+
+    #[test]
+    fn offline_render_positioned_sound_OK() -> () {
+        let mut renderer = OfflineRenderer::new(44100, 2).expect("Cannot create the renderer");
+        let mut snd = Sound::new("res/shot.wav").unwrap();
+        snd.set_position([2., 0., 0.]);
+        snd.play();
+        let samples = renderer.render(4410);
+
+        // The sound is on the right of the listener
+        let left = get_energy(samples.as_slice(), 0);
+        let right = get_energy(samples.as_slice(), 1);
+        assert!(right > 0.);
+        assert!(right > left * 2.);
+    }
+
+    #[test]
+    fn offline_render_music_OK() -> () {
+        let mut renderer = OfflineRenderer::new(44100, 2).expect("Cannot create the renderer");
+        let mut music = Music::new("res/shot.wav").unwrap();
+        music.play();
+        let samples = renderer.render(44100);
+
+        // res/shot.wav lasts 3605 frames at 7042 Hz, about 0.51 second
+        let last = samples.iter().rposition(|s| *s != 0.).expect("Nothing rendered") / 2;
+        assert!(last > 44100 * 45 / 100);
+        assert!(last < 44100 * 60 / 100);
+
+        // The music is centered
+        let left = get_energy(samples.as_slice(), 0);
+        let right = get_energy(samples.as_slice(), 1);
+        assert!((left - right).abs() < left * 0.01);
+    }
+
+    #[test]
+    fn offline_render_to_file_OK() -> () {
+        let mut renderer = OfflineRenderer::new(44100, 2).expect("Cannot create the renderer");
+        let mut snd = Sound::new("res/shot.wav").unwrap();
+        snd.play();
+        let dir = TempDir::new("ears_offline").unwrap();
+        let path = dir.path().join("scene.wav");
+        let path = path.as_str().unwrap();
+        assert!(renderer.render_to_file(path, 4410, (FormatWav | FormatPcm16) as i32));
+        assert_eq!(renderer.get_position(), 4410);
+
+        let mut reader = SoundReader::open(path).unwrap();
+        let info = reader.get_info();
+        assert_eq!(info.frames, 4410);
+        assert_eq!(info.samplerate, 44100);
+        assert_eq!(info.channels, 2);
+        let samples: Vec<i16> = reader.read_all().unwrap();
+        assert_eq!(samples.len(), 8820);
+        assert!(samples.iter().any(|s| *s != 0));
+    }
+}
@@ -0,0 +1,229 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Fire and forget playback of SoundData.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::mem;
+
+use internal::OpenAlData;
+use sound_data::SoundData;
+use sound_data;
+use openal::{ffi, al};
+
+/// The maximum count of OpenAL sources owned by the one-shot pool.
+const MAX_ONESHOT_SOURCES: uint = 32;
+
+local_data_key!(oneshot_pool: RefCell<SourcePool>)
+
+/// A pooled source currently playing a SoundData.
+struct PlayingSource {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The SoundData kept alive until the end of the playback
+    sound_data: Rc<RefCell<SoundData>>
+}
+
+/// The sources used by the one-shot sounds.
+struct SourcePool {
+    /// The sources ready to be used
+    free: Vec<u32>,
+    /// The sources currently playing, oldest first
+    playing: Vec<PlayingSource>
+}
+
+impl SourcePool {
+    fn new() -> SourcePool {
+        SourcePool {
+            free: Vec::new(),
+            playing: Vec::new()
+        }
+    }
+
+    /// Give back to the pool the sources which have finished to play.
+    fn recycle(&mut self) -> () {
+        let playing = mem::replace(&mut self.playing, Vec::new());
+        let (done, playing) = playing.partition(|p| {
+            al::alGetState(p.al_source) != ffi::AL_PLAYING
+        });
+
+        for p in done.iter() {
+            al::alSourcei(p.al_source, ffi::AL_BUFFER, 0);
+            sound_data::remove_source(&mut *p.sound_data.borrow_mut(), p.al_source);
+            self.free.push(p.al_source);
+        }
+        self.playing = playing;
+    }
+
+    /**
+     * Get a source from the pool.
+     *
+     * Create a new source if the pool is not full, otherwise steal the
+     * oldest playing source.
+     */
+    fn acquire(&mut self) -> Option<u32> {
+        match self.free.pop() {
+            Some(source) => return Some(source),
+            None         => {}
+        }
+
+        if self.playing.len() < MAX_ONESHOT_SOURCES {
+            let mut source_id = 0;
+            al::alGenSources(1, &mut source_id);
+            match al::openal_has_error() {
+                Some(err) => { println!("{}", err); None },
+                None      => Some(source_id)
+            }
+        } else {
+            match self.playing.remove(0) {
+                Some(oldest) => {
+                    al::alSourceStop(oldest.al_source);
+                    al::alSourcei(oldest.al_source, ffi::AL_BUFFER, 0);
+                    sound_data::remove_source(&mut *oldest.sound_data.borrow_mut(),
+                                              oldest.al_source);
+                    Some(oldest.al_source)
+                },
+                None         => None
+            }
+        }
+    }
+}
+
+impl Drop for SourcePool {
+    /// Destroy all the sources owned by the pool.
+    fn drop(&mut self) -> () {
+        for p in self.playing.iter() {
+            sound_data::remove_source(&mut *p.sound_data.borrow_mut(), p.al_source);
+            self.free.push(p.al_source);
+        }
+        for source in self.free.iter_mut() {
+            al::alSourceStop(*source);
+            al::alDeleteSources(1, source);
+        }
+    }
+}
+
+fn with_pool<T>(f: |&mut SourcePool| -> T) -> T {
+    if oneshot_pool.get().is_none() {
+        oneshot_pool.replace(Some(RefCell::new(SourcePool::new())));
+    }
+    let pool = oneshot_pool.get().unwrap();
+    let mut pool = pool.borrow_mut();
+    f(&mut *pool)
+}
+
+/**
+ * Play a SoundData once without keeping a handle on it.
+ *
+ * A source is borrowed from an internal pool, play the SoundData at the given
+ * position, then is given back to the pool when the playback is over.
+ * If all the sources of the pool are busy, the oldest one is stolen.
+ *
+ * # Arguments
+ * * `sound_data` - The SoundData to play
+ * * `position` - The position of the sound in three dimensional space [x, y, z]
+ * * `volume` - The volume of the sound, should be between 0. and 1.
+ * * `pitch` - The pitch of the sound in the range [0.5 - 2.0]
+ *
+ * # Return
+ * true if the sound is playing, false otherwise.
+ *
+ * # Example
+ * ```Rust
+ * let snd_data = Rc::new(RefCell::new(SoundData::new("path/to/shot.wav")
+ *                                     .unwrap()));
+ * ears::play_oneshot(&snd_data, [0., 0., 0.], 1., 1.);
+ * ```
+ */
+pub fn play_oneshot(sound_data: &Rc<RefCell<SoundData>>,
+                    position: [f32, ..3],
+                    volume: f32,
+                    pitch: f32) -> bool {
+    check_openal_context!(false);
+
+    with_pool(|pool| {
+        pool.recycle();
+
+        let source = match pool.acquire() {
+            Some(source) => source,
+            None         => {
+                println!("Internal error : no source available for a one-shot sound.");
+                return false;
+            }
+        };
+
+        al::alSourcei(source,
+                      ffi::AL_BUFFER,
+                      sound_data::get_buffer(&*sound_data.borrow()) as i32);
+        al::alSourcei(source, ffi::AL_LOOPING, ffi::ALC_FALSE as i32);
+        al::alSourcei(source, ffi::AL_SOURCE_RELATIVE, ffi::ALC_FALSE as i32);
+        al::alSourcefv(source, ffi::AL_POSITION, &position[0]);
+        al::alSourcef(source, ffi::AL_GAIN, volume);
+        al::alSourcef(source, ffi::AL_PITCH, pitch);
+        al::alSourcePlay(source);
+
+        match al::openal_has_error() {
+            Some(err) => {
+                println!("{}", err);
+                al::alSourcei(source, ffi::AL_BUFFER, 0);
+                pool.free.push(source);
+                false
+            },
+            None      => {
+                // Bound to the new buffer if the samples are reloaded
+                sound_data::add_source(&mut *sound_data.borrow_mut(), source);
+                pool.playing.push(PlayingSource {
+                    al_source: source,
+                    sound_data: sound_data.clone()
+                });
+                true
+            }
+        }
+    })
+}
+
+/// Give back to the pool the one-shot sources which have finished to play.
+#[doc(hidden)]
+pub fn recycle() -> () {
+    if oneshot_pool.get().is_some() {
+        with_pool(|pool| pool.recycle());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    use sound_data::SoundData;
+    use oneshot::play_oneshot;
+
+    #[test]
+    fn oneshot_play_OK() -> () {
+        let snd_data = Rc::new(RefCell::new(SoundData::new("res/shot.wav")
+                                            .expect("Cannot create SoundData")));
+
+        assert_eq!(play_oneshot(&snd_data, [0., 0., 0.], 1., 1.), true);
+    }
+}
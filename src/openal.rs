@@ -45,6 +45,7 @@ pub mod ffi {
     pub const AL_POSITION:            i32         = 0x1004;
     pub const AL_ORIENTATION:         i32         = 0x100F;
     pub const AL_DIRECTION:           i32         = 0x1005;
+    pub const AL_VELOCITY:            i32         = 0x1006;
     pub const AL_LOOPING:             i32         = 0x1007;
     pub const AL_MIN_GAIN:            i32         = 0x100D;
     pub const AL_MAX_GAIN:            i32         = 0x100E;
@@ -59,6 +60,27 @@ pub mod ffi {
     pub const AL_FORMAT_61CHN16:      i32         = 0x120E;
     pub const AL_FORMAT_71CHN16:      i32         = 0x1211;
     pub const AL_FORMAT_QUAD16:       i32         = 0x1205;
+    pub const AL_FORMAT_MONO8:        i32         = 0x1100;
+    pub const AL_FORMAT_STEREO8:      i32         = 0x1102;
+    pub const AL_FORMAT_QUAD8:        i32         = 0x1204;
+    pub const AL_FORMAT_51CHN8:       i32         = 0x120A;
+    pub const AL_FORMAT_61CHN8:       i32         = 0x120D;
+    pub const AL_FORMAT_71CHN8:       i32         = 0x1210;
+    pub const AL_FORMAT_MONO_FLOAT32: i32         = 0x10010;
+    pub const AL_FORMAT_STEREO_FLOAT32: i32       = 0x10011;
+    pub const AL_FORMAT_QUAD32:       i32         = 0x1206;
+    pub const AL_FORMAT_51CHN32:      i32         = 0x120C;
+    pub const AL_FORMAT_61CHN32:      i32         = 0x120F;
+    pub const AL_FORMAT_71CHN32:      i32         = 0x1212;
+    pub const AL_FORMAT_REAR8:        i32         = 0x1207;
+    pub const AL_FORMAT_REAR16:       i32         = 0x1208;
+    pub const AL_FORMAT_REAR32:       i32         = 0x1209;
+    pub const AL_FORMAT_BFORMAT2D_8:  i32         = 0x20021;
+    pub const AL_FORMAT_BFORMAT2D_16: i32         = 0x20022;
+    pub const AL_FORMAT_BFORMAT2D_FLOAT32: i32    = 0x20023;
+    pub const AL_FORMAT_BFORMAT3D_8:  i32         = 0x20031;
+    pub const AL_FORMAT_BFORMAT3D_16: i32         = 0x20032;
+    pub const AL_FORMAT_BFORMAT3D_FLOAT32: i32    = 0x20033;
 
     /// Source params
     pub const AL_BUFFER:              i32         = 0x1009;
@@ -80,9 +102,35 @@ pub mod ffi {
     pub const AL_PAUSED:              i32         = 0x1013;
     pub const AL_STOPPED:             i32         = 0x1014;
 
+    /// Buffer parameters
+    pub const AL_FREQUENCY:           i32         = 0x2001;
+    pub const AL_BITS:                i32         = 0x2002;
+    pub const AL_CHANNELS:            i32         = 0x2003;
+    pub const AL_SIZE:                i32         = 0x2004;
+
+    /// Source offsets
+    pub const AL_SEC_OFFSET:          i32         = 0x1024;
+    pub const AL_SAMPLE_OFFSET:       i32         = 0x1025;
+
     /// ALC
     pub const ALC_CAPTURE_SAMPLES :    i32         = 0x312;
 
+    /// ALC_SOFT_device_clock
+    pub const ALC_DEVICE_CLOCK_SOFT:  i32         = 0x1600;
+
+    /// ALC_SOFT_loopback
+    pub const ALC_FREQUENCY:          i32         = 0x1007;
+    pub const ALC_FORMAT_CHANNELS_SOFT: i32       = 0x1990;
+    pub const ALC_FORMAT_TYPE_SOFT:   i32         = 0x1991;
+    pub const ALC_SHORT_SOFT:         i32         = 0x1402;
+    pub const ALC_FLOAT_SOFT:         i32         = 0x1406;
+    pub const ALC_MONO_SOFT:          i32         = 0x1500;
+    pub const ALC_STEREO_SOFT:        i32         = 0x1501;
+    pub const ALC_QUAD_SOFT:          i32         = 0x1503;
+    pub const ALC_5POINT1_SOFT:       i32         = 0x1504;
+    pub const ALC_6POINT1_SOFT:       i32         = 0x1505;
+    pub const ALC_7POINT1_SOFT:       i32         = 0x1506;
+
 
     extern "C" {
         /// Context functions
@@ -90,6 +138,7 @@ pub mod ffi {
         pub fn alcMakeContextCurrent(context: *mut ALCcontext) -> ALCboolean;
         pub fn alcDestroyContext(context: *mut ALCcontext);
         pub fn alcGetCurrentContext() -> *mut ALCcontext;
+        pub fn alcGetContextsDevice(context: *mut ALCcontext) -> *mut ALCdevice;
 
         /// Device functions
         pub fn alcOpenDevice(devicename: *mut c_char) -> *mut ALCdevice;
@@ -99,7 +148,7 @@ pub mod ffi {
         pub fn alListenerf(param: i32, value: f32) -> ();
         pub fn alListener3f(param: i32, value1: f32, value2: f32, value3: f32) -> ();
         pub fn alGetListenerf(param: i32, value: *mut f32) -> ();
-        pub fn alGetListener3f(param: f32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> ();
+        pub fn alGetListener3f(param: i32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> ();
         pub fn alListenerfv(param: i32, values: *const f32) -> ();
         pub fn alGetListenerfv(param: i32, values: *mut f32) -> ();
 
@@ -111,6 +160,7 @@ pub mod ffi {
         pub fn alSourcePlay(source: u32) -> ();
         pub fn alSourcePause(source: u32) -> ();
         pub fn alSourceStop(source: u32) -> ();
+        pub fn alSourceRewind(source: u32) -> ();
         pub fn alGetSourcei(source: u32, param: i32, value: *mut i32) -> ();
         pub fn alGetSourcef(source: u32, param: i32, value: *mut f32) -> ();
         pub fn alSourcefv(source: u32, param: i32, value: *const f32) -> ();
@@ -128,11 +178,15 @@ pub mod ffi {
 
         /// extension check
         pub fn alcIsExtensionPresent(device: *mut ALCdevice, extension: *const c_char) -> ALCboolean;
+        pub fn alIsExtensionPresent(extension: *const c_char) -> ALCboolean;
+        pub fn alcGetProcAddress(device: *mut ALCdevice, funcname: *const c_char) -> *mut c_void;
+        pub fn alGetProcAddress(funcname: *const c_char) -> *mut c_void;
 
         /// Buffers functions
         pub fn alGenBuffers(n: i32, buffers: *mut u32) -> ();
         pub fn alDeleteBuffers(n: i32, buffers: *mut u32);
         pub fn alBufferData(buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> ();
+        pub fn alGetBufferi(buffer: u32, param: i32, value: *mut i32) -> ();
 
         /// Error
         pub fn alGetError() -> i32;
@@ -148,95 +202,153 @@ pub mod al {
 
     use super::ffi;
     use libc::c_void;
+    use backend;
+    use sample::{SampleType, Int8, Int16, Float32};
+    use channels::{ChannelLayout, LayoutMono, LayoutStereo, LayoutRear, LayoutQuad,
+                   Layout51, Layout61, Layout71, LayoutBFormat2D, LayoutBFormat3D};
+
+    pub fn alGenBuffers(n: i32, buffers: *mut u32) -> () {
+        backend::get().gen_buffers(n, buffers);
+    }
+
+    pub fn alDeleteBuffers(n: i32, buffers: *mut u32) -> () {
+        backend::get().delete_buffers(n, buffers);
+    }
 
     pub fn alBufferData(buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> () {
-        unsafe { ffi::alBufferData(buffer, format, data, size, freq); }
+        backend::get().buffer_data(buffer, format, data, size, freq);
+    }
+
+    pub fn alGetBufferi(buffer: u32, param: i32, value: *mut i32) -> () {
+        backend::get().get_bufferi(buffer, param, value);
     }
 
     pub fn alSourceQueueBuffers(source: u32, nb: i32, buffers: *const u32) -> () {
-        unsafe { ffi::alSourceQueueBuffers(source, nb, buffers); }
+        backend::get().source_queue_buffers(source, nb, buffers);
     }
 
     pub fn alSourcePlay(source: u32) -> () {
-        unsafe { ffi::alSourcePlay(source); }
+        backend::get().source_play(source);
+    }
+
+    pub fn alSourcePlayAtTimeSOFT(source: u32, time: i64) -> () {
+        backend::get().source_play_at_time(source, time);
     }
 
     pub fn alGetSourcei(source: u32, param: i32, value: *mut i32) -> () {
-        unsafe { ffi::alGetSourcei(source, param, value); }
+        backend::get().get_sourcei(source, param, value);
     }
 
     pub fn alGetSourcef(source: u32, param: i32, value: *mut f32) -> () {
-        unsafe { ffi::alGetSourcef(source, param, value); }
+        backend::get().get_sourcef(source, param, value);
     }
 
     pub fn alGetState(source: u32) -> i32 {
         let mut i = 0;
-        unsafe { ffi::alGetSourcei(source, ffi::AL_SOURCE_STATE, &mut i); }
+        backend::get().get_sourcei(source, ffi::AL_SOURCE_STATE, &mut i);
         i
     }
 
     pub fn alSourcei(source: u32, param: i32, value: i32) -> () {
-        unsafe { ffi::alSourcei(source, param, value); }
+        backend::get().sourcei(source, param, value);
     }
 
     pub fn alSourcef(source: u32, param: i32, value: f32) -> () {
-        unsafe { ffi::alSourcef(source, param, value); }
+        backend::get().sourcef(source, param, value);
     }
 
     pub fn alSourcePause(source: u32) -> () {
-        unsafe { ffi::alSourcePause(source); }
+        backend::get().source_pause(source);
     }
 
     pub fn alSourceStop(source: u32) -> () {
-        unsafe { ffi::alSourceStop(source); }
+        backend::get().source_stop(source);
+    }
+
+    pub fn alSourceRewind(source: u32) -> () {
+        backend::get().source_rewind(source);
     }
 
     pub fn alSourceUnqueueBuffers(source: u32, nb: i32, buffers: *mut u32) -> () {
-        unsafe { ffi::alSourceUnqueueBuffers(source, nb, buffers); }
+        backend::get().source_unqueue_buffers(source, nb, buffers);
     }
 
     pub fn alGenSources(n: i32, sources: *mut u32) -> () {
-        unsafe {ffi::alGenSources(n, sources); }
+        backend::get().gen_sources(n, sources);
     }
 
-    pub fn alSourcefv(source: u32, param: i32, value: *const f32) -> () {
-        unsafe { ffi::alSourcefv(source, param, value); }
+    pub fn alDeleteSources(n: i32, sources: *mut u32) -> () {
+        backend::get().delete_sources(n, sources);
     }
 
-    pub fn alGetSourcefv(source: u32, param: i32, value: *mut f32) -> () {
-        unsafe { ffi::alGetSourcefv(source, param, value); }
+    pub fn alSourcefv(source: u32, param: i32, value: *const f32) -> () {
+        backend::get().sourcefv(source, param, value);
     }
 
-    pub fn alGenBuffers(n: i32, buffers: *mut u32) -> () {
-        unsafe { ffi::alGenBuffers(n, buffers); }
+    pub fn alGetSourcefv(source: u32, param: i32, value: *mut f32) -> () {
+        backend::get().get_sourcefv(source, param, value);
     }
 
     pub fn alListenerf(param: i32, value: f32) -> () {
-        unsafe { ffi::alListenerf(param, value); }
+        backend::get().listenerf(param, value);
     }
 
     pub fn alListener3f(param: i32, value1: f32, value2: f32, value3: f32) -> () {
-        unsafe { ffi::alListener3f(param, value1, value2, value3); }
+        backend::get().listener3f(param, value1, value2, value3);
     }
 
     pub fn alGetListenerf(param: i32, value: *mut f32) -> () {
-        unsafe { ffi::alGetListenerf(param, value); }
+        backend::get().get_listenerf(param, value);
     }
 
-    pub fn alGetListener3f(param: f32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> () {
-        unsafe { ffi::alGetListener3f(param, value1, value2, value3); }
+    pub fn alGetListener3f(param: i32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> () {
+        backend::get().get_listener3f(param, value1, value2, value3);
     }
 
     pub fn alListenerfv(param: i32, values: *const f32) -> () {
-        unsafe { ffi::alListenerfv(param, values); }
+        backend::get().listenerfv(param, values);
     }
 
     pub fn alGetListenerfv(param: i32, values: *mut f32) -> () {
-        unsafe { ffi::alGetListenerfv(param, values); }
+        backend::get().get_listenerfv(param, values);
+    }
+
+    pub fn alcCaptureOpenDevice(samplerate: i32, format: i32, buffer_size: i32) -> *mut ffi::ALCdevice {
+        backend::get().capture_open(samplerate, format, buffer_size)
+    }
+
+    pub fn alcCaptureCloseDevice(device: *mut ffi::ALCdevice) -> () {
+        backend::get().capture_close(device);
+    }
+
+    pub fn alcCaptureStart(device: *mut ffi::ALCdevice) -> () {
+        backend::get().capture_start(device);
+    }
+
+    pub fn alcCaptureStop(device: *mut ffi::ALCdevice) -> () {
+        backend::get().capture_stop(device);
+    }
+
+    pub fn alcGetCaptureSamples(device: *mut ffi::ALCdevice) -> i32 {
+        backend::get().capture_available(device)
+    }
+
+    pub fn alcCaptureSamples(device: *mut ffi::ALCdevice, buffer: *mut c_void, samples: i32) -> () {
+        backend::get().capture_samples(device, buffer, samples);
+    }
+
+    pub fn alIsExtensionPresent(extension: &str) -> bool {
+        backend::get().is_extension_present(extension)
+    }
+
+    pub fn alcIsExtensionPresent(device: *mut ffi::ALCdevice, extension: &str) -> bool {
+        extension.with_c_str(|c_str| unsafe {
+            ffi::alcIsExtensionPresent(device, c_str)
+        }) == ffi::ALC_TRUE
     }
 
     pub fn openal_has_error() -> Option<String> {
-         match unsafe { ffi::alGetError() } {
+         match backend::get().get_error() {
             ffi::AL_NO_ERROR          => None,
             ffi::AL_INVALID_NAME      => Some("OpenAL error : Invalid name paramater passed to AL call.".to_string()),
             ffi::AL_INVALID_ENUM      => Some("OpenAL error : Invalid enum parameter passed to AL call.".to_string()),
@@ -248,14 +360,85 @@ pub mod al {
     }
 
     pub fn get_channels_format(channels : i32) -> Option<i32> {
-        match channels {
-            1 => Some(ffi::AL_FORMAT_MONO16),
-            2 => Some(ffi::AL_FORMAT_STEREO16),
-            4 => Some(ffi::AL_FORMAT_QUAD16),
-            5 => Some(ffi::AL_FORMAT_51CHN16),
-            6 => Some(ffi::AL_FORMAT_61CHN16),
-            7 => Some(ffi::AL_FORMAT_71CHN16),
-            _ => return None
+        get_format(channels, Int16)
+    }
+
+    /// Get the OpenAL format for a count of channels and a type of samples.
+    pub fn get_format(channels : i32, sample_type : SampleType) -> Option<i32> {
+        ChannelLayout::from_channels(channels as uint).map(|layout| {
+            get_layout_format(layout, sample_type)
+        })
+    }
+
+    /// Get the OpenAL format for a channel layout and a type of samples.
+    pub fn get_layout_format(layout : ChannelLayout, sample_type : SampleType) -> i32 {
+        match (sample_type, layout) {
+            (Int8, LayoutMono)         => ffi::AL_FORMAT_MONO8,
+            (Int8, LayoutStereo)       => ffi::AL_FORMAT_STEREO8,
+            (Int8, LayoutRear)         => ffi::AL_FORMAT_REAR8,
+            (Int8, LayoutQuad)         => ffi::AL_FORMAT_QUAD8,
+            (Int8, Layout51)           => ffi::AL_FORMAT_51CHN8,
+            (Int8, Layout61)           => ffi::AL_FORMAT_61CHN8,
+            (Int8, Layout71)           => ffi::AL_FORMAT_71CHN8,
+            (Int8, LayoutBFormat2D)    => ffi::AL_FORMAT_BFORMAT2D_8,
+            (Int8, LayoutBFormat3D)    => ffi::AL_FORMAT_BFORMAT3D_8,
+            (Int16, LayoutMono)        => ffi::AL_FORMAT_MONO16,
+            (Int16, LayoutStereo)      => ffi::AL_FORMAT_STEREO16,
+            (Int16, LayoutRear)        => ffi::AL_FORMAT_REAR16,
+            (Int16, LayoutQuad)        => ffi::AL_FORMAT_QUAD16,
+            (Int16, Layout51)          => ffi::AL_FORMAT_51CHN16,
+            (Int16, Layout61)          => ffi::AL_FORMAT_61CHN16,
+            (Int16, Layout71)          => ffi::AL_FORMAT_71CHN16,
+            (Int16, LayoutBFormat2D)   => ffi::AL_FORMAT_BFORMAT2D_16,
+            (Int16, LayoutBFormat3D)   => ffi::AL_FORMAT_BFORMAT3D_16,
+            (Float32, LayoutMono)      => ffi::AL_FORMAT_MONO_FLOAT32,
+            (Float32, LayoutStereo)    => ffi::AL_FORMAT_STEREO_FLOAT32,
+            (Float32, LayoutRear)      => ffi::AL_FORMAT_REAR32,
+            (Float32, LayoutQuad)      => ffi::AL_FORMAT_QUAD32,
+            (Float32, Layout51)        => ffi::AL_FORMAT_51CHN32,
+            (Float32, Layout61)        => ffi::AL_FORMAT_61CHN32,
+            (Float32, Layout71)        => ffi::AL_FORMAT_71CHN32,
+            (Float32, LayoutBFormat2D) => ffi::AL_FORMAT_BFORMAT2D_FLOAT32,
+            (Float32, LayoutBFormat3D) => ffi::AL_FORMAT_BFORMAT3D_FLOAT32
         }
     }
+
+    /**
+     * Choose the OpenAL format of a sound.
+     *
+     * The float formats are only used if the AL_EXT_FLOAT32 extension is
+     * present, otherwise the samples fall back to 16 bit. The multichannel
+     * layouts need the AL_EXT_MCFORMATS extension and the B-format layouts
+     * the AL_EXT_BFORMAT extension.
+     *
+     * # Arguments
+     * * `layout` - The channel layout of the sound
+     * * `sample_type` - The wanted type of samples, None for the most precise
+     * type supported
+     *
+     * # Return
+     * The OpenAL format and the type of samples to upload, None if the
+     * layout is not supported.
+     */
+    pub fn negotiate_format(layout : ChannelLayout,
+                            sample_type : Option<SampleType>)
+                            -> Option<(i32, SampleType)> {
+        let supported = match layout {
+            LayoutMono | LayoutStereo          => true,
+            LayoutBFormat2D | LayoutBFormat3D  => alIsExtensionPresent("AL_EXT_BFORMAT"),
+            _                                  => alIsExtensionPresent("AL_EXT_MCFORMATS")
+        };
+        if !supported {
+            return None;
+        }
+
+        let float32 = alIsExtensionPresent("AL_EXT_FLOAT32");
+        let wanted = match sample_type {
+            Some(Float32) if !float32 => Int16,
+            Some(sample_type)         => sample_type,
+            None if float32           => Float32,
+            None                      => Int16
+        };
+        Some((get_layout_format(layout, wanted), wanted))
+    }
 }
@@ -0,0 +1,1001 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Play several files back to back.
+
+use std::cell::Cell;
+use std::default::Default;
+use std::rand::{task_rng, Rng};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, SeqCst};
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use decoder::Decoder;
+use decoder;
+use states::{State, Playing, Paused};
+use audio_controller::AudioController;
+use sample::{SampleType, SampleBuffer, Int16};
+use stream::{Streamer, StreamProvider, FileProvider, StreamEvent, TrackStarted};
+use stream;
+use bus::Bus;
+use bus;
+use source_control;
+use audio_tags::{Tags, AudioTags};
+use audio_tags;
+use channels;
+
+/// The different ways to chain the tracks of a Playlist.
+#[deriving(Clone, PartialEq, Show)]
+pub enum PlaylistMode {
+    /// Stop at the end of the last track
+    NoRepeat,
+    /// Repeat the current track
+    RepeatOne,
+    /// Go back to the first track at the end of the last one
+    RepeatAll
+}
+
+/// The order of the tracks, shared with the streaming task.
+struct PlaylistQueue {
+    /// The paths of the tracks
+    paths: Vec<String>,
+    /// The play order, as indices in paths
+    order: Vec<uint>,
+    /// The position in order of the current track
+    position: uint,
+    /// The playlist is over
+    finished: bool,
+    mode: PlaylistMode,
+    shuffle: bool
+}
+
+impl PlaylistQueue {
+    /// The index of the current track.
+    fn current(&self) -> Option<uint> {
+        self.order.as_slice().get(self.position).map(|t| *t)
+    }
+
+    /// Generate a new play order, the current track stay the current one.
+    fn reorder(&mut self) -> () {
+        let current = self.current();
+        self.order = range(0, self.paths.len()).collect();
+        if self.shuffle {
+            task_rng().shuffle(self.order.as_mut_slice());
+        }
+        self.position = match current {
+            Some(track) => {
+                let idx = self.order.iter().position(|t| *t == track).unwrap();
+                if self.shuffle {
+                    self.order.as_mut_slice().swap(0, idx);
+                    0
+                } else {
+                    idx
+                }
+            },
+            None        => 0
+        };
+    }
+
+    /**
+     * Go to the next track.
+     *
+     * # Argument
+     * * `user` - true if the user asked for the next track, false at the end
+     * of a track.
+     *
+     * # Return
+     * true if there is a next track, false otherwise.
+     */
+    fn next(&mut self, user: bool) -> bool {
+        if self.order.is_empty() {
+            return false;
+        }
+        if self.mode == RepeatOne && !user {
+            return true;
+        }
+        if self.position + 1 < self.order.len() {
+            self.position += 1;
+            true
+        } else if self.mode == RepeatAll || (user && self.mode == RepeatOne) {
+            if self.shuffle {
+                task_rng().shuffle(self.order.as_mut_slice());
+            }
+            self.position = 0;
+            true
+        } else {
+            self.finished = true;
+            false
+        }
+    }
+
+    /// Go to the previous track.
+    fn previous(&mut self) -> bool {
+        if self.order.is_empty() {
+            false
+        } else if self.position > 0 {
+            self.position -= 1;
+            true
+        } else if self.mode == NoRepeat {
+            false
+        } else {
+            self.position = self.order.len() - 1;
+            true
+        }
+    }
+
+    /// Go to a track.
+    fn skip_to(&mut self, track: uint) -> bool {
+        match self.order.iter().position(|t| *t == track) {
+            Some(idx) => { self.position = idx; true },
+            None      => false
+        }
+    }
+}
+
+/// Provide the samples of the tracks of a Playlist.
+struct PlaylistProvider {
+    queue: Arc<Mutex<PlaylistQueue>>,
+    file: Option<FileProvider>,
+    track: uint
+}
+
+impl PlaylistProvider {
+    /// Open the current track, skip the tracks which cannot be opened.
+    fn open_current(&mut self) -> bool {
+        let mut queue = self.queue.lock();
+        let mut tries = queue.order.len();
+
+        while tries > 0 {
+            let track = match queue.current() {
+                Some(track) => track,
+                None        => break
+            };
+            let file = match decoder::open(queue.paths[track].as_slice()) {
+                Ok(file) => FileProvider::new(file, &Default::default()),
+                Err(err) => { println!("{}", err); None }
+            };
+            match file {
+                Some(file) => {
+                    self.file = Some(file);
+                    self.track = track;
+                    return true;
+                },
+                None       => {
+                    if !queue.next(true) {
+                        break;
+                    }
+                }
+            }
+            tries -= 1;
+        }
+        self.file = None;
+        false
+    }
+}
+
+impl StreamProvider for PlaylistProvider {
+    fn read(&mut self, samples: &mut SampleBuffer) -> uint {
+        match self.file {
+            Some(ref mut file) => file.read(samples),
+            None               => 0
+        }
+    }
+
+    fn get_sample_type(&self) -> SampleType {
+        self.file.as_ref().map(|f| f.get_sample_type()).unwrap_or(Int16)
+    }
+
+    fn get_format(&self) -> i32 {
+        self.file.as_ref().map(|f| f.get_format()).unwrap_or(0)
+    }
+
+    fn get_samplerate(&self) -> i32 {
+        self.file.as_ref().map(|f| f.get_samplerate()).unwrap_or(0)
+    }
+
+    fn rewind(&mut self) -> () {
+        {
+            let mut queue = self.queue.lock();
+            if queue.finished {
+                queue.finished = false;
+                queue.position = 0;
+            }
+        }
+        self.open_current();
+    }
+
+    fn next_track(&mut self) -> bool {
+        let next = self.queue.lock().next(false);
+        next && self.open_current()
+    }
+
+    fn get_track(&self) -> uint {
+        self.track
+    }
+}
+
+/**
+ * Play several files back to back.
+ *
+ * The tracks of a Playlist are streamed through the same source, so there is
+ * no gap between two tracks as long as they have the same format and
+ * sample rate.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use ears::{Playlist, AudioController, AudioTags, RepeatAll};
+ *
+ * fn main() -> () {
+ *     let mut playlist = Playlist::new().unwrap();
+ *     playlist.add("path/to/first.ogg");
+ *     playlist.add("path/to/second.ogg");
+ *     playlist.set_mode(RepeatAll);
+ *     playlist.play();
+ *
+ *     // Later
+ *     playlist.next();
+ *     println!("Now playing: {}", playlist.get_tags().title);
+ * }
+ * ```
+ */
+pub struct Playlist {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The internal OpenAL buffers
+    al_buffers: [u32, ..3],
+    /// The tags of the tracks
+    tracks: Vec<Tags>,
+    /// The order of the tracks, shared with the streaming task
+    queue: Arc<Mutex<PlaylistQueue>>,
+    /// The Streamer of the tracks, when the Playlist is not streaming
+    streamer: Option<Streamer>,
+    /// Receive the Streamer at the end of the streaming task
+    streamer_receiver: Option<Receiver<Streamer>>,
+    /// Set to terminate the streaming task
+    stop_flag: Arc<AtomicBool>,
+    /// The events of the streaming task
+    events: Receiver<StreamEvent>,
+    /// The track currently playing
+    current: Cell<Option<uint>>,
+    /// The Bus the Playlist is assigned to
+    bus: Option<Bus>
+}
+
+impl Playlist {
+    /**
+     * Create a new empty Playlist.
+     *
+     * # Return
+     * An Option containing Some(Playlist) on success, None otherwise
+     */
+    pub fn new() -> Option<Playlist> {
+        check_openal_context!(None);
+
+        let mut source_id = 0;
+        let mut buffer_ids = [0, ..3];
+        al::alGenSources(1, &mut source_id);
+        al::alGenBuffers(3, &mut buffer_ids[0]);
+
+        match al::openal_has_error() {
+            Some(err) => { println!("{}", err); return None; },
+            None => {}
+        };
+
+        let queue = Arc::new(Mutex::new(PlaylistQueue {
+            paths: Vec::new(),
+            order: Vec::new(),
+            position: 0,
+            finished: false,
+            mode: NoRepeat,
+            shuffle: false
+        }));
+        let provider = box PlaylistProvider {
+            queue: queue.clone(),
+            file: None,
+            track: 0
+        };
+        let (sender, receiver) = channel();
+        let mut streamer = Streamer::new(source_id,
+                                         buffer_ids.to_vec(),
+                                         provider,
+                                         50000);
+        streamer.set_events(sender);
+
+        Some(Playlist {
+            al_source: source_id,
+            al_buffers: buffer_ids,
+            tracks: Vec::new(),
+            queue: queue,
+            stop_flag: streamer.get_stop_flag(),
+            streamer: Some(streamer),
+            streamer_receiver: None,
+            events: receiver,
+            current: Cell::new(None),
+            bus: None
+        })
+    }
+
+    /**
+     * Add a track at the end of the Playlist.
+     *
+     * # Argument
+     * * `path` - The path of the file to add
+     *
+     * # Return
+     * true if the file can be played, false otherwise.
+     */
+    pub fn add(&mut self, path: &str) -> bool {
+        let file = match decoder::open(path) {
+            Ok(file) => file,
+            Err(err) => { println!("{}", err); return false; }
+        };
+        if channels::negotiate(&*file, &Default::default()).is_none() {
+            println!("Internal error : unrecognized format.");
+            return false;
+        }
+        self.tracks.push(file.get_tags());
+
+        let mut queue = self.queue.lock();
+        let track = queue.paths.len();
+        queue.paths.push(path.to_string());
+        queue.order.push(track);
+        true
+    }
+
+    /// Get the count of tracks in the Playlist.
+    pub fn get_track_count(&self) -> uint {
+        self.tracks.len()
+    }
+
+    /**
+     * Get the tags of a track.
+     *
+     * # Argument
+     * * `track` - The index of the track
+     *
+     * # Return
+     * Some(Tags) if the track exist, None otherwise.
+     */
+    pub fn get_track_tags(&self, track: uint) -> Option<Tags> {
+        self.tracks.as_slice().get(track).map(|t| t.clone())
+    }
+
+    /**
+     * Get the track currently playing.
+     *
+     * # Return
+     * The index of the track currently playing, or the track to play next
+     * if the Playlist is stopped. None if the Playlist is empty.
+     */
+    pub fn get_current_track(&self) -> Option<uint> {
+        loop {
+            match self.events.try_recv() {
+                Ok(TrackStarted(track)) => self.current.set(Some(track)),
+                Ok(_)                   => {},
+                Err(_)                  => break
+            }
+        }
+        if self.get_state() == Playing || self.get_state() == Paused {
+            self.current.get()
+        } else {
+            self.queue.lock().current()
+        }
+    }
+
+    /**
+     * Set how the tracks are chained.
+     *
+     * # Argument
+     * * `mode` - The new mode of the Playlist
+     */
+    pub fn set_mode(&mut self, mode: PlaylistMode) -> () {
+        self.queue.lock().mode = mode;
+    }
+
+    /// Get how the tracks are chained.
+    pub fn get_mode(&self) -> PlaylistMode {
+        self.queue.lock().mode
+    }
+
+    /**
+     * Shuffle the tracks or not.
+     *
+     * # Argument
+     * * `shuffle` - true to play the tracks in a random order, false to play
+     * them in the order they were added.
+     */
+    pub fn set_shuffle(&mut self, shuffle: bool) -> () {
+        let mut queue = self.queue.lock();
+        queue.shuffle = shuffle;
+        queue.reorder();
+    }
+
+    /// Are the tracks shuffled or not ?
+    pub fn is_shuffle(&self) -> bool {
+        self.queue.lock().shuffle
+    }
+
+    /**
+     * Play the next track.
+     *
+     * # Return
+     * true if there is a next track, false otherwise.
+     */
+    pub fn next(&mut self) -> bool {
+        self.change_track(|queue| queue.next(true))
+    }
+
+    /**
+     * Play the previous track.
+     *
+     * On the first track without repeat, the first track is restarted.
+     *
+     * # Return
+     * true if there is a previous track, false otherwise.
+     */
+    pub fn previous(&mut self) -> bool {
+        self.change_track(|queue| queue.previous())
+    }
+
+    /**
+     * Play a track.
+     *
+     * # Argument
+     * * `track` - The index of the track to play
+     *
+     * # Return
+     * true if the track exist, false otherwise.
+     */
+    pub fn skip_to(&mut self, track: uint) -> bool {
+        self.change_track(|queue| queue.skip_to(track))
+    }
+
+    fn change_track(&mut self, f: |&mut PlaylistQueue| -> bool) -> bool {
+        check_openal_context!(false);
+
+        let playing = match self.get_state() {
+            Playing | Paused => true,
+            _                => false
+        };
+        if playing {
+            self.stop();
+        }
+        let changed = {
+            let mut queue = self.queue.lock();
+            queue.finished = false;
+            f(&mut *queue)
+        };
+        if playing {
+            self.process_playlist();
+        }
+        changed
+    }
+
+    /// Get back the Streamer, wait for the end of the streaming task if needed.
+    fn take_streamer(&mut self) -> Option<Streamer> {
+        match self.streamer.take() {
+            Some(streamer) => Some(streamer),
+            None           => match self.streamer_receiver.take() {
+                Some(receiver) => {
+                    // The synchronous Streamers stop at their next step
+                    stream::pump();
+                    receiver.recv_opt().ok()
+                },
+                None           => None
+            }
+        }
+    }
+
+    fn process_playlist(&mut self) -> () {
+        let mut streamer = match self.take_streamer() {
+            Some(streamer) => streamer,
+            None           => return
+        };
+
+        streamer.get_provider().rewind();
+        streamer.prefill();
+        al::alSourcePlay(self.al_source);
+
+        self.streamer_receiver = Some(stream::spawn(streamer));
+    }
+
+    /**
+     * Assign the Playlist to a Bus.
+     *
+     * # Argument
+     * `bus` - The Bus to assign the Playlist to.
+     */
+    pub fn set_bus(&mut self, bus: &Bus) -> () {
+        check_openal_context!(());
+
+        let volume = self.get_volume();
+        let pitch = self.get_pitch();
+        self.remove_bus();
+        bus::add_source(bus, self.al_source, volume, pitch);
+        self.bus = Some(bus.clone());
+    }
+
+    /// Remove the Playlist from its Bus.
+    pub fn remove_bus(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+    }
+
+    /// Get the Bus of the Playlist.
+    pub fn get_bus(&self) -> Option<Bus> {
+        self.bus.clone()
+    }
+}
+
+impl AudioTags for Playlist {
+    /**
+     * Get the tags of the current track.
+     *
+     * # Return
+     * The tags of the current track, empty tags if the Playlist is empty.
+     */
+    fn get_tags(&self) -> Tags {
+        match self.get_current_track() {
+            Some(track) => self.tracks[track].clone(),
+            None        => audio_tags::empty()
+        }
+    }
+}
+
+impl AudioController for Playlist {
+    /**
+     * Play or resume the Playlist.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.get_state() {
+            Paused   => { al::alSourcePlay(self.al_source); return; },
+            _       => {
+                if self.is_playing() {
+                    self.stop();
+                }
+                self.process_playlist();
+            }
+        }
+    }
+
+    /**
+     * Pause the Playlist.
+     */
+    fn pause(&mut self) -> () {
+        source_control::pause(self.al_source)
+    }
+
+    /**
+     * Stop the Playlist.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        self.stop_flag.store(true, SeqCst);
+        al::alSourceStop(self.al_source);
+        // wait for the streaming task to terminate
+        self.streamer = self.take_streamer();
+    }
+
+    /**
+     * Check if the Playlist is playing or not.
+     *
+     * # Return
+     * True if the Playlist is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the Playlist
+     *
+     * # Return
+     * The state of the playlist as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        source_control::get_state(self.al_source)
+    }
+
+    /**
+     * Set the volume of the Playlist.
+     *
+     * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
+     * of about -6dB. Each multiplicaton by 2 equals an amplification of about
+     * +6dB.
+     *
+     * # Argument
+     * * `volume` - The volume of the Playlist, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        source_control::set_volume(self.al_source, &self.bus, volume)
+    }
+
+    /**
+     * Get the volume of the Playlist.
+     *
+     * # Return
+     * The volume of the Playlist between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        source_control::get_volume(self.al_source, &self.bus)
+    }
+
+    /**
+     * Set the minimal volume for a Playlist.
+     *
+     * The minimum volume allowed for a playlist, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume of the Playlist should be
+     * between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MIN_GAIN, min_volume)
+    }
+
+    /**
+     * Get the minimal volume of the Playlist.
+     *
+     * # Return
+     * The minimal volume of the Playlist between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MIN_GAIN, 0.)
+    }
+
+    /**
+     * Set the maximal volume for a Playlist.
+     *
+     * The maximum volume allowed for a Playlist, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume of the Playlist should be
+     * between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MAX_GAIN, max_volume)
+    }
+
+    /**
+     * Get the maximal volume of the Playlist.
+     *
+     * # Return
+     * The maximal volume of the Playlist between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MAX_GAIN, 0.)
+    }
+
+    /**
+     * Set the Playlist looping or not
+     *
+     * The default looping is false.
+     *
+     * # Arguments
+     * `looping` - The new looping state.
+     */
+    fn set_looping(&mut self, looping: bool) -> () {
+        source_control::set_bool(self.al_source, ffi::AL_LOOPING, looping)
+    }
+
+    /**
+     * Check if the Playlist is looping or not
+     *
+     * # Return
+     * True if the Playlist is looping, false otherwise.
+     */
+    fn is_looping(&self) -> bool {
+        source_control::get_bool(self.al_source, ffi::AL_LOOPING)
+    }
+
+    /**
+     * Set the pitch of the Playlist.
+     *
+     * A multiplier for the frequency (sample rate) of the Playlist's buffer.
+     *
+     * Default pitch is 1.0.
+     *
+     * # Argument
+     * * `new_pitch` - The new pitch of the Playlist in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        source_control::set_pitch(self.al_source, &self.bus, pitch)
+    }
+
+    /**
+     * Set the pitch of the Playlist.
+     *
+     * # Return
+     * The pitch of the Playlist in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        source_control::get_pitch(self.al_source, &self.bus)
+    }
+
+    /**
+     * Set the position of the Playlist relative to the listener or absolute.
+     *
+     * Default position is absolute.
+     *
+     * # Argument
+     * `relative` - True to set Playlist relative to the listener false to set the
+     * Playlist position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        source_control::set_bool(self.al_source, ffi::AL_SOURCE_RELATIVE, relative)
+    }
+
+    /**
+     * Is the Playlist relative to the listener or not ?
+     *
+     * # Return
+     * True if the Playlist is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        source_control::get_bool(self.al_source, ffi::AL_SOURCE_RELATIVE)
+    }
+
+    /**
+     * Set the Playlist location in three dimensional space.
+     *
+     * OpenAL, like OpenGL, uses a right handed coordinate system, where in a
+     * frontal default view X (thumb) points right, Y points up (index finger),
+     * and Z points towards the viewer/camera (middle finger).
+     * To switch from a left handed coordinate system, flip the sign on the Z
+     * coordinate.
+     *
+     * Default position is [0., 0., 0.].
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the position
+     * of the listener [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32, ..3]) -> () {
+        source_control::set_vector(self.al_source, ffi::AL_POSITION, position);
+    }
+
+    /**
+     * Get the position of the Playlist in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * listener [x, y, z].
+     */
+    fn get_position(&self) -> [f32, ..3] {
+        source_control::get_vector(self.al_source, ffi::AL_POSITION)
+    }
+
+    /**
+     * Set the direction of the Playlist.
+     *
+     * Specifies the current direction in local space.
+     *
+     * The default direction is: [0., 0., 0.]
+     *
+     * # Argument
+     * `direction` - The new direction of the Playlist.
+     */
+    fn set_direction(&mut self, direction: [f32, ..3]) -> () {
+        source_control::set_vector(self.al_source, ffi::AL_DIRECTION, direction)
+    }
+
+    /**
+     * Get the direction of the Playlist.
+     *
+     * # Return
+     * The current direction of the Playlist.
+     */
+    fn get_direction(&self)  -> [f32, ..3] {
+        source_control::get_vector(self.al_source, ffi::AL_DIRECTION)
+    }
+
+    /**
+     * Set the maximum distance of the Playlist.
+     *
+     * The distance above which the source is not attenuated any further with a
+     * clamped distance model, or where attenuation reaches 0.0 gain for linear
+     * distance models with a default rolloff factor.
+     *
+     * The default maximum distance is +inf.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MAX_DISTANCE, max_distance)
+    }
+
+    /**
+     * Get the maximum distance of the Playlist.
+     *
+     * # Return
+     * The maximum distance of the Playlist in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MAX_DISTANCE, 0.)
+    }
+
+    /**
+     * Set the reference distance of the Playlist.
+     *
+     * The distance in units that no attenuation occurs.
+     * At 0.0, no distance attenuation ever occurs on non-linear
+     * attenuation models.
+     *
+     * The default distance reference is 1.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the Playlist.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance)
+    }
+
+    /**
+     * Get the reference distance of the Playlist.
+     *
+     * # Return
+     * The current reference distance of the Playlist.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, 1.)
+    }
+
+    /**
+     * Set the attenuation of a Playlist.
+     *
+     * Multiplier to exaggerate or diminish distance attenuation.
+     * At 0.0, no distance attenuation ever occurs.
+     *
+     * The default attenuation is 1.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the Playlist in the range [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation)
+    }
+
+    /**
+     * Get the attenuation of a Playlist.
+     *
+     * # Return
+     * The current attenuation for the Playlist in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, 1.)
+    }
+}
+
+
+impl Drop for Playlist {
+    /// Destroy all the resources of the Playlist.
+    fn drop(&mut self) -> () {
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+        self.stop_flag.store(true, SeqCst);
+        al::alSourceStop(self.al_source);
+        self.take_streamer();
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        al::alDeleteBuffers(3, &mut self.al_buffers[0]);
+        al::alDeleteSources(1, &mut self.al_source);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use playlist::{PlaylistQueue, NoRepeat, RepeatOne, RepeatAll};
+
+    fn queue(len: uint) -> PlaylistQueue {
+        PlaylistQueue {
+            paths: range(0, len).map(|i| i.to_string()).collect(),
+            order: range(0, len).collect(),
+            position: 0,
+            finished: false,
+            mode: NoRepeat,
+            shuffle: false
+        }
+    }
+
+    #[test]
+    fn playlist_next_OK() -> () {
+        let mut q = queue(2);
+
+        assert_eq!(q.next(false), true);
+        assert_eq!(q.current(), Some(1));
+        assert_eq!(q.next(false), false);
+        assert_eq!(q.finished, true);
+    }
+
+    #[test]
+    fn playlist_previous_FAIL() -> () {
+        let mut q = queue(2);
+
+        assert_eq!(q.previous(), false);
+        assert_eq!(q.current(), Some(0));
+    }
+
+    #[test]
+    fn playlist_previous_repeat_all_OK() -> () {
+        let mut q = queue(2);
+        q.mode = RepeatAll;
+
+        assert_eq!(q.previous(), true);
+        assert_eq!(q.current(), Some(1));
+    }
+
+    #[test]
+    fn playlist_repeat_one_OK() -> () {
+        let mut q = queue(2);
+        q.mode = RepeatOne;
+
+        assert_eq!(q.next(false), true);
+        assert_eq!(q.current(), Some(0));
+        assert_eq!(q.next(true), true);
+        assert_eq!(q.current(), Some(1));
+    }
+
+    #[test]
+    fn playlist_repeat_all_OK() -> () {
+        let mut q = queue(2);
+        q.mode = RepeatAll;
+
+        q.next(false);
+        assert_eq!(q.next(false), true);
+        assert_eq!(q.current(), Some(0));
+    }
+
+    #[test]
+    fn playlist_shuffle_keep_current_OK() -> () {
+        let mut q = queue(10);
+        q.skip_to(4);
+        q.shuffle = true;
+        q.reorder();
+
+        assert_eq!(q.current(), Some(4));
+        assert_eq!(q.order.len(), 10);
+    }
+
+    #[test]
+    fn playlist_skip_to_FAIL() -> () {
+        let mut q = queue(2);
+
+        assert_eq!(q.skip_to(5), false);
+    }
+}
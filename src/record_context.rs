@@ -22,19 +22,110 @@
 #![allow(raw_pointer_deriving)]
 
 use openal::ffi;
+use io::SoundReader;
+use capture::{CaptureSource, CaptureSignal, DeviceCapture, FileCapture, SignalCapture};
+use capture::SharedCapture;
 
-/// The context needed to initialize a new Recorder
+/// The input a RecordContext captures.
+#[deriving(Clone, PartialEq, Show)]
+enum CaptureInput {
+    DeviceInput(*mut ffi::ALCdevice),
+    FileInput(String),
+    SignalInput(CaptureSignal),
+    SourceInput(SharedCapture)
+}
+
+/**
+ * The context needed to initialize a new Recorder
+ *
+ * The context returned by ears::init_in captures the default input device.
+ * A context can also capture an audio file or a generated signal in real
+ * time, to test a Recorder without microphone, or any CaptureSource.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use ears::{RecordContext, Recorder, CaptureSine};
+ *
+ * fn main() -> () {
+ *     let context = RecordContext::from_signal(CaptureSine(440., 0.5));
+ *     let mut recorder = Recorder::new(context);
+ *     recorder.start();
+ * }
+ * ```
+ */
 #[deriving(Clone, PartialEq, Show)]
 pub struct RecordContext {
-    capt_device: *mut ffi::ALCdevice
+    input: CaptureInput
+}
+
+impl RecordContext {
+    /**
+     * Create a context capturing an audio file.
+     *
+     * # Argument
+     * * `path` - The path of the file
+     *
+     * # Return
+     * Some(RecordContext) if the file can be opened, None otherwise.
+     */
+    pub fn from_file(path: &str) -> Option<RecordContext> {
+        match SoundReader::open(path) {
+            Ok(_)    => Some(RecordContext { input: FileInput(path.to_string()) }),
+            Err(err) => { println!("{}", err); None }
+        }
+    }
+
+    /**
+     * Create a context capturing a generated signal.
+     *
+     * # Argument
+     * * `signal` - The signal to capture
+     */
+    pub fn from_signal(signal: CaptureSignal) -> RecordContext {
+        RecordContext {
+            input: SignalInput(signal)
+        }
+    }
+
+    /**
+     * Create a context capturing a custom source.
+     *
+     * The clones of the context share the source.
+     *
+     * # Argument
+     * * `source` - The source to capture
+     */
+    pub fn from_source(source: Box<CaptureSource + Send>) -> RecordContext {
+        RecordContext {
+            input: SourceInput(SharedCapture::new(source))
+        }
+    }
 }
 
 pub fn new(capt_device: *mut ffi::ALCdevice) -> RecordContext {
     RecordContext {
-        capt_device: capt_device
+        input: DeviceInput(capt_device)
     }
 }
 
-pub fn get(ctxt: RecordContext) -> *mut ffi::ALCdevice {
-    ctxt.capt_device
+/**
+ * Open the source of the captured samples.
+ *
+ * # Return
+ * Some(source) on success, None if the file can't be read anymore.
+ */
+pub fn open_source(ctxt: &RecordContext) -> Option<Box<CaptureSource + Send>> {
+    match ctxt.input {
+        DeviceInput(device)     => Some(box DeviceCapture::new(device) as Box<CaptureSource + Send>),
+        FileInput(ref path)     => {
+            FileCapture::new(path.as_slice()).map(|source| {
+                box source as Box<CaptureSource + Send>
+            })
+        },
+        SignalInput(ref signal) => {
+            Some(box SignalCapture::new(signal.clone()) as Box<CaptureSource + Send>)
+        },
+        SourceInput(ref source) => Some(box source.clone() as Box<CaptureSource + Send>)
+    }
 }
@@ -23,13 +23,19 @@
 
 #![allow(missing_doc)]
 
-use std::{task, mem};
+use std::task;
 use std::vec::Vec;
+use std::io::timer::sleep;
+use std::time::Duration;
 
 use record_context::RecordContext;
 use record_context;
-use openal::ffi;
-use sndfile::{SndInfo, SndFile, FormatWav, FormatPcm16, Write};
+use capture::CAPTURE_SAMPLERATE;
+use sndfile::{SndInfo, SndFile, FormatType, FormatWav, FormatPcm16, Write, StringSoundType};
+use audio_tags::Tags;
+use audio_tags;
+use formats;
+use resampler::{Resampler, ResampleQuality};
 
 /**
  * Record audio
@@ -38,6 +44,7 @@ use sndfile::{SndInfo, SndFile, FormatWav, FormatPcm16, Write};
  * to record sound, then save it in a file, or create a SoundData object to play the
  * recorded sound in the same program.
  * A special context, RecordContext is needed to create the Recorder object.
+ * The context captures the input device, an audio file or a generated signal.
  * The Recorder work in it's own task.
  *
  * # Examples
@@ -66,7 +73,11 @@ pub struct Recorder {
     ctxt: RecordContext,
     stop_sender: Option<Sender<bool>>,
     data_receiver: Option<Receiver<Vec<i16>>>,
-    samples: Vec<i16>
+    samples: Vec<i16>,
+    tags: Option<Tags>,
+    stored_tags: Vec<StringSoundType>,
+    format: FormatType,
+    output: Option<(i32, ResampleQuality)>
 }
 
 impl Recorder {
@@ -76,8 +87,11 @@ impl Recorder {
             ctxt: record_context,
             stop_sender: None,
             data_receiver: None,
-            samples: Vec::new()
-
+            samples: Vec::new(),
+            tags: None,
+            stored_tags: Vec::new(),
+            format: FormatWav,
+            output: None
         }
     }
 
@@ -90,34 +104,27 @@ impl Recorder {
         self.data_receiver = Some(data_receiver);
 
         task::spawn(proc() {
+            let mut source = match record_context::open_source(&r_c) {
+                Some(source) => source,
+                None         => { data_sender.send(Vec::new()); return; }
+            };
             let mut terminate = false;
-            let ctxt = record_context::get(r_c);
-            unsafe { ffi::alcCaptureStart(ctxt); }
-            let mut available_samples = 0;
             let mut samples: Vec<i16> = Vec::new();
+            source.start();
 
             while !terminate {
-                unsafe {
-                    ffi::alcGetIntegerv(ctxt,
-                                        ffi::ALC_CAPTURE_SAMPLES,
-                                        1,
-                                        &mut available_samples)
-                };
-
+                let available_samples = source.available();
                 if available_samples != 0 {
-                    let tmp_buf =
-                        Vec::from_elem(available_samples as uint, 0i16);
-                    unsafe {
-                        ffi::alcCaptureSamples(ctxt,
-                                               mem::transmute(&tmp_buf.as_slice()[0]),
-                                               available_samples);
-                    }
+                    let mut tmp_buf = Vec::from_elem(available_samples, 0i16);
+                    source.read(tmp_buf.as_mut_slice());
                     samples.extend(tmp_buf.into_iter());
+                } else {
+                    sleep(Duration::milliseconds(1i64));
                 }
 
                 match stop_receiver.try_recv() {
                     Ok(_) => {
-                        unsafe { ffi::alcCaptureStop(ctxt); }
+                        source.stop();
                         terminate = true;
                     },
                     _       => {}
@@ -143,25 +150,85 @@ impl Recorder {
         }
     }
 
+    /**
+     * Set the tags written in the saved files.
+     *
+     * The empty fields are not written. The fields the format of the files
+     * can't store are ignored, get_stored_tags gives the written ones.
+     */
+    pub fn set_tags(&mut self, tags: &Tags) -> () {
+        self.tags = Some(tags.clone());
+    }
+
+    /**
+     * Get the tags written in the last saved file.
+     *
+     * # Return
+     * The fields the format of the file was able to store.
+     */
+    pub fn get_stored_tags(&self) -> &[StringSoundType] {
+        self.stored_tags.as_slice()
+    }
+
+    /**
+     * Set the major format of the saved files, WAV by default.
+     *
+     * The samples are saved in 16 bits PCM, with the usual extension of the
+     * format.
+     *
+     * # Argument
+     * * `format` - The major format, like FormatAiff
+     */
+    pub fn set_output_format(&mut self, format: FormatType) -> () {
+        self.format = format;
+    }
+
+    /**
+     * Set the sample rate of the saved files.
+     *
+     * The samples are captured at 44100 Hz and converted when saved.
+     */
+    pub fn set_output_samplerate(&mut self, samplerate: i32, quality: ResampleQuality) -> () {
+        self.output = Some((samplerate, quality));
+    }
+
     pub fn save_to_file(&mut self, filename: &str) -> bool {
         if self.samples.len() == 0 {
             false
         } else {
+            let (samplerate, samples) = match self.output {
+                Some((rate, ref quality)) if rate != CAPTURE_SAMPLERATE => {
+                    let resampler = Resampler::new(CAPTURE_SAMPLERATE, rate, 1, quality.clone());
+                    (rate, resampler.process_i16(self.samples.as_slice()))
+                },
+                _                                                       => {
+                    (CAPTURE_SAMPLERATE, self.samples.clone())
+                }
+            };
             let infos = box SndInfo {
-                frames : self.samples.len() as i64,
-                samplerate : 44100,
+                frames : samples.len() as i64,
+                samplerate : samplerate,
                 channels : 1,
-                format : (FormatPcm16 | FormatWav) as i32,
+                format : (FormatPcm16 | self.format) as i32,
                 sections : 0,
                 seekable : 0
             };
 
+            let extension = formats::formats().major.into_iter()
+                .find(|description| description.format == Some(self.format))
+                .map(|description| description.extension)
+                .unwrap_or("wav".to_string());
             let mut file_ext = String::from_str(filename);
-            file_ext.push_str(".wav");
+            file_ext.push_str(".");
+            file_ext.push_str(extension.as_slice());
             match SndFile::new_with_info(file_ext.as_slice(), Write, infos) {
                 Ok(mut f) => {
-                    let len = self.samples.len() as i64;
-                    f.write_i16(self.samples.as_mut_slice(), len);
+                    self.stored_tags = match self.tags {
+                        Some(ref tags) => audio_tags::set_sound_tags(&mut f, tags),
+                        None           => Vec::new()
+                    };
+                    let len = samples.len() as i64;
+                    f.write_i16(samples.as_slice(), len);
                     f.close();
                     true
                 },
@@ -170,3 +237,64 @@ impl Recorder {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::io::TempDir;
+    use std::io::timer::sleep;
+    use std::time::Duration;
+    use recorder::Recorder;
+    use record_context::RecordContext;
+    use capture::CaptureSine;
+    use audio_tags;
+    use sndfile::{FormatAu, Title, Artist};
+
+    #[test]
+    fn recorder_record_signal_OK() -> () {
+        let mut recorder = Recorder::new(RecordContext::from_signal(CaptureSine(440., 0.5)));
+        recorder.start();
+        sleep(Duration::milliseconds(50i64));
+        assert_eq!(recorder.stop(), true);
+        assert!(recorder.samples.len() > 0);
+    }
+
+    #[test]
+    fn recorder_record_file_OK() -> () {
+        let mut recorder = Recorder::new(RecordContext::from_file("res/shot.wav").unwrap());
+        recorder.start();
+        sleep(Duration::milliseconds(50i64));
+        assert_eq!(recorder.stop(), true);
+        assert!(recorder.samples.len() > 0);
+    }
+
+    #[test]
+    fn recorder_stored_tags_OK() -> () {
+        let mut recorder = Recorder::new(RecordContext::from_file("res/shot.wav").unwrap());
+        recorder.start();
+        sleep(Duration::milliseconds(50i64));
+        assert_eq!(recorder.stop(), true);
+
+        let mut tags = audio_tags::empty();
+        tags.title = "Shot".to_string();
+        tags.artist = "ears".to_string();
+        recorder.set_tags(&tags);
+        let dir = TempDir::new("ears_recorder").unwrap();
+        let path = dir.path().join("test");
+        let path = path.as_str().unwrap();
+
+        assert!(recorder.save_to_file(path));
+        assert_eq!(recorder.get_stored_tags(), [Title, Artist].as_slice());
+
+        // The AU files can't store any tag
+        recorder.set_output_format(FormatAu);
+        assert!(recorder.save_to_file(path));
+        assert!(recorder.get_stored_tags().is_empty());
+    }
+
+    #[test]
+    fn recorder_record_file_FAIL() -> () {
+        assert!(RecordContext::from_file("toto.wav").is_none());
+    }
+}
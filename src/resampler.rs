@@ -0,0 +1,287 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Convert the sample rate of interleaved samples.
+
+use std::cmp;
+use std::f32::consts::PI;
+use std::num::Float;
+
+/// Half the count of input frames used by the windowed-sinc interpolation.
+static SINC_HALF_WIDTH: int = 16;
+
+/**
+ * The quality of a sample rate conversion.
+ *
+ * * ResampleLinear - Linear interpolation, the fastest
+ * * ResampleCubic - Catmull-Rom interpolation between 4 frames
+ * * ResampleSinc - Windowed-sinc interpolation between 32 frames, filters
+ * the frequencies above the new Nyquist frequency when downsampling
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum ResampleQuality {
+    /// Linear interpolation
+    ResampleLinear,
+    /// Cubic interpolation
+    ResampleCubic,
+    /// Windowed-sinc interpolation
+    ResampleSinc
+}
+
+/**
+ * Convert interleaved samples from a sample rate to another.
+ *
+ * # Example
+ * ```Rust
+ * use ears::{Resampler, ResampleSinc};
+ *
+ * // Stereo, from 44.1 kHz to 48 kHz
+ * let resampler = Resampler::new(44100, 48000, 2, ResampleSinc);
+ * let output = resampler.process(input.as_slice());
+ * ```
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub struct Resampler {
+    from: i32,
+    to: i32,
+    channels: uint,
+    quality: ResampleQuality
+}
+
+impl Resampler {
+    /**
+     * Create a new Resampler.
+     *
+     * # Arguments
+     * * `from` - The sample rate of the input
+     * * `to` - The sample rate of the output
+     * * `channels` - The count of interleaved channels
+     * * `quality` - The interpolation to use
+     */
+    pub fn new(from: i32,
+               to: i32,
+               channels: uint,
+               quality: ResampleQuality) -> Resampler {
+        Resampler {
+            from: from,
+            to: to,
+            channels: cmp::max(channels, 1),
+            quality: quality
+        }
+    }
+
+    /**
+     * Get the count of output frames for a count of input frames.
+     */
+    pub fn get_output_frames(&self, frames: uint) -> uint {
+        if self.from <= 0 || self.to <= 0 {
+            return frames;
+        }
+        (frames as u64 * self.to as u64 / self.from as u64) as uint
+    }
+
+    /**
+     * Convert interleaved samples.
+     *
+     * # Argument
+     * * `input` - The interleaved samples at the input rate
+     *
+     * # Return
+     * The interleaved samples at the output rate.
+     */
+    pub fn process(&self, input: &[f32]) -> Vec<f32> {
+        let frames = input.len() / self.channels;
+        if self.from == self.to || self.from <= 0 || self.to <= 0 || frames == 0 {
+            return input.slice_to(frames * self.channels).to_vec();
+        }
+
+        let out_frames = self.get_output_frames(frames);
+        let step = self.from as f64 / self.to as f64;
+        let mut output = Vec::with_capacity(out_frames * self.channels);
+
+        for i in range(0, out_frames) {
+            let position = i as f64 * step;
+            let index = position.floor() as int;
+            let fraction = (position - index as f64) as f32;
+            for channel in range(0, self.channels) {
+                let samples = ChannelSamples {
+                    input: input,
+                    channels: self.channels,
+                    channel: channel
+                };
+                output.push(match self.quality {
+                    ResampleLinear => {
+                        let current = samples.get(index);
+                        current + (samples.get(index + 1) - current) * fraction
+                    },
+                    ResampleCubic  => {
+                        cubic(samples.get(index - 1), samples.get(index),
+                              samples.get(index + 1), samples.get(index + 2), fraction)
+                    },
+                    ResampleSinc   => self.sinc(&samples, index, fraction)
+                });
+            }
+        }
+        output
+    }
+
+    /**
+     * Convert interleaved signed 16 bit samples.
+     *
+     * # Argument
+     * * `input` - The interleaved samples at the input rate
+     *
+     * # Return
+     * The interleaved samples at the output rate.
+     */
+    pub fn process_i16(&self, input: &[i16]) -> Vec<i16> {
+        let floats: Vec<f32> = input.iter().map(|&s| s as f32 / 32768.).collect();
+        self.process(floats.as_slice()).iter().map(|&s| {
+            (s.max(-1.).min(1.) * 32767.) as i16
+        }).collect()
+    }
+
+    /// Interpolate a sample with a Blackman windowed sinc.
+    fn sinc(&self, samples: &ChannelSamples, index: int, fraction: f32) -> f32 {
+        // Lower the cutoff to the new Nyquist frequency when downsampling
+        let cutoff = (self.to as f32 / self.from as f32).min(1.);
+        let mut sum = 0f32;
+        let mut weights = 0f32;
+
+        for n in range(index - SINC_HALF_WIDTH + 1, index + SINC_HALF_WIDTH + 1) {
+            let distance = (n - index) as f32 - fraction;
+            let x = distance * cutoff;
+            let sinc = if x == 0. { 1. } else { (PI * x).sin() / (PI * x) };
+            let window = blackman(distance / SINC_HALF_WIDTH as f32);
+            let weight = sinc * window;
+            sum = sum + samples.get(n) * weight;
+            weights = weights + weight;
+        }
+
+        if weights == 0. { 0. } else { sum / weights }
+    }
+}
+
+/// A channel of interleaved samples, repeating the first and the last
+/// frames past the edges.
+struct ChannelSamples<'a> {
+    input: &'a [f32],
+    channels: uint,
+    channel: uint
+}
+
+impl<'a> ChannelSamples<'a> {
+    fn get(&self, n: int) -> f32 {
+        let frames = (self.input.len() / self.channels) as int;
+        let n = cmp::min(cmp::max(n, 0), frames - 1) as uint;
+        self.input[n * self.channels + self.channel]
+    }
+}
+
+/**
+ * Convert interleaved samples from a sample rate to another.
+ *
+ * # Arguments
+ * * `input` - The interleaved samples
+ * * `channels` - The count of interleaved channels
+ * * `from` - The sample rate of the input
+ * * `to` - The sample rate of the output
+ * * `quality` - The interpolation to use
+ *
+ * # Return
+ * The interleaved samples at the output rate.
+ */
+pub fn resample(input: &[f32],
+                channels: uint,
+                from: i32,
+                to: i32,
+                quality: ResampleQuality) -> Vec<f32> {
+    Resampler::new(from, to, channels, quality).process(input)
+}
+
+/// Catmull-Rom interpolation between `p1` and `p2`.
+fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let a = -0.5 * p0 + 1.5 * p1 - 1.5 * p2 + 0.5 * p3;
+    let b = p0 - 2.5 * p1 + 2. * p2 - 0.5 * p3;
+    let c = -0.5 * p0 + 0.5 * p2;
+    ((a * t + b) * t + c) * t + p1
+}
+
+/// The Blackman window, for `x` from -1 to 1.
+fn blackman(x: f32) -> f32 {
+    if x.abs() >= 1. {
+        return 0.;
+    }
+    let phase = PI * (x + 1.);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos()
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::num::Float;
+    use resampler::{Resampler, resample, ResampleLinear, ResampleCubic,
+                    ResampleSinc};
+
+    #[test]
+    fn resampler_same_rate_OK() -> () {
+        let input = [0.1f32, 0.2, 0.3, 0.4];
+
+        assert_eq!(resample(input.as_slice(), 2, 44100, 44100, ResampleSinc),
+                   input.to_vec());
+    }
+
+    #[test]
+    fn resampler_length_OK() -> () {
+        let input = Vec::from_elem(44100 * 2, 0f32);
+        let resampler = Resampler::new(44100, 48000, 2, ResampleLinear);
+
+        assert_eq!(resampler.process(input.as_slice()).len(), 48000 * 2);
+        assert_eq!(resampler.get_output_frames(441), 480);
+    }
+
+    #[test]
+    fn resampler_constant_OK() -> () {
+        let input = Vec::from_elem(1000, 0.5f32);
+
+        for quality in [ResampleLinear, ResampleCubic, ResampleSinc].iter() {
+            let output = resample(input.as_slice(), 1, 48000, 22050, quality.clone());
+            for sample in output.iter() {
+                assert!((*sample - 0.5).abs() < 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn resampler_linear_OK() -> () {
+        let output = resample([0f32, 1.].as_slice(), 1, 1, 2, ResampleLinear);
+
+        assert_eq!(output, vec![0f32, 0.5, 1., 1.]);
+    }
+
+    #[test]
+    fn resampler_i16_OK() -> () {
+        let resampler = Resampler::new(8000, 16000, 1, ResampleCubic);
+
+        assert_eq!(resampler.process_i16([0i16, 0, 0].as_slice()), Vec::from_elem(6, 0i16));
+    }
+}
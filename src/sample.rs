@@ -0,0 +1,269 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The types of samples uploaded to OpenAL.
+
+use std::mem;
+use std::num::Float;
+use libc::c_void;
+
+use decoder::Decoder;
+use channels::ChannelPlan;
+
+/**
+ * The type of the samples of an OpenAL buffer.
+ *
+ * * Int8 - Unsigned 8 bit samples, the smallest in memory
+ * * Int16 - Signed 16 bit samples, supported by every OpenAL implementation
+ * * Float32 - 32 bit float samples, keep the precision of high bit depth
+ * and float files, require the AL_EXT_FLOAT32 extension
+ */
+#[deriving(Clone, PartialEq, Show)]
+pub enum SampleType {
+    /// Unsigned 8 bit samples
+    Int8,
+    /// Signed 16 bit samples
+    Int16,
+    /// 32 bit float samples
+    Float32
+}
+
+/// Samples of one of the SampleTypes.
+#[doc(hidden)]
+pub enum SampleBuffer {
+    Int8Buffer(Vec<u8>),
+    Int16Buffer(Vec<i16>),
+    Float32Buffer(Vec<f32>)
+}
+
+impl SampleBuffer {
+    /**
+     * Create a SampleBuffer filled with silence.
+     *
+     * # Arguments
+     * * `sample_type` - The type of the samples
+     * * `len` - The count of samples
+     */
+    pub fn new(sample_type: SampleType, len: uint) -> SampleBuffer {
+        match sample_type {
+            Int8    => Int8Buffer(Vec::from_elem(len, 128u8)),
+            Int16   => Int16Buffer(Vec::from_elem(len, 0i16)),
+            Float32 => Float32Buffer(Vec::from_elem(len, 0f32))
+        }
+    }
+
+    /**
+     * Create a SampleBuffer from float samples.
+     *
+     * # Arguments
+     * * `sample_type` - The type of the samples
+     * * `samples` - The samples, from -1 to 1
+     */
+    pub fn from_f32(sample_type: SampleType, samples: &[f32]) -> SampleBuffer {
+        let mut buffer = SampleBuffer::new(sample_type, samples.len());
+        buffer.copy_from_f32(samples);
+        buffer
+    }
+
+    /**
+     * Append the first samples of another SampleBuffer of the same type.
+     *
+     * # Arguments
+     * * `other` - The samples to append
+     * * `count` - The count of samples to append
+     */
+    pub fn append(&mut self, other: &SampleBuffer, count: uint) -> () {
+        match (self, other) {
+            (&Int8Buffer(ref mut samples), &Int8Buffer(ref other))       => {
+                samples.push_all(other.slice_to(count))
+            },
+            (&Int16Buffer(ref mut samples), &Int16Buffer(ref other))     => {
+                samples.push_all(other.slice_to(count))
+            },
+            (&Float32Buffer(ref mut samples), &Float32Buffer(ref other)) => {
+                samples.push_all(other.slice_to(count))
+            },
+            _                                                            => {
+                panic!("Internal error : appending samples of another type.")
+            }
+        }
+    }
+
+    /// Get the type of the samples.
+    pub fn get_type(&self) -> SampleType {
+        match *self {
+            Int8Buffer(_)    => Int8,
+            Int16Buffer(_)   => Int16,
+            Float32Buffer(_) => Float32
+        }
+    }
+
+    /// Get the count of samples.
+    pub fn len(&self) -> uint {
+        match *self {
+            Int8Buffer(ref samples)    => samples.len(),
+            Int16Buffer(ref samples)   => samples.len(),
+            Float32Buffer(ref samples) => samples.len()
+        }
+    }
+
+    /// Get the size in bytes of `count` samples.
+    pub fn byte_size(&self, count: uint) -> uint {
+        count * match *self {
+            Int8Buffer(_)    => mem::size_of::<u8>(),
+            Int16Buffer(_)   => mem::size_of::<i16>(),
+            Float32Buffer(_) => mem::size_of::<f32>()
+        }
+    }
+
+    /// Get a pointer to the samples, to upload them to OpenAL.
+    pub fn as_ptr(&self) -> *mut c_void {
+        match *self {
+            Int8Buffer(ref samples)    => samples.as_ptr() as *mut c_void,
+            Int16Buffer(ref samples)   => samples.as_ptr() as *mut c_void,
+            Float32Buffer(ref samples) => samples.as_ptr() as *mut c_void
+        }
+    }
+
+    /**
+     * Read interleaved frames from a file.
+     *
+     * The channels are reordered or mixed down to the layout of the plan.
+     *
+     * # Arguments
+     * * `file` - The file to read
+     * * `plan` - How to convert the channels of the file
+     *
+     * # Return
+     * The count of samples read, in the layout of the plan.
+     */
+    pub fn read_from(&mut self, file: &mut Decoder, plan: &ChannelPlan) -> uint {
+        let channels = plan.get_output_channels();
+        let frames = (self.len() / channels) as i64;
+
+        if plan.is_downmix() {
+            let mut tmp = Vec::from_elem(frames as uint * plan.get_file_channels(), 0f32);
+            let read = file.readf_f32(tmp.as_mut_slice(), frames) as uint;
+            let mixed = plan.mix(tmp.slice_to(read * plan.get_file_channels()));
+            self.copy_from_f32(mixed.as_slice());
+            return read * channels;
+        }
+
+        let read = match *self {
+            Int8Buffer(ref mut samples) => {
+                let mut tmp = Vec::from_elem(samples.len(), 0i16);
+                let read = file.readf_i16(tmp.as_mut_slice(), frames);
+                for (dst, src) in samples.iter_mut().zip(tmp.iter()) {
+                    *dst = to_unsigned_8(*src);
+                }
+                plan.reorder(samples.as_mut_slice());
+                read
+            },
+            Int16Buffer(ref mut samples)   => {
+                let read = file.readf_i16(samples.as_mut_slice(), frames);
+                plan.reorder(samples.as_mut_slice());
+                read
+            },
+            Float32Buffer(ref mut samples) => {
+                let read = file.readf_f32(samples.as_mut_slice(), frames);
+                plan.reorder(samples.as_mut_slice());
+                read
+            }
+        };
+        read as uint * channels
+    }
+
+    /**
+     * Convert the samples of the buffer to float samples.
+     *
+     * # Argument
+     * * `count` - The count of samples to convert
+     */
+    pub fn to_f32(&self, count: uint) -> Vec<f32> {
+        match *self {
+            Int8Buffer(ref samples)    => {
+                samples.slice_to(count).iter().map(|s| (*s as f32 - 128.) / 128.).collect()
+            },
+            Int16Buffer(ref samples)   => {
+                samples.slice_to(count).iter().map(|s| *s as f32 / 32767.).collect()
+            },
+            Float32Buffer(ref samples) => samples.slice_to(count).to_vec()
+        }
+    }
+
+    /// Convert float samples to the type of the buffer.
+    pub fn copy_from_f32(&mut self, input: &[f32]) -> () {
+        match *self {
+            Int8Buffer(ref mut samples)    => {
+                for (dst, src) in samples.iter_mut().zip(input.iter()) {
+                    *dst = to_unsigned_8(from_f32(*src));
+                }
+            },
+            Int16Buffer(ref mut samples)   => {
+                for (dst, src) in samples.iter_mut().zip(input.iter()) {
+                    *dst = from_f32(*src);
+                }
+            },
+            Float32Buffer(ref mut samples) => {
+                for (dst, src) in samples.iter_mut().zip(input.iter()) {
+                    *dst = *src;
+                }
+            }
+        }
+    }
+}
+
+/// Convert a float sample to a signed 16 bit sample.
+pub fn from_f32(sample: f32) -> i16 {
+    (sample.max(-1.).min(1.) * 32767.) as i16
+}
+
+/// Convert a signed 16 bit sample to an unsigned 8 bit sample.
+fn to_unsigned_8(sample: i16) -> u8 {
+    ((sample >> 8) + 128) as u8
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use sample::{to_unsigned_8, from_f32, SampleBuffer, Int8, Float32};
+
+    #[test]
+    fn sample_to_unsigned_8_OK() -> () {
+        assert_eq!(to_unsigned_8(0), 128);
+        assert_eq!(to_unsigned_8(32767), 255);
+        assert_eq!(to_unsigned_8(-32768), 0);
+    }
+
+    #[test]
+    fn sample_from_f32_OK() -> () {
+        assert_eq!(from_f32(0.), 0);
+        assert_eq!(from_f32(1.), 32767);
+        assert_eq!(from_f32(-2.), -32767);
+    }
+
+    #[test]
+    fn sample_buffer_size_OK() -> () {
+        assert_eq!(SampleBuffer::new(Int8, 10).byte_size(10), 10);
+        assert_eq!(SampleBuffer::new(Float32, 10).byte_size(10), 40);
+    }
+}
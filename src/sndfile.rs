@@ -32,8 +32,18 @@
 
 #![allow(dead_code)]
 
-use std::ptr;
+use std::{ptr, mem, cmp};
 use std::c_str::CString;
+use std::num::FromPrimitive;
+use libc::{c_char, c_void};
+
+use formats::FormatDescription;
+use channels::{Speaker, SpeakerMono, SpeakerLeft, SpeakerRight, SpeakerCenter,
+               SpeakerLfe, SpeakerRearLeft, SpeakerRearRight,
+               SpeakerRearCenter, SpeakerSideLeft, SpeakerSideRight,
+               AmbisonicW, AmbisonicX, AmbisonicY, AmbisonicZ, SpeakerOther};
+use metadata::{BroadcastInfo, CuePoint, Instrument, Loop, LoopMode, LoopNone,
+               LoopForward, LoopBackward, LoopAlternating};
 
 #[doc(hidden)]
 #[cfg(any(target_os="macos", target_os="linux", target_os="win32"))]
@@ -59,6 +69,23 @@ pub struct SndInfo {
     pub seekable : i32
 }
 
+impl SndInfo {
+    /// Get the major format of the file, like FormatWav or FormatFlac.
+    pub fn get_major_format(&self) -> Option<FormatType> {
+        FromPrimitive::from_i32(self.format & ffi::SF_FORMAT_TYPEMASK as i32)
+    }
+
+    /// Get the encoding of the samples, like FormatPcm16 or FormatVorbis.
+    pub fn get_subtype_format(&self) -> Option<FormatType> {
+        FromPrimitive::from_i32(self.format & ffi::SF_FORMAT_SUBMASK as i32)
+    }
+
+    /// Check if the file supports the seek operations.
+    pub fn is_seekable(&self) -> bool {
+        self.seekable == ffi::SF_TRUE as i32
+    }
+}
+
 /// Modes availables for the open function.
 ///
 /// * Read - Read only mode
@@ -71,6 +98,7 @@ pub enum OpenMode {
 }
 
 /// Type of strings available for method get_string()
+#[deriving(Show, Clone, PartialEq)]
 pub enum StringSoundType {
     Title       = ffi::SF_STR_TITLE as int,
     Copyright   = ffi::SF_STR_COPYRIGHT as int,
@@ -86,6 +114,7 @@ pub enum StringSoundType {
 
 /// Types of error who can be return by API functions
 #[repr(C)]
+#[deriving(Show, Clone, PartialEq, FromPrimitive)]
 pub enum Error {
     NoError             = ffi::SF_ERR_NO_ERROR as int,
     UnrecognizedFormat  = ffi::SF_ERR_UNRECOGNISED_FORMAT as int,
@@ -161,7 +190,7 @@ pub enum SeekMode {
 /// * EndianBig - Force big endian-ness
 /// * EndianCpu - Force CPU endian-ness
 #[repr(C)]
-#[deriving(Show, Clone, PartialOrd, PartialEq)]
+#[deriving(Show, Clone, PartialOrd, PartialEq, FromPrimitive)]
 pub enum FormatType {
     FormatWav = ffi::SF_FORMAT_WAV as int,
     FormatAiff = ffi::SF_FORMAT_AIFF as int,
@@ -231,14 +260,6 @@ pub struct SndFile {
     info : Box<SndInfo>
 }
 
-impl Clone for SndFile {
-    fn clone(&self) -> SndFile {
-        SndFile {
-            handle : self.handle,
-            info : self.info.clone()
-        }
-    }
-}
 
 impl SndFile {
     /**
@@ -408,18 +429,100 @@ impl SndFile {
     }
 
 
+    /**
+     * Get the channel map of the file.
+     *
+     * Return Some(Speakers) with the speaker of each channel if the file
+     * contains a channel map, None otherwise.
+     */
+    pub fn get_channel_map(&self) -> Option<Vec<Speaker>> {
+        let channels = self.info.channels as uint;
+        if channels == 0 {
+            return None;
+        }
+        let mut map = Vec::from_elem(channels, ffi::SF_CHANNEL_MAP_INVALID);
+        let found = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_GET_CHANNEL_MAP_INFO,
+                            map.as_mut_ptr() as *mut c_void,
+                            (channels * mem::size_of::<i32>()) as i32) == ffi::SF_TRUE
+        };
+        if found {
+            Some(map.iter().map(|&channel| speaker_from_c(channel)).collect())
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Get the formats supported by the linked libsndfile.
+     *
+     * # Argument
+     * * major - true for the major formats, false for the subtypes
+     *
+     * Return the descriptions of the formats.
+     */
+    pub fn get_formats(major: bool) -> Vec<FormatDescription> {
+        let (count_command, format_command) = if major {
+            (ffi::SFC_GET_FORMAT_MAJOR_COUNT, ffi::SFC_GET_FORMAT_MAJOR)
+        } else {
+            (ffi::SFC_GET_FORMAT_SUBTYPE_COUNT, ffi::SFC_GET_FORMAT_SUBTYPE)
+        };
+        let mut count = 0i32;
+        unsafe {
+            ffi::sf_command(ptr::null_mut(),
+                            count_command,
+                            &mut count as *mut i32 as *mut c_void,
+                            mem::size_of::<i32>() as i32);
+        }
+
+        range(0, count).filter_map(|index| {
+            let mut info = ffi::FormatInfo {
+                format: index,
+                name: ptr::null_mut(),
+                extension: ptr::null_mut()
+            };
+            let error = unsafe {
+                ffi::sf_command(ptr::null_mut(),
+                                format_command,
+                                &mut info as *mut ffi::FormatInfo as *mut c_void,
+                                mem::size_of::<ffi::FormatInfo>() as i32)
+            };
+            if error != 0 || info.name.is_null() {
+                return None;
+            }
+            Some(FormatDescription {
+                id: info.format,
+                format: FromPrimitive::from_i32(info.format),
+                name: from_c_string(info.name),
+                extension: from_c_string(info.extension)
+            })
+        }).collect()
+    }
+
     /**
      * Close the SndFile object.
      *
-     * This function must be called before the exist of the program to destroy
-     * all the resources.
+     * The file is closed automatically when the SndFile is dropped, close it
+     * explicitly to check the error. Closing an already closed SndFile does
+     * nothing.
      *
      * Return NoError if destruction success, an other error code otherwise.
      */
-    pub fn close(&self) -> Error {
-        unsafe {
-            ffi::sf_close(self.handle)
+    pub fn close(&mut self) -> Error {
+        if self.handle.is_null() {
+            return NoError;
         }
+        let error = unsafe {
+            ffi::sf_close(self.handle)
+        };
+        self.handle = ptr::null_mut();
+        error
+    }
+
+    /// Check if the SndFile is closed.
+    pub fn is_closed(&self) -> bool {
+        self.handle.is_null()
     }
 
     /**
@@ -568,9 +671,9 @@ impl SndFile {
      *
      * Return the count of wrote items.
      */
-    pub fn write_i16<'r>(&'r mut self, array : &'r mut [i16], items : i64) -> i64 {
+    pub fn write_i16<'r>(&'r mut self, array : &'r [i16], items : i64) -> i64 {
         unsafe {
-            ffi::sf_write_short(self.handle, array.as_mut_ptr(), items)
+            ffi::sf_write_short(self.handle, array.as_ptr() as *mut i16, items)
         }
     }
 
@@ -583,9 +686,9 @@ impl SndFile {
      *
      * Return the count of wrote items.
      */
-    pub fn write_i32<'r>(&'r mut self, array : &'r mut [i32], items : i64) -> i64 {
+    pub fn write_i32<'r>(&'r mut self, array : &'r [i32], items : i64) -> i64 {
         unsafe {
-            ffi::sf_write_int(self.handle, array.as_mut_ptr(), items)
+            ffi::sf_write_int(self.handle, array.as_ptr() as *mut i32, items)
         }
     }
 
@@ -598,9 +701,9 @@ impl SndFile {
      *
      * Return the count of wrote items.
      */
-    pub fn write_f32<'r>(&'r mut self, array : &'r mut [f32], items : i64) -> i64 {
+    pub fn write_f32<'r>(&'r mut self, array : &'r [f32], items : i64) -> i64 {
         unsafe {
-            ffi::sf_write_float(self.handle, array.as_mut_ptr(), items)
+            ffi::sf_write_float(self.handle, array.as_ptr() as *mut f32, items)
         }
     }
 
@@ -613,9 +716,9 @@ impl SndFile {
      *
      * Return the count of wrote items.
      */
-    pub fn write_f64<'r>(&'r mut self, array : &'r mut [f64], items : i64) -> i64 {
+    pub fn write_f64<'r>(&'r mut self, array : &'r [f64], items : i64) -> i64 {
         unsafe {
-            ffi::sf_write_double(self.handle, array.as_mut_ptr(), items)
+            ffi::sf_write_double(self.handle, array.as_ptr() as *mut f64, items)
         }
     }
 
@@ -628,9 +731,9 @@ impl SndFile {
      *
      * Return the count of wrote frames.
      */
-    pub fn writef_i16<'r>(&'r mut self, array : &'r mut [i16], frames : i64) -> i64 {
+    pub fn writef_i16<'r>(&'r mut self, array : &'r [i16], frames : i64) -> i64 {
         unsafe {
-            ffi::sf_writef_short(self.handle, array.as_mut_ptr(), frames)
+            ffi::sf_writef_short(self.handle, array.as_ptr() as *mut i16, frames)
         }
     }
 
@@ -643,9 +746,9 @@ impl SndFile {
      *
      * Return the count of wrote frames.
      */
-    pub fn writef_i32<'r>(&'r mut self, array : &'r mut [i32], frames : i64) -> i64 {
+    pub fn writef_i32<'r>(&'r mut self, array : &'r [i32], frames : i64) -> i64 {
         unsafe {
-            ffi::sf_writef_int(self.handle, array.as_mut_ptr(), frames)
+            ffi::sf_writef_int(self.handle, array.as_ptr() as *mut i32, frames)
         }
     }
 
@@ -658,9 +761,9 @@ impl SndFile {
      *
      * Return the count of wrote frames.
      */
-    pub fn writef_f32<'r>(&'r mut self, array : &'r mut [f32], frames : i64) -> i64 {
+    pub fn writef_f32<'r>(&'r mut self, array : &'r [f32], frames : i64) -> i64 {
         unsafe {
-            ffi::sf_writef_float(self.handle, array.as_mut_ptr(), frames)
+            ffi::sf_writef_float(self.handle, array.as_ptr() as *mut f32, frames)
         }
     }
 
@@ -673,10 +776,185 @@ impl SndFile {
      *
      * Return the count of wrote frames.
      */
-    pub fn writef_f64<'r>(&'r mut self, array : &'r mut [f64], frames : i64) -> i64 {
+    pub fn writef_f64<'r>(&'r mut self, array : &'r [f64], frames : i64) -> i64 {
+        unsafe {
+            ffi::sf_writef_double(self.handle, array.as_ptr() as *mut f64, frames)
+        }
+    }
+
+    /// Send a command with a struct to libsndfile, return true on success.
+    fn command<T>(&self, command: i32, data: &mut T) -> bool {
         unsafe {
-            ffi::sf_writef_double(self.handle, array.as_mut_ptr(), frames)
+            ffi::sf_command(self.handle,
+                            command,
+                            data as *mut T as *mut c_void,
+                            mem::size_of::<T>() as i32) == ffi::SF_TRUE
+        }
+    }
+
+    /**
+     * Get the Broadcast Wave Format informations of the file.
+     *
+     * Return Some(BroadcastInfo) if the file contains a `bext` chunk, None
+     * otherwise.
+     */
+    pub fn get_broadcast_info(&self) -> Option<BroadcastInfo> {
+        let mut info: ffi::SF_BROADCAST_INFO = unsafe { mem::zeroed() };
+        if !self.command(ffi::SFC_GET_BROADCAST_INFO, &mut info) {
+            return None;
+        }
+        let history_size = cmp::min(info.coding_history_size as uint,
+                                    info.coding_history.len());
+
+        Some(BroadcastInfo {
+            description: from_c_chars(&info.description),
+            originator: from_c_chars(&info.originator),
+            originator_reference: from_c_chars(&info.originator_reference),
+            origination_date: from_c_chars(&info.origination_date),
+            origination_time: from_c_chars(&info.origination_time),
+            time_reference: (info.time_reference_high as u64 << 32) |
+                            info.time_reference_low as u64,
+            version: info.version,
+            umid: from_c_chars(&info.umid),
+            coding_history: from_c_chars(info.coding_history.slice_to(history_size))
+        })
+    }
+
+    /**
+     * Set the Broadcast Wave Format informations of the file.
+     *
+     * # Argument
+     * * info - The informations to set, the too long strings are truncated
+     *
+     * Return true if the format of the file can store them, false otherwise.
+     */
+    pub fn set_broadcast_info(&mut self, info: &BroadcastInfo) -> bool {
+        let mut c_info: ffi::SF_BROADCAST_INFO = unsafe { mem::zeroed() };
+        to_c_chars(info.description.as_slice(), &mut c_info.description);
+        to_c_chars(info.originator.as_slice(), &mut c_info.originator);
+        to_c_chars(info.originator_reference.as_slice(), &mut c_info.originator_reference);
+        to_c_chars(info.origination_date.as_slice(), &mut c_info.origination_date);
+        to_c_chars(info.origination_time.as_slice(), &mut c_info.origination_time);
+        c_info.time_reference_low = info.time_reference as u32;
+        c_info.time_reference_high = (info.time_reference >> 32) as u32;
+        c_info.version = info.version;
+        to_c_chars(info.umid.as_slice(), &mut c_info.umid);
+        c_info.coding_history_size = to_c_chars(info.coding_history.as_slice(),
+                                                &mut c_info.coding_history) as u32;
+
+        self.command(ffi::SFC_SET_BROADCAST_INFO, &mut c_info)
+    }
+
+    /**
+     * Get the cue points of the file.
+     *
+     * Return the cue points, empty if the file doesn't contain any.
+     */
+    pub fn get_cues(&self) -> Vec<CuePoint> {
+        let mut count = 0u32;
+        if !self.command(ffi::SFC_GET_CUE_COUNT, &mut count) || count == 0 {
+            return Vec::new();
+        }
+        let mut cues: Box<ffi::SF_CUES> = box unsafe { mem::zeroed() };
+        if !self.command(ffi::SFC_GET_CUE, &mut *cues) {
+            return Vec::new();
+        }
+        let count = cmp::min(cues.cue_count as uint, ffi::SF_MAX_CUES);
+
+        cues.cue_points.slice_to(count).iter().map(|cue| {
+            CuePoint {
+                id: cue.indx,
+                position: cue.sample_offset,
+                name: from_c_chars(&cue.name)
+            }
+        }).collect()
+    }
+
+    /**
+     * Set the cue points of the file.
+     *
+     * # Argument
+     * * cues - The cue points to set, 100 max
+     *
+     * Return true if the format of the file can store them, false otherwise.
+     */
+    pub fn set_cues(&mut self, cues: &[CuePoint]) -> bool {
+        let mut c_cues: Box<ffi::SF_CUES> = box unsafe { mem::zeroed() };
+        let count = cmp::min(cues.len(), ffi::SF_MAX_CUES);
+
+        c_cues.cue_count = count as u32;
+        for (c_cue, cue) in c_cues.cue_points.iter_mut().zip(cues.slice_to(count).iter()) {
+            c_cue.indx = cue.id;
+            c_cue.position = cue.position;
+            // 'data' chunk
+            c_cue.fcc_chunk = 0x61746164;
+            c_cue.sample_offset = cue.position;
+            to_c_chars(cue.name.as_slice(), &mut c_cue.name);
+        }
+
+        self.command(ffi::SFC_SET_CUE, &mut *c_cues)
+    }
+
+    /**
+     * Get the instrument informations of the file.
+     *
+     * Return Some(Instrument) if the file contains an instrument chunk, None
+     * otherwise.
+     */
+    pub fn get_instrument(&self) -> Option<Instrument> {
+        let mut instrument: ffi::SF_INSTRUMENT = unsafe { mem::zeroed() };
+        if !self.command(ffi::SFC_GET_INSTRUMENT, &mut instrument) {
+            return None;
         }
+        let count = cmp::min(instrument.loop_count as uint, ffi::SF_MAX_LOOPS);
+
+        Some(Instrument {
+            gain: instrument.gain,
+            base_note: instrument.basenote,
+            detune: instrument.detune,
+            velocity_low: instrument.velocity_lo,
+            velocity_high: instrument.velocity_hi,
+            key_low: instrument.key_lo,
+            key_high: instrument.key_hi,
+            loops: instrument.loops.slice_to(count).iter().map(|l| {
+                Loop {
+                    mode: loop_mode_from_c(l.mode),
+                    start: l.start,
+                    end: l.end,
+                    count: l.count
+                }
+            }).collect()
+        })
+    }
+
+    /**
+     * Set the instrument informations of the file.
+     *
+     * # Argument
+     * * instrument - The informations to set, 16 loops max
+     *
+     * Return true if the format of the file can store them, false otherwise.
+     */
+    pub fn set_instrument(&mut self, instrument: &Instrument) -> bool {
+        let mut c_instrument: ffi::SF_INSTRUMENT = unsafe { mem::zeroed() };
+        let count = cmp::min(instrument.loops.len(), ffi::SF_MAX_LOOPS);
+
+        c_instrument.gain = instrument.gain;
+        c_instrument.basenote = instrument.base_note;
+        c_instrument.detune = instrument.detune;
+        c_instrument.velocity_lo = instrument.velocity_low;
+        c_instrument.velocity_hi = instrument.velocity_high;
+        c_instrument.key_lo = instrument.key_low;
+        c_instrument.key_hi = instrument.key_high;
+        c_instrument.loop_count = count as i32;
+        for (c_loop, l) in c_instrument.loops.iter_mut().zip(instrument.loops.slice_to(count).iter()) {
+            c_loop.mode = loop_mode_to_c(&l.mode);
+            c_loop.start = l.start;
+            c_loop.end = l.end;
+            c_loop.count = l.count;
+        }
+
+        self.command(ffi::SFC_SET_INSTRUMENT, &mut c_instrument)
     }
 
     /**
@@ -685,9 +963,16 @@ impl SndFile {
      * Return the last error as a variant of the enum Error.
      */
     pub fn error(&self) -> Error {
-        unsafe {
-            ffi::sf_error(self.handle)
-        }
+        to_error(unsafe { ffi::sf_error(self.handle) })
+    }
+
+    /**
+     * Get the error of the last file which can't be opened
+     *
+     * Return the error as a variant of the enum Error.
+     */
+    pub fn open_error() -> Error {
+        to_error(unsafe { ffi::sf_error(ptr::null_mut()) })
     }
 
     /**
@@ -714,3 +999,86 @@ impl SndFile {
 
 }
 
+/// Convert an error code of libsndfile, SystemError when the code is unknown.
+fn to_error(code: ffi::SF_ERR) -> Error {
+    FromPrimitive::from_i32(code).unwrap_or(SystemError)
+}
+
+impl Drop for SndFile {
+    /// Close the file if it is still open.
+    fn drop(&mut self) -> () {
+        self.close();
+    }
+}
+
+/// Convert a fixed size C string to a String.
+fn from_c_chars(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter()
+                              .take_while(|&&c| c != 0)
+                              .map(|&c| c as u8)
+                              .collect();
+    String::from_utf8_lossy(bytes.as_slice()).into_string()
+}
+
+/// Copy a string in a fixed size C string, return the count of copied bytes.
+fn to_c_chars(string: &str, chars: &mut [c_char]) -> uint {
+    let len = cmp::min(string.len(), chars.len());
+    for (c, &b) in chars.iter_mut().zip(string.as_bytes().slice_to(len).iter()) {
+        *c = b as c_char;
+    }
+    len
+}
+
+fn loop_mode_from_c(mode: i32) -> LoopMode {
+    match mode {
+        ffi::SF_LOOP_FORWARD     => LoopForward,
+        ffi::SF_LOOP_BACKWARD    => LoopBackward,
+        ffi::SF_LOOP_ALTERNATING => LoopAlternating,
+        _                        => LoopNone
+    }
+}
+
+fn loop_mode_to_c(mode: &LoopMode) -> i32 {
+    match *mode {
+        LoopNone        => ffi::SF_LOOP_NONE,
+        LoopForward     => ffi::SF_LOOP_FORWARD,
+        LoopBackward    => ffi::SF_LOOP_BACKWARD,
+        LoopAlternating => ffi::SF_LOOP_ALTERNATING
+    }
+}
+
+/// Convert a C string to a String, empty if the pointer is null.
+fn from_c_string(string: *mut c_char) -> String {
+    if string.is_null() {
+        "".to_string()
+    } else {
+        unsafe {
+            CString::new(string as *const c_char, false).as_str().unwrap_or("").to_string()
+        }
+    }
+}
+
+fn speaker_from_c(channel: i32) -> Speaker {
+    match channel {
+        ffi::SF_CHANNEL_MAP_MONO                  => SpeakerMono,
+        ffi::SF_CHANNEL_MAP_LEFT
+        | ffi::SF_CHANNEL_MAP_FRONT_LEFT
+        | ffi::SF_CHANNEL_MAP_FRONT_LEFT_OF_CENTER => SpeakerLeft,
+        ffi::SF_CHANNEL_MAP_RIGHT
+        | ffi::SF_CHANNEL_MAP_FRONT_RIGHT
+        | ffi::SF_CHANNEL_MAP_FRONT_RIGHT_OF_CENTER => SpeakerRight,
+        ffi::SF_CHANNEL_MAP_CENTER
+        | ffi::SF_CHANNEL_MAP_FRONT_CENTER         => SpeakerCenter,
+        ffi::SF_CHANNEL_MAP_LFE                   => SpeakerLfe,
+        ffi::SF_CHANNEL_MAP_REAR_LEFT             => SpeakerRearLeft,
+        ffi::SF_CHANNEL_MAP_REAR_RIGHT            => SpeakerRearRight,
+        ffi::SF_CHANNEL_MAP_REAR_CENTER           => SpeakerRearCenter,
+        ffi::SF_CHANNEL_MAP_SIDE_LEFT             => SpeakerSideLeft,
+        ffi::SF_CHANNEL_MAP_SIDE_RIGHT            => SpeakerSideRight,
+        ffi::SF_CHANNEL_MAP_AMBISONIC_B_W         => AmbisonicW,
+        ffi::SF_CHANNEL_MAP_AMBISONIC_B_X         => AmbisonicX,
+        ffi::SF_CHANNEL_MAP_AMBISONIC_B_Y         => AmbisonicY,
+        ffi::SF_CHANNEL_MAP_AMBISONIC_B_Z         => AmbisonicZ,
+        _                                         => SpeakerOther
+    }
+}
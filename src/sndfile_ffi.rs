@@ -119,8 +119,114 @@ pub const SF_FORMAT_SUBMASK : FORMAT_TYPE      = 0x0000FFFF;
 pub const SF_FORMAT_TYPEMASK : FORMAT_TYPE     = 0x0FFF0000;
 pub const SF_FORMAT_ENDMASK : FORMAT_TYPE      = 0x30000000;
 
+pub type SF_COMMAND = i32;
+pub const SFC_GET_FORMAT_MAJOR_COUNT : SF_COMMAND   = 0x1030;
+pub const SFC_GET_FORMAT_MAJOR : SF_COMMAND         = 0x1031;
+pub const SFC_GET_FORMAT_SUBTYPE_COUNT : SF_COMMAND = 0x1032;
+pub const SFC_GET_FORMAT_SUBTYPE : SF_COMMAND       = 0x1033;
+pub const SFC_GET_CUE_COUNT : SF_COMMAND       = 0x10CD;
+pub const SFC_GET_CUE : SF_COMMAND             = 0x10CE;
+pub const SFC_SET_CUE : SF_COMMAND             = 0x10CF;
+pub const SFC_GET_INSTRUMENT : SF_COMMAND      = 0x10D0;
+pub const SFC_SET_INSTRUMENT : SF_COMMAND      = 0x10D1;
+pub const SFC_GET_BROADCAST_INFO : SF_COMMAND  = 0x10F0;
+pub const SFC_SET_BROADCAST_INFO : SF_COMMAND  = 0x10F1;
+pub const SFC_GET_CHANNEL_MAP_INFO : SF_COMMAND = 0x1100;
+
+pub type SF_CHANNEL = i32;
+pub const SF_CHANNEL_MAP_INVALID : SF_CHANNEL               = 0;
+pub const SF_CHANNEL_MAP_MONO : SF_CHANNEL                  = 1;
+pub const SF_CHANNEL_MAP_LEFT : SF_CHANNEL                  = 2;
+pub const SF_CHANNEL_MAP_RIGHT : SF_CHANNEL                 = 3;
+pub const SF_CHANNEL_MAP_CENTER : SF_CHANNEL                = 4;
+pub const SF_CHANNEL_MAP_FRONT_LEFT : SF_CHANNEL            = 5;
+pub const SF_CHANNEL_MAP_FRONT_RIGHT : SF_CHANNEL           = 6;
+pub const SF_CHANNEL_MAP_FRONT_CENTER : SF_CHANNEL          = 7;
+pub const SF_CHANNEL_MAP_REAR_CENTER : SF_CHANNEL           = 8;
+pub const SF_CHANNEL_MAP_REAR_LEFT : SF_CHANNEL             = 9;
+pub const SF_CHANNEL_MAP_REAR_RIGHT : SF_CHANNEL            = 10;
+pub const SF_CHANNEL_MAP_LFE : SF_CHANNEL                   = 11;
+pub const SF_CHANNEL_MAP_FRONT_LEFT_OF_CENTER : SF_CHANNEL  = 12;
+pub const SF_CHANNEL_MAP_FRONT_RIGHT_OF_CENTER : SF_CHANNEL = 13;
+pub const SF_CHANNEL_MAP_SIDE_LEFT : SF_CHANNEL             = 14;
+pub const SF_CHANNEL_MAP_SIDE_RIGHT : SF_CHANNEL            = 15;
+pub const SF_CHANNEL_MAP_TOP_CENTER : SF_CHANNEL            = 16;
+pub const SF_CHANNEL_MAP_TOP_FRONT_LEFT : SF_CHANNEL        = 17;
+pub const SF_CHANNEL_MAP_TOP_FRONT_RIGHT : SF_CHANNEL       = 18;
+pub const SF_CHANNEL_MAP_TOP_FRONT_CENTER : SF_CHANNEL      = 19;
+pub const SF_CHANNEL_MAP_TOP_REAR_LEFT : SF_CHANNEL         = 20;
+pub const SF_CHANNEL_MAP_TOP_REAR_RIGHT : SF_CHANNEL        = 21;
+pub const SF_CHANNEL_MAP_TOP_REAR_CENTER : SF_CHANNEL       = 22;
+pub const SF_CHANNEL_MAP_AMBISONIC_B_W : SF_CHANNEL         = 23;
+pub const SF_CHANNEL_MAP_AMBISONIC_B_X : SF_CHANNEL         = 24;
+pub const SF_CHANNEL_MAP_AMBISONIC_B_Y : SF_CHANNEL         = 25;
+pub const SF_CHANNEL_MAP_AMBISONIC_B_Z : SF_CHANNEL         = 26;
+
+pub type SF_LOOP = i32;
+pub const SF_LOOP_NONE : SF_LOOP           = 800;
+pub const SF_LOOP_FORWARD : SF_LOOP        = 801;
+pub const SF_LOOP_BACKWARD : SF_LOOP       = 802;
+pub const SF_LOOP_ALTERNATING : SF_LOOP    = 803;
+
+pub const SF_MAX_CUES : uint = 100;
+pub const SF_MAX_LOOPS : uint = 16;
+
 pub type SNDFILE = c_void;
 
+#[repr(C)]
+pub struct SF_BROADCAST_INFO {
+    pub description : [c_char, ..256],
+    pub originator : [c_char, ..32],
+    pub originator_reference : [c_char, ..32],
+    pub origination_date : [c_char, ..10],
+    pub origination_time : [c_char, ..8],
+    pub time_reference_low : u32,
+    pub time_reference_high : u32,
+    pub version : i16,
+    pub umid : [c_char, ..64],
+    pub reserved : [c_char, ..190],
+    pub coding_history_size : u32,
+    pub coding_history : [c_char, ..256]
+}
+
+#[repr(C)]
+pub struct SF_CUE_POINT {
+    pub indx : i32,
+    pub position : u32,
+    pub fcc_chunk : i32,
+    pub chunk_start : i32,
+    pub block_start : i32,
+    pub sample_offset : u32,
+    pub name : [c_char, ..256]
+}
+
+#[repr(C)]
+pub struct SF_CUES {
+    pub cue_count : u32,
+    pub cue_points : [SF_CUE_POINT, ..100]
+}
+
+#[repr(C)]
+pub struct SF_LOOP_INFO {
+    pub mode : SF_LOOP,
+    pub start : u32,
+    pub end : u32,
+    pub count : u32
+}
+
+#[repr(C)]
+pub struct SF_INSTRUMENT {
+    pub gain : i32,
+    pub basenote : c_char,
+    pub detune : c_char,
+    pub velocity_lo : c_char,
+    pub velocity_hi : c_char,
+    pub key_lo : c_char,
+    pub key_hi : c_char,
+    pub loop_count : i32,
+    pub loops : [SF_LOOP_INFO, ..16]
+}
+
 #[repr(C)]
 pub struct FormatInfo {
     pub format : i32,
@@ -134,9 +240,9 @@ extern "C" {
     pub fn sf_format_check(info : *mut SndInfo) -> SF_BOOL;
 
     pub fn sf_seek(sndfile : *mut SNDFILE, frames : i64, whence : i32) -> i64;
-    pub fn sf_command(sndfile : *mut SNDFILE, cmd : i32, data : *mut c_void, datasize : i32) -> Error;
+    pub fn sf_command(sndfile : *mut SNDFILE, cmd : i32, data : *mut c_void, datasize : i32) -> i32;
 
-    pub fn sf_error(sndfile : *mut SNDFILE) -> Error;
+    pub fn sf_error(sndfile : *mut SNDFILE) -> SF_ERR;
     pub fn sf_strerror(sndfile : *mut SNDFILE) -> *mut c_char;
     pub fn sf_error_number(errnum : i32) -> *mut c_char;
 
@@ -0,0 +1,1122 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! A mixer written in Rust, usable instead of OpenAL.
+
+#![allow(raw_pointer_deriving)]
+
+use std::{ptr, mem, cmp};
+use std::num::Float;
+use std::slice::raw;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::f32;
+use std::f32::consts::PI;
+use libc::c_void;
+
+use openal::ffi;
+use backend;
+use backend::AudioBackend;
+
+/// The default capacity of the ring buffer, in frames.
+static DEFAULT_RING_FRAMES: uint = 8192;
+
+/**
+ * Mix the Sounds and the Musics without OpenAL.
+ *
+ * Once installed, the SoftwareMixer replaces OpenAL for the whole process:
+ * the sources are mixed in stereo with their volume, pitch, position and
+ * distance attenuation, like with the inverse distance clamped model of
+ * OpenAL. The mono sounds are panned between the speakers, the stereo
+ * sounds are played as is. The direction, the velocity and the cones of
+ * the sources are ignored, and the mixer can't capture audio.
+ *
+ * Nothing is played on a sound card: the sources only advance when the
+ * mix is pulled with `mix`, or rendered in the ring buffer with `render`
+ * then consumed with `read`. The SoftwareMixer is a shared handle, cloning
+ * it gives another handle on the same mixer.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use ears::{SoftwareMixer, Sound, AudioController};
+ *
+ * fn main() -> () {
+ *     let mixer = SoftwareMixer::new(44100);
+ *     if !mixer.install() {
+ *         panic!("ears is already used");
+ *     }
+ *
+ *     let mut snd = Sound::new("path/to/my/sound.wav").unwrap();
+ *     snd.play();
+ *
+ *     // Render one second of audio, then consume it
+ *     let mut samples = Vec::from_elem(1024 * 2, 0f32);
+ *     for _ in range(0u, 44100 / 1024) {
+ *         mixer.render(1024);
+ *         mixer.read(samples.as_mut_slice());
+ *     }
+ * }
+ * ```
+ */
+#[deriving(Clone)]
+pub struct SoftwareMixer {
+    state: Arc<Mutex<MixerState>>
+}
+
+/// The samples of a buffer, converted to float.
+struct MixBuffer {
+    samples: Vec<f32>,
+    channels: uint,
+    frequency: i32,
+    bits: i32,
+    size: i32
+}
+
+impl MixBuffer {
+    fn get_frames(&self) -> uint {
+        if self.channels == 0 { 0 } else { self.samples.len() / self.channels }
+    }
+
+    /// Get the sample of a channel at a fractional frame, 0 after the end.
+    fn sample_at(&self, frame: f64, channel: uint, looping: bool) -> f32 {
+        let frames = self.get_frames();
+        let index = frame as uint;
+        if index >= frames {
+            return 0.;
+        }
+        let mut next = index + 1;
+        if next >= frames {
+            next = if looping { 0 } else { index };
+        }
+        let t = (frame - index as f64) as f32;
+        let a = self.samples.as_slice()[index * self.channels + channel];
+        let b = self.samples.as_slice()[next * self.channels + channel];
+        a + (b - a) * t
+    }
+}
+
+/// A source, with its parameters and its queue of buffers.
+struct Voice {
+    state: i32,
+    gain: f32,
+    min_gain: f32,
+    max_gain: f32,
+    pitch: f32,
+    position: [f32, ..3],
+    direction: [f32, ..3],
+    velocity: [f32, ..3],
+    relative: bool,
+    looping: bool,
+    reference_distance: f32,
+    rolloff_factor: f32,
+    max_distance: f32,
+    queue: Vec<u32>,
+    current: uint,
+    cursor: f64,
+    ints: HashMap<i32, i32>,
+    floats: HashMap<i32, f32>
+}
+
+impl Voice {
+    fn new() -> Voice {
+        Voice {
+            state: ffi::AL_INITIAL,
+            gain: 1.,
+            min_gain: 0.,
+            max_gain: 1.,
+            pitch: 1.,
+            position: [0., ..3],
+            direction: [0., ..3],
+            velocity: [0., ..3],
+            relative: false,
+            looping: false,
+            reference_distance: 1.,
+            rolloff_factor: 1.,
+            max_distance: f32::MAX_VALUE,
+            queue: Vec::new(),
+            current: 0,
+            cursor: 0.,
+            ints: HashMap::new(),
+            floats: HashMap::new()
+        }
+    }
+
+    fn rewind(&mut self) -> () {
+        self.current = 0;
+        self.cursor = 0.;
+    }
+}
+
+struct Listener {
+    gain: f32,
+    position: [f32, ..3],
+    velocity: [f32, ..3],
+    orientation: [f32, ..6]
+}
+
+struct MixerState {
+    samplerate: i32,
+    buffers: HashMap<u32, MixBuffer>,
+    voices: HashMap<u32, Voice>,
+    next_name: u32,
+    listener: Listener,
+    ring: RingBuffer,
+    /// The count of frames mixed since the creation of the mixer
+    frames_mixed: u64,
+    error: i32
+}
+
+/// A fixed size FIFO of interleaved stereo samples.
+struct RingBuffer {
+    samples: Vec<f32>,
+    start: uint,
+    len: uint
+}
+
+impl RingBuffer {
+    fn new(capacity: uint) -> RingBuffer {
+        RingBuffer {
+            samples: Vec::from_elem(capacity, 0f32),
+            start: 0,
+            len: 0
+        }
+    }
+
+    fn free(&self) -> uint {
+        self.samples.len() - self.len
+    }
+
+    fn push(&mut self, input: &[f32]) -> uint {
+        let capacity = self.samples.len();
+        let count = cmp::min(input.len(), self.free());
+        for i in range(0, count) {
+            let index = (self.start + self.len + i) % capacity;
+            self.samples.as_mut_slice()[index] = input[i];
+        }
+        self.len += count;
+        count
+    }
+
+    fn pop(&mut self, output: &mut [f32]) -> uint {
+        let capacity = self.samples.len();
+        let count = cmp::min(output.len(), self.len);
+        for i in range(0, count) {
+            output[i] = self.samples.as_slice()[(self.start + i) % capacity];
+        }
+        self.start = (self.start + count) % capacity;
+        self.len -= count;
+        count
+    }
+}
+
+impl SoftwareMixer {
+    /**
+     * Create a new SoftwareMixer.
+     *
+     * # Argument
+     * * `samplerate` - The sample rate of the mix
+     *
+     * # Return
+     * A new SoftwareMixer producing interleaved stereo samples.
+     */
+    pub fn new(samplerate: i32) -> SoftwareMixer {
+        SoftwareMixer {
+            state: Arc::new(Mutex::new(MixerState {
+                samplerate: samplerate,
+                buffers: HashMap::new(),
+                voices: HashMap::new(),
+                next_name: 1,
+                listener: Listener {
+                    gain: 1.,
+                    position: [0., ..3],
+                    velocity: [0., ..3],
+                    orientation: [0., 0., -1., 0., 1., 0.]
+                },
+                ring: RingBuffer::new(DEFAULT_RING_FRAMES * 2),
+                frames_mixed: 0,
+                error: ffi::AL_NO_ERROR
+            }))
+        }
+    }
+
+    /**
+     * Use the mixer instead of OpenAL.
+     *
+     * Must be called before any other use of ears in the process.
+     *
+     * # Return
+     * true if the mixer is installed, false if ears is already used.
+     */
+    pub fn install(&self) -> bool {
+        let installed = backend::set_backend(box self.clone() as Box<AudioBackend + Send + Sync>);
+        if !installed {
+            println!("Error: the mixer must be installed before any other use of ears.");
+        }
+        installed
+    }
+
+    /// Get the sample rate of the mix.
+    pub fn get_samplerate(&self) -> i32 {
+        self.state.lock().samplerate
+    }
+
+    /**
+     * Mix the playing sources.
+     *
+     * Use it to feed the audio callback of another engine.
+     *
+     * # Argument
+     * * `output` - The interleaved stereo samples to fill
+     */
+    pub fn mix(&self, output: &mut [f32]) -> () {
+        let mut state = self.state.lock();
+        state.mix(output);
+    }
+
+    /**
+     * Mix the playing sources in the ring buffer.
+     *
+     * # Argument
+     * * `frames` - The count of frames to mix, limited by the free space of
+     * the ring buffer
+     *
+     * # Return
+     * The count of mixed frames.
+     */
+    pub fn render(&self, frames: uint) -> uint {
+        let mut state = self.state.lock();
+        let frames = cmp::min(frames, state.ring.free() / 2);
+        let mut mixed = Vec::from_elem(frames * 2, 0f32);
+        state.mix(mixed.as_mut_slice());
+        state.ring.push(mixed.as_slice()) / 2
+    }
+
+    /**
+     * Consume samples of the ring buffer.
+     *
+     * # Argument
+     * * `output` - The interleaved stereo samples to fill
+     *
+     * # Return
+     * The count of samples written in `output`.
+     */
+    pub fn read(&self, output: &mut [f32]) -> uint {
+        self.state.lock().ring.pop(output)
+    }
+
+    /// Get the count of samples waiting in the ring buffer.
+    pub fn get_available(&self) -> uint {
+        self.state.lock().ring.len
+    }
+
+    /**
+     * Set the capacity of the ring buffer.
+     *
+     * The samples waiting in the ring buffer are dropped.
+     *
+     * # Argument
+     * * `frames` - The capacity in frames
+     */
+    pub fn set_ring_capacity(&self, frames: uint) -> () {
+        self.state.lock().ring = RingBuffer::new(frames * 2);
+    }
+
+    /// Run `f` on a source, or record AL_INVALID_NAME.
+    fn with_voice<T>(&self, source: u32, default: T, f: |&mut Voice| -> T) -> T {
+        let mut state = self.state.lock();
+        let result = match state.voices.find_mut(&source) {
+            Some(voice) => return f(voice),
+            None        => default
+        };
+        state.error = ffi::AL_INVALID_NAME;
+        result
+    }
+}
+
+impl MixerState {
+    fn mix(&mut self, output: &mut [f32]) -> () {
+        for sample in output.iter_mut() {
+            *sample = 0.;
+        }
+        let MixerState { ref mut voices, ref buffers, ref listener, samplerate, .. } = *self;
+        for voice in voices.values_mut() {
+            if voice.state == ffi::AL_PLAYING {
+                mix_voice(voice, buffers, listener, samplerate, output);
+            }
+        }
+        self.frames_mixed += (output.len() / 2) as u64;
+    }
+
+    /// Get the frame offset of a source from the start of its queue.
+    fn get_offset(&self, voice: &Voice) -> f64 {
+        let mut offset = voice.cursor;
+        for name in voice.queue.iter().take(voice.current) {
+            match self.buffers.find(name) {
+                Some(buffer) => offset += buffer.get_frames() as f64,
+                None         => {}
+            }
+        }
+        offset
+    }
+
+    /// Move a source to a frame offset from the start of its queue.
+    fn set_offset(&self, voice: &mut Voice, offset: f64) -> () {
+        let mut offset = offset.max(0.);
+        voice.rewind();
+        for name in voice.queue.iter() {
+            let frames = match self.buffers.find(name) {
+                Some(buffer) => buffer.get_frames() as f64,
+                None         => 0.
+            };
+            if offset < frames {
+                break;
+            }
+            offset -= frames;
+            voice.current += 1;
+        }
+        voice.cursor = offset;
+    }
+
+    fn get_frequency(&self, voice: &Voice) -> f64 {
+        match voice.queue.as_slice().get(voice.current).or(voice.queue.last()) {
+            Some(name) => match self.buffers.find(name) {
+                Some(buffer) => buffer.frequency as f64,
+                None         => 1.
+            },
+            None       => 1.
+        }
+    }
+}
+
+/// Add a source to the mix.
+fn mix_voice(voice: &mut Voice,
+             buffers: &HashMap<u32, MixBuffer>,
+             listener: &Listener,
+             samplerate: i32,
+             output: &mut [f32]) -> () {
+    let mut frame = 0;
+    let frames = output.len() / 2;
+    while frame < frames {
+        let buffer = match voice.queue.as_slice().get(voice.current) {
+            Some(name) => match buffers.find(name) {
+                Some(buffer) => buffer,
+                None         => { voice.state = ffi::AL_STOPPED; return; }
+            },
+            None       => { voice.state = ffi::AL_STOPPED; return; }
+        };
+        let buffer_frames = buffer.get_frames() as f64;
+        if voice.cursor >= buffer_frames {
+            voice.cursor -= buffer_frames;
+            voice.current += 1;
+            if voice.current >= voice.queue.len() {
+                if voice.looping {
+                    voice.current = 0;
+                } else {
+                    voice.cursor = 0.;
+                    voice.state = ffi::AL_STOPPED;
+                    return;
+                }
+            }
+            continue;
+        }
+
+        let (left, right) = get_gains(voice, buffer.channels, listener);
+        let step = voice.pitch.max(0.) as f64 * buffer.frequency as f64 / samplerate as f64;
+        let looping = voice.looping && voice.queue.len() == 1;
+        while frame < frames && voice.cursor < buffer_frames {
+            let (l, r) = if buffer.channels == 1 {
+                let sample = buffer.sample_at(voice.cursor, 0, looping);
+                (sample * left, sample * right)
+            } else {
+                (buffer.sample_at(voice.cursor, 0, looping) * left,
+                 buffer.sample_at(voice.cursor, 1, looping) * right)
+            };
+            output[frame * 2] += l;
+            output[frame * 2 + 1] += r;
+            voice.cursor += step;
+            frame += 1;
+        }
+    }
+}
+
+/**
+ * Get the gains of the left and right speakers for a source.
+ *
+ * Only the mono sources are spatialized, with the inverse distance clamped
+ * model and a constant power panning.
+ */
+fn get_gains(voice: &Voice, channels: uint, listener: &Listener) -> (f32, f32) {
+    if channels != 1 {
+        let gain = voice.gain.max(voice.min_gain).min(voice.max_gain) * listener.gain;
+        return (gain, gain);
+    }
+
+    let relative = if voice.relative {
+        voice.position
+    } else {
+        [voice.position[0] - listener.position[0],
+         voice.position[1] - listener.position[1],
+         voice.position[2] - listener.position[2]]
+    };
+    let distance = length(&relative);
+
+    let clamped = distance.max(voice.reference_distance).min(voice.max_distance);
+    let denominator = voice.reference_distance
+        + voice.rolloff_factor * (clamped - voice.reference_distance);
+    let attenuation = if denominator > 0. { voice.reference_distance / denominator } else { 1. };
+    let gain = (voice.gain * attenuation).max(voice.min_gain).min(voice.max_gain) * listener.gain;
+
+    // The position of a relative source is already in the space of the
+    // listener, otherwise the right vector is the cross product of at and up
+    let o = &listener.orientation;
+    let right = if voice.relative {
+        [1., 0., 0.]
+    } else {
+        [o[1] * o[5] - o[2] * o[4],
+         o[2] * o[3] - o[0] * o[5],
+         o[0] * o[4] - o[1] * o[3]]
+    };
+    let right_length = length(&right);
+    let pan = if distance > 0. && right_length > 0. {
+        let x = (relative[0] * right[0] + relative[1] * right[1] + relative[2] * right[2])
+            / right_length;
+        (x / distance).max(-1.).min(1.)
+    } else {
+        0.
+    };
+    let angle = (pan + 1.) * PI / 4.;
+    (gain * angle.cos(), gain * angle.sin())
+}
+
+fn length(v: &[f32, ..3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+/// Get the count of channels and the bits per sample of a format.
+fn get_format_layout(format: i32) -> Option<(uint, i32)> {
+    match format {
+        ffi::AL_FORMAT_MONO8            => Some((1, 8)),
+        ffi::AL_FORMAT_MONO16           => Some((1, 16)),
+        ffi::AL_FORMAT_MONO_FLOAT32     => Some((1, 32)),
+        ffi::AL_FORMAT_STEREO8          => Some((2, 8)),
+        ffi::AL_FORMAT_STEREO16         => Some((2, 16)),
+        ffi::AL_FORMAT_STEREO_FLOAT32   => Some((2, 32)),
+        _                               => None
+    }
+}
+
+/// Convert the samples of a buffer to float.
+unsafe fn read_samples(data: *mut c_void, size: uint, bits: i32) -> Vec<f32> {
+    match bits {
+        8  => raw::buf_as_slice(data as *const u8, size, |samples| {
+            samples.iter().map(|s| (*s as f32 - 128.) / 128.).collect()
+        }),
+        16 => raw::buf_as_slice(data as *const i16, size / 2, |samples| {
+            samples.iter().map(|s| *s as f32 / 32768.).collect()
+        }),
+        _  => raw::buf_as_slice(data as *const f32, size / 4, |samples| {
+            samples.to_vec()
+        })
+    }
+}
+
+impl AudioBackend for SoftwareMixer {
+    fn get_name(&self) -> &'static str {
+        "software mixer"
+    }
+
+    fn needs_context(&self) -> bool {
+        false
+    }
+
+    fn is_extension_present(&self, extension: &str) -> bool {
+        extension == "AL_EXT_FLOAT32"
+    }
+
+    fn get_clock(&self) -> Option<i64> {
+        let state = self.state.lock();
+        let samplerate = state.samplerate as u64;
+        let seconds = state.frames_mixed / samplerate;
+        let frames = state.frames_mixed % samplerate;
+        Some((seconds * 1000000000 + frames * 1000000000 / samplerate) as i64)
+    }
+
+    fn gen_buffers(&self, n: i32, buffers: *mut u32) -> () {
+        let mut state = self.state.lock();
+        for i in range(0, n as uint) {
+            let name = state.next_name;
+            state.next_name += 1;
+            state.buffers.insert(name, MixBuffer {
+                samples: Vec::new(),
+                channels: 1,
+                frequency: 44100,
+                bits: 16,
+                size: 0
+            });
+            unsafe { *buffers.offset(i as int) = name; }
+        }
+    }
+
+    fn delete_buffers(&self, n: i32, buffers: *mut u32) -> () {
+        let mut state = self.state.lock();
+        for i in range(0, n as uint) {
+            let name = unsafe { *buffers.offset(i as int) };
+            state.buffers.remove(&name);
+        }
+    }
+
+    fn buffer_data(&self, buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> () {
+        let mut state = self.state.lock();
+        let (channels, bits) = match get_format_layout(format) {
+            Some(layout) => layout,
+            None         => { state.error = ffi::AL_INVALID_ENUM; return; }
+        };
+        let samples = if data.is_null() || size <= 0 {
+            Vec::new()
+        } else {
+            unsafe { read_samples(data, size as uint, bits) }
+        };
+        match state.buffers.find_mut(&buffer) {
+            Some(mix_buffer) => {
+                *mix_buffer = MixBuffer {
+                    samples: samples,
+                    channels: channels,
+                    frequency: freq,
+                    bits: bits,
+                    size: size
+                };
+                return;
+            },
+            None             => {}
+        }
+        state.error = ffi::AL_INVALID_NAME;
+    }
+
+    fn get_bufferi(&self, buffer: u32, param: i32, value: *mut i32) -> () {
+        let mut state = self.state.lock();
+        let result = state.buffers.find(&buffer).map(|buffer| {
+            match param {
+                ffi::AL_FREQUENCY => Some(buffer.frequency),
+                ffi::AL_BITS      => Some(buffer.bits),
+                ffi::AL_CHANNELS  => Some(buffer.channels as i32),
+                ffi::AL_SIZE      => Some(buffer.size),
+                _                 => None
+            }
+        });
+        match result {
+            Some(Some(result)) => unsafe { *value = result; },
+            Some(None)         => state.error = ffi::AL_INVALID_ENUM,
+            None               => state.error = ffi::AL_INVALID_NAME
+        }
+    }
+
+    fn gen_sources(&self, n: i32, sources: *mut u32) -> () {
+        let mut state = self.state.lock();
+        for i in range(0, n as uint) {
+            let name = state.next_name;
+            state.next_name += 1;
+            state.voices.insert(name, Voice::new());
+            unsafe { *sources.offset(i as int) = name; }
+        }
+    }
+
+    fn delete_sources(&self, n: i32, sources: *mut u32) -> () {
+        let mut state = self.state.lock();
+        for i in range(0, n as uint) {
+            let name = unsafe { *sources.offset(i as int) };
+            state.voices.remove(&name);
+        }
+    }
+
+    fn sourcei(&self, source: u32, param: i32, value: i32) -> () {
+        let mut state = self.state.lock();
+        let popped = state.voices.pop(&source);
+        let mut voice = match popped {
+            Some(voice) => voice,
+            None        => { state.error = ffi::AL_INVALID_NAME; return; }
+        };
+        match param {
+            ffi::AL_BUFFER           => {
+                voice.queue = if value == 0 { Vec::new() } else { vec![value as u32] };
+                voice.rewind();
+            },
+            ffi::AL_LOOPING          => voice.looping = value != 0,
+            ffi::AL_SOURCE_RELATIVE  => voice.relative = value != 0,
+            ffi::AL_SAMPLE_OFFSET    => state.set_offset(&mut voice, value as f64),
+            _                        => { voice.ints.insert(param, value); }
+        }
+        state.voices.insert(source, voice);
+    }
+
+    fn sourcef(&self, source: u32, param: i32, value: f32) -> () {
+        let mut state = self.state.lock();
+        let popped = state.voices.pop(&source);
+        let mut voice = match popped {
+            Some(voice) => voice,
+            None        => { state.error = ffi::AL_INVALID_NAME; return; }
+        };
+        match param {
+            ffi::AL_GAIN               => voice.gain = value,
+            ffi::AL_MIN_GAIN           => voice.min_gain = value,
+            ffi::AL_MAX_GAIN           => voice.max_gain = value,
+            ffi::AL_PITCH              => voice.pitch = value,
+            ffi::AL_REFERENCE_DISTANCE => voice.reference_distance = value,
+            ffi::AL_ROLLOFF_FACTOR     => voice.rolloff_factor = value,
+            ffi::AL_MAX_DISTANCE       => voice.max_distance = value,
+            ffi::AL_SEC_OFFSET         => {
+                let frequency = state.get_frequency(&voice);
+                state.set_offset(&mut voice, value as f64 * frequency);
+            },
+            _                          => { voice.floats.insert(param, value); }
+        }
+        state.voices.insert(source, voice);
+    }
+
+    fn sourcefv(&self, source: u32, param: i32, value: *const f32) -> () {
+        let vector = unsafe { [*value, *value.offset(1), *value.offset(2)] };
+        self.with_voice(source, (), |voice| {
+            match param {
+                ffi::AL_POSITION  => voice.position = vector,
+                ffi::AL_DIRECTION => voice.direction = vector,
+                ffi::AL_VELOCITY  => voice.velocity = vector,
+                _                 => {}
+            }
+        });
+    }
+
+    fn get_sourcei(&self, source: u32, param: i32, value: *mut i32) -> () {
+        let mut state = self.state.lock();
+        let result = state.voices.find(&source).map(|voice| {
+            match param {
+                ffi::AL_SOURCE_STATE       => voice.state,
+                ffi::AL_BUFFER             => {
+                    match voice.queue.as_slice().get(voice.current) {
+                        Some(name) => *name as i32,
+                        None       => voice.queue.last().map_or(0, |name| *name as i32)
+                    }
+                },
+                ffi::AL_LOOPING            => voice.looping as i32,
+                ffi::AL_SOURCE_RELATIVE    => voice.relative as i32,
+                ffi::AL_BUFFERS_QUEUED     => voice.queue.len() as i32,
+                ffi::AL_BUFFERS_PROCESSED  => cmp::min(voice.current, voice.queue.len()) as i32,
+                ffi::AL_SAMPLE_OFFSET      => state.get_offset(voice) as i32,
+                _                          => voice.ints.find(&param).map_or(0, |v| *v)
+            }
+        });
+        match result {
+            Some(result) => unsafe { *value = result; },
+            None         => state.error = ffi::AL_INVALID_NAME
+        }
+    }
+
+    fn get_sourcef(&self, source: u32, param: i32, value: *mut f32) -> () {
+        let mut state = self.state.lock();
+        let result = state.voices.find(&source).map(|voice| {
+            match param {
+                ffi::AL_GAIN               => voice.gain,
+                ffi::AL_MIN_GAIN           => voice.min_gain,
+                ffi::AL_MAX_GAIN           => voice.max_gain,
+                ffi::AL_PITCH              => voice.pitch,
+                ffi::AL_REFERENCE_DISTANCE => voice.reference_distance,
+                ffi::AL_ROLLOFF_FACTOR     => voice.rolloff_factor,
+                ffi::AL_MAX_DISTANCE       => voice.max_distance,
+                ffi::AL_SEC_OFFSET         => {
+                    (state.get_offset(voice) / state.get_frequency(voice)) as f32
+                },
+                _                          => voice.floats.find(&param).map_or(0., |v| *v)
+            }
+        });
+        match result {
+            Some(result) => unsafe { *value = result; },
+            None         => state.error = ffi::AL_INVALID_NAME
+        }
+    }
+
+    fn get_sourcefv(&self, source: u32, param: i32, value: *mut f32) -> () {
+        let vector = self.with_voice(source, [0., ..3], |voice| {
+            match param {
+                ffi::AL_POSITION  => voice.position,
+                ffi::AL_DIRECTION => voice.direction,
+                ffi::AL_VELOCITY  => voice.velocity,
+                _                 => [0., ..3]
+            }
+        });
+        unsafe { ptr::copy_nonoverlapping_memory(value, &vector[0], 3); }
+    }
+
+    fn source_play(&self, source: u32) -> () {
+        self.with_voice(source, (), |voice| {
+            if voice.state != ffi::AL_PAUSED {
+                voice.rewind();
+            }
+            voice.state = ffi::AL_PLAYING;
+        });
+    }
+
+    fn source_play_at_time(&self, source: u32, _: i64) -> () {
+        // The mixer doesn't have AL_SOFT_source_start_delay
+        let mut state = self.state.lock();
+        state.error = if state.voices.contains_key(&source) {
+            ffi::AL_INVALID_OPERATION
+        } else {
+            ffi::AL_INVALID_NAME
+        };
+    }
+
+    fn source_pause(&self, source: u32) -> () {
+        self.with_voice(source, (), |voice| {
+            if voice.state == ffi::AL_PLAYING {
+                voice.state = ffi::AL_PAUSED;
+            }
+        });
+    }
+
+    fn source_stop(&self, source: u32) -> () {
+        self.with_voice(source, (), |voice| {
+            if voice.state != ffi::AL_INITIAL {
+                voice.current = voice.queue.len();
+                voice.cursor = 0.;
+                voice.state = ffi::AL_STOPPED;
+            }
+        });
+    }
+
+    fn source_rewind(&self, source: u32) -> () {
+        self.with_voice(source, (), |voice| {
+            voice.rewind();
+            voice.state = ffi::AL_INITIAL;
+        });
+    }
+
+    fn source_queue_buffers(&self, source: u32, nb: i32, buffers: *const u32) -> () {
+        self.with_voice(source, (), |voice| {
+            for i in range(0, nb as uint) {
+                voice.queue.push(unsafe { *buffers.offset(i as int) });
+            }
+        });
+    }
+
+    fn source_unqueue_buffers(&self, source: u32, nb: i32, buffers: *mut u32) -> () {
+        let mut state = self.state.lock();
+        let error = match state.voices.find_mut(&source) {
+            Some(voice) => {
+                let processed = cmp::min(voice.current, voice.queue.len());
+                if nb as uint > processed {
+                    ffi::AL_INVALID_VALUE
+                } else {
+                    for i in range(0, nb as uint) {
+                        unsafe { *buffers.offset(i as int) = voice.queue.remove(0).unwrap(); }
+                    }
+                    voice.current -= nb as uint;
+                    ffi::AL_NO_ERROR
+                }
+            },
+            None        => ffi::AL_INVALID_NAME
+        };
+        if error != ffi::AL_NO_ERROR {
+            state.error = error;
+        }
+    }
+
+    fn listenerf(&self, param: i32, value: f32) -> () {
+        if param == ffi::AL_GAIN {
+            self.state.lock().listener.gain = value;
+        }
+    }
+
+    fn listener3f(&self, param: i32, value1: f32, value2: f32, value3: f32) -> () {
+        let values = [value1, value2, value3];
+        self.listenerfv(param, &values[0]);
+    }
+
+    fn listenerfv(&self, param: i32, values: *const f32) -> () {
+        let mut state = self.state.lock();
+        let listener = &mut state.listener;
+        unsafe {
+            match param {
+                ffi::AL_POSITION    => {
+                    ptr::copy_nonoverlapping_memory(&mut listener.position[0], values, 3)
+                },
+                ffi::AL_VELOCITY    => {
+                    ptr::copy_nonoverlapping_memory(&mut listener.velocity[0], values, 3)
+                },
+                ffi::AL_ORIENTATION => {
+                    ptr::copy_nonoverlapping_memory(&mut listener.orientation[0], values, 6)
+                },
+                _                   => {}
+            }
+        }
+    }
+
+    fn get_listenerf(&self, param: i32, value: *mut f32) -> () {
+        if param == ffi::AL_GAIN {
+            unsafe { *value = self.state.lock().listener.gain; }
+        }
+    }
+
+    fn get_listener3f(&self, param: i32, value1: *mut f32, value2: *mut f32, value3: *mut f32) -> () {
+        let mut values = [0f32, ..6];
+        self.get_listenerfv(param, &mut values[0]);
+        unsafe {
+            *value1 = values[0];
+            *value2 = values[1];
+            *value3 = values[2];
+        }
+    }
+
+    fn get_listenerfv(&self, param: i32, values: *mut f32) -> () {
+        let state = self.state.lock();
+        let listener = &state.listener;
+        unsafe {
+            match param {
+                ffi::AL_POSITION    => {
+                    ptr::copy_nonoverlapping_memory(values, &listener.position[0], 3)
+                },
+                ffi::AL_VELOCITY    => {
+                    ptr::copy_nonoverlapping_memory(values, &listener.velocity[0], 3)
+                },
+                ffi::AL_ORIENTATION => {
+                    ptr::copy_nonoverlapping_memory(values, &listener.orientation[0], 6)
+                },
+                _                   => {}
+            }
+        }
+    }
+
+    fn capture_open(&self, _: i32, _: i32, _: i32) -> *mut ffi::ALCdevice {
+        ptr::null_mut()
+    }
+
+    fn capture_close(&self, _: *mut ffi::ALCdevice) -> () {}
+
+    fn capture_start(&self, _: *mut ffi::ALCdevice) -> () {}
+
+    fn capture_stop(&self, _: *mut ffi::ALCdevice) -> () {}
+
+    fn capture_available(&self, _: *mut ffi::ALCdevice) -> i32 {
+        0
+    }
+
+    fn capture_samples(&self, _: *mut ffi::ALCdevice, _: *mut c_void, _: i32) -> () {}
+
+    fn get_error(&self) -> i32 {
+        mem::replace(&mut self.state.lock().error, ffi::AL_NO_ERROR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::num::Float;
+    use std::time::Duration;
+    use libc::c_void;
+    use openal::ffi;
+    use backend::AudioBackend;
+    use backend;
+    use software_mixer::{SoftwareMixer, RingBuffer};
+    use sound::Sound;
+    use audio_controller::AudioController;
+    use clock;
+
+    /// Create a playing source with a buffer of mono samples.
+    fn play_mono(mixer: &SoftwareMixer, samples: &[i16], frequency: i32) -> u32 {
+        let mut buffer = 0;
+        let mut source = 0;
+        mixer.gen_buffers(1, &mut buffer);
+        mixer.buffer_data(buffer, ffi::AL_FORMAT_MONO16, samples.as_ptr() as *mut c_void,
+                          (samples.len() * 2) as i32, frequency);
+        mixer.gen_sources(1, &mut source);
+        mixer.sourcei(source, ffi::AL_BUFFER, buffer as i32);
+        mixer.source_play(source);
+        source
+    }
+
+    fn get_state(mixer: &SoftwareMixer, source: u32) -> i32 {
+        let mut state = 0;
+        mixer.get_sourcei(source, ffi::AL_SOURCE_STATE, &mut state);
+        state
+    }
+
+    #[test]
+    fn software_mixer_gain_and_center_pan_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        mixer.sourcef(source, ffi::AL_GAIN, 0.5);
+        let mut output = [0f32, ..4];
+        mixer.mix(output.as_mut_slice());
+        assert!((output[0] - 0.25 * 0.5f32.sqrt()).abs() < 0.001);
+        assert!((output[0] - output[1]).abs() < 0.001);
+    }
+
+    #[test]
+    fn software_mixer_pan_right_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        let position = [10f32, 0., 0.];
+        mixer.sourcefv(source, ffi::AL_POSITION, &position[0]);
+        let mut output = [0f32, ..2];
+        mixer.mix(output.as_mut_slice());
+        assert!(output[0].abs() < 0.001);
+        // Inverse distance clamped: 1 / (1 + 1 * (10 - 1))
+        assert!((output[1] - 0.5 * 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn software_mixer_relative_pan_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        // The listener looks at +X, its right is +Z
+        let orientation = [1f32, 0., 0., 0., 1., 0.];
+        mixer.listenerfv(ffi::AL_ORIENTATION, &orientation[0]);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        mixer.sourcei(source, ffi::AL_SOURCE_RELATIVE, 1);
+        let position = [10f32, 0., 0.];
+        mixer.sourcefv(source, ffi::AL_POSITION, &position[0]);
+        let mut output = [0f32, ..2];
+        mixer.mix(output.as_mut_slice());
+        assert!(output[0].abs() < 0.001);
+        assert!((output[1] - 0.5 * 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn software_mixer_max_distance_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        mixer.sourcef(source, ffi::AL_MAX_DISTANCE, 4.);
+        let position = [0f32, 0., -100.];
+        mixer.sourcefv(source, ffi::AL_POSITION, &position[0]);
+        let mut output = [0f32, ..2];
+        mixer.mix(output.as_mut_slice());
+        assert!((output[0] - 0.5 * 0.25 * 0.5f32.sqrt()).abs() < 0.001);
+    }
+
+    #[test]
+    fn software_mixer_pitch_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        mixer.sourcef(source, ffi::AL_PITCH, 2.);
+        let mut output = [0f32, ..10];
+        mixer.mix(output.as_mut_slice());
+        assert_eq!(get_state(&mixer, source), ffi::AL_PLAYING);
+        mixer.mix(output.as_mut_slice());
+        assert_eq!(get_state(&mixer, source), ffi::AL_STOPPED);
+    }
+
+    #[test]
+    fn software_mixer_looping_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let source = play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        mixer.sourcei(source, ffi::AL_LOOPING, 1);
+        let mut output = [0f32, ..50];
+        mixer.mix(output.as_mut_slice());
+        assert_eq!(get_state(&mixer, source), ffi::AL_PLAYING);
+        assert!(output[48] > 0.);
+    }
+
+    #[test]
+    fn software_mixer_queue_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        let samples = [16384i16, ..10];
+        let mut buffers = [0u32, ..2];
+        let mut source = 0;
+        mixer.gen_buffers(2, &mut buffers[0]);
+        for buffer in buffers.iter() {
+            mixer.buffer_data(*buffer, ffi::AL_FORMAT_MONO16, samples.as_ptr() as *mut c_void,
+                              20, 100);
+        }
+        mixer.gen_sources(1, &mut source);
+        mixer.source_queue_buffers(source, 2, &buffers[0]);
+        mixer.source_play(source);
+
+        let mut output = [0f32, ..24];
+        mixer.mix(output.as_mut_slice());
+        let mut processed = 0;
+        mixer.get_sourcei(source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+        assert_eq!(processed, 1);
+        let mut unqueued = 0;
+        mixer.source_unqueue_buffers(source, 1, &mut unqueued);
+        assert_eq!(unqueued, buffers[0]);
+        let mut offset = 0;
+        mixer.get_sourcei(source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+        assert_eq!(offset, 2);
+    }
+
+    #[test]
+    fn software_mixer_invalid_source_FAIL() -> () {
+        let mixer = SoftwareMixer::new(100);
+        mixer.source_play(42);
+        assert_eq!(mixer.get_error(), ffi::AL_INVALID_NAME);
+        assert_eq!(mixer.get_error(), ffi::AL_NO_ERROR);
+    }
+
+    #[test]
+    fn software_mixer_ring_buffer_OK() -> () {
+        let mut ring = RingBuffer::new(4);
+        assert_eq!(ring.push([1f32, 2., 3.].as_slice()), 3);
+        let mut output = [0f32, ..2];
+        assert_eq!(ring.pop(output.as_mut_slice()), 2);
+        assert_eq!(output, [1., 2.]);
+        assert_eq!(ring.push([4f32, 5., 6., 7.].as_slice()), 3);
+        let mut output = [0f32, ..8];
+        assert_eq!(ring.pop(output.as_mut_slice()), 4);
+        assert_eq!(output.slice_to(4), [3f32, 4., 5., 6.].as_slice());
+    }
+
+    #[test]
+    fn software_mixer_render_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        mixer.set_ring_capacity(8);
+        play_mono(&mixer, [16384i16, ..10].as_slice(), 100);
+        assert_eq!(mixer.render(16), 8);
+        assert_eq!(mixer.get_available(), 16);
+        let mut output = [0f32, ..16];
+        assert_eq!(mixer.read(output.as_mut_slice()), 16);
+        assert!(output[15] > 0.);
+    }
+
+    #[test]
+    fn software_mixer_clock_OK() -> () {
+        let mixer = SoftwareMixer::new(100);
+        backend::set_task_backend(box mixer.clone() as Box<AudioBackend + Send + Sync>);
+        assert_eq!(clock::now(), Duration::nanoseconds(0));
+
+        let mut output = [0f32, ..100];
+        mixer.mix(output.as_mut_slice());
+        assert_eq!(mixer.get_clock(), Some(500000000));
+        assert_eq!(clock::now(), Duration::milliseconds(500));
+    }
+
+    #[test]
+    fn software_mixer_play_sound_OK() -> () {
+        let mixer = SoftwareMixer::new(44100);
+        backend::set_task_backend(box mixer.clone() as Box<AudioBackend + Send + Sync>);
+
+        let mut snd = Sound::new("res/shot.wav").unwrap();
+        snd.play();
+        assert!(snd.is_playing());
+
+        assert_eq!(mixer.render(4096), 4096);
+        let mut output = Vec::from_elem(4096 * 2, 0f32);
+        assert_eq!(mixer.read(output.as_mut_slice()), 4096 * 2);
+        assert!(output.iter().any(|sample| *sample != 0.));
+    }
+}
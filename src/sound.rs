@@ -23,14 +23,25 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::Duration;
 
 use internal::OpenAlData;
 use sound_data::SoundData;
+use load_options::LoadOptions;
 use sound_data;
 use openal::{ffi, al};
 use states::{State, Initial, Playing, Paused, Stopped};
 use audio_controller::AudioController;
+use bus::Bus;
+use bus;
+use clock;
 use audio_tags::{AudioTags, Tags};
+use metadata::{AudioMetadata, Metadata};
+use marker::Marker;
+use marker;
+use channels;
+use loader;
+use hot_reload;
 
 /**
  * Play Sounds easily.
@@ -60,7 +71,19 @@ pub struct Sound {
     /// The internal OpenAl source identifier
     al_source: u32,
     /// The SoundData associated to the Sound.
-    sound_data: Rc<RefCell<SoundData>>
+    sound_data: Rc<RefCell<SoundData>>,
+    /// The Bus the Sound is assigned to.
+    bus: Option<Bus>,
+    /// The markers of the Sound, sorted by position.
+    markers: Vec<Marker>,
+    /// The markers copied from the SoundData, replaced when it's reloaded.
+    data_markers: Vec<Marker>,
+    /// The position of the last markers poll.
+    marker_position: Option<u64>,
+    /// Switch to mono samples when the Sound is positioned.
+    auto_mono: bool,
+    /// The warning about the positioning of several channels was printed.
+    unpositioned_warned: bool
 }
 
 impl Sound {
@@ -95,6 +118,28 @@ impl Sound {
         Sound::new_with_data(s_data)
     }
 
+    /**
+     * Create a new struct and an associated SoundData loaded with options.
+     *
+     * # Arguments
+     * `path` - The path of the sound file to create the SoundData.
+     * `options` - How to load the samples.
+     *
+     * # Return
+     * An Option with Some(Sound) if the Sound is created properly, or None if
+     * un error has occured.
+     */
+    pub fn new_with_options(path: &str, options: &LoadOptions) -> Option<Sound> {
+        check_openal_context!(None);
+
+        let s_data = match SoundData::new_with_options(path, options) {
+            Some(s_d) => Rc::new(RefCell::new(s_d)),
+            None      => return None
+        };
+
+        Sound::new_with_data(s_data)
+    }
+
     /**
      * Create a new struct with a SoundData to associate.
      *
@@ -139,9 +184,20 @@ impl Sound {
             None => {}
         };
 
+        let markers = sound_data::get_markers(&*sound_data.borrow()).clone();
+        sound_data::add_source(&mut *sound_data.borrow_mut(), source_id);
+        loader::add_source(&sound_data, source_id);
+        hot_reload::watch_sound(&sound_data);
+
         Some(Sound {
             al_source: source_id,
-            sound_data: sound_data
+            sound_data: sound_data,
+            bus: None,
+            markers: markers.clone(),
+            data_markers: markers,
+            marker_position: None,
+            auto_mono: true,
+            unpositioned_warned: false
         })
     }
 
@@ -190,8 +246,271 @@ impl Sound {
                         sound_data::get_buffer(&*sound_data
                                                .borrow()) as i32);
 
+        sound_data::remove_source(&mut *self.sound_data.borrow_mut(), self.al_source);
+        sound_data::add_source(&mut *sound_data.borrow_mut(), self.al_source);
+        loader::remove_source(self.al_source);
+        loader::add_source(&sound_data, self.al_source);
+        hot_reload::watch_sound(&sound_data);
+        self.markers = sound_data::get_markers(&*sound_data.borrow()).clone();
+        self.data_markers = self.markers.clone();
+        self.marker_position = None;
         self.sound_data = sound_data
     }
+
+    /**
+     * Add a marker to the Sound.
+     *
+     * # Arguments
+     * * `position` - The position of the marker in frames
+     * * `name` - The name of the marker
+     */
+    pub fn add_marker(&mut self, position: u64, name: &str) -> () {
+        self.refresh_markers();
+        marker::insert(&mut self.markers, Marker::new(position, name));
+    }
+
+    /**
+     * Get the markers of the Sound.
+     *
+     * # Return
+     * The markers loaded from the file and the added markers, sorted by
+     * position.
+     */
+    pub fn get_markers(&self) -> Vec<Marker> {
+        let data_markers = sound_data::get_markers(&*self.sound_data.borrow()).clone();
+        if data_markers == self.data_markers {
+            self.markers.clone()
+        } else {
+            replace_markers(&self.markers, &self.data_markers, &data_markers)
+        }
+    }
+
+    /// Remove all the markers of the Sound.
+    pub fn clear_markers(&mut self) -> () {
+        self.refresh_markers();
+        self.markers.clear();
+    }
+
+    /// Take the markers of the SoundData once its samples are loaded or
+    /// reloaded, keeping the added markers.
+    fn refresh_markers(&mut self) -> () {
+        let data_markers = sound_data::get_markers(&*self.sound_data.borrow()).clone();
+        if data_markers != self.data_markers {
+            self.markers = replace_markers(&self.markers, &self.data_markers, &data_markers);
+            self.data_markers = data_markers;
+        }
+    }
+
+    /**
+     * Get the markers crossed by the playback since the last call.
+     *
+     * Call it regularly, for example once per frame, to be notified of the
+     * markers.
+     *
+     * # Return
+     * The crossed markers, in the order of the playback.
+     *
+     * # Example
+     * ```Rust
+     * let mut snd = Sound::new("path/to/voice.wav").unwrap();
+     * snd.play();
+     * while snd.is_playing() {
+     *     for marker in snd.poll_markers().iter() {
+     *         println!("Reached {}", marker.name);
+     *     }
+     * }
+     * ```
+     */
+    pub fn poll_markers(&mut self) -> Vec<Marker> {
+        check_openal_context!(Vec::new());
+        self.refresh_markers();
+
+        let frames = sound_data::get_sndinfo(&*self.sound_data.borrow()).frames as u64;
+        let state = self.get_state();
+        let position = match state {
+            Playing | Paused => {
+                let mut offset = 0;
+                al::alGetSourcei(self.al_source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+                offset as u64
+            },
+            // The Sound reached its end since the last poll
+            Stopped if self.marker_position.is_some() => frames,
+            _ => {
+                self.marker_position = None;
+                return Vec::new();
+            }
+        };
+
+        let crossed = match self.marker_position {
+            // The Sound looped since the last poll
+            Some(last) if position < last => {
+                let mut crossed = marker::crossed(self.markers.as_slice(),
+                                                  Some(last),
+                                                  frames);
+                crossed.push_all(marker::crossed(self.markers.as_slice(),
+                                                 None,
+                                                 position).as_slice());
+                crossed
+            },
+            last => marker::crossed(self.markers.as_slice(), last, position)
+        };
+        self.marker_position = match state {
+            Stopped => None,
+            _       => Some(position)
+        };
+        crossed
+    }
+
+    /**
+     * Assign the Sound to a Bus.
+     *
+     * The Sound keeps its volume and its pitch, which are now relative to
+     * the Bus.
+     *
+     * # Argument
+     * `bus` - The Bus to assign the Sound to.
+     *
+     * # Example
+     * ```Rust
+     * let sfx = Bus::new();
+     * let mut snd = Sound::new("path/to/the/sound.ogg").unwrap();
+     * snd.set_bus(&sfx);
+     * ```
+     */
+    pub fn set_bus(&mut self, bus: &Bus) -> () {
+        check_openal_context!(());
+
+        let volume = self.get_volume();
+        let pitch = self.get_pitch();
+        self.remove_bus();
+        bus::add_source(bus, self.al_source, volume, pitch);
+        self.bus = Some(bus.clone());
+    }
+
+    /**
+     * Remove the Sound from its Bus.
+     *
+     * The volume and the pitch of the Sound are no longer modified by the Bus.
+     */
+    pub fn remove_bus(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+    }
+
+    /**
+     * Get the Bus of the Sound.
+     *
+     * # Return
+     * Some(Bus) if the Sound is assigned to a Bus, None otherwise.
+     */
+    pub fn get_bus(&self) -> Option<Bus> {
+        self.bus.clone()
+    }
+
+    /**
+     * Play the Sound at a time of the audio clock.
+     *
+     * If the time is already passed, the Sound starts immediately at the
+     * position it would have reached.
+     * Without the `AL_SOFT_source_start_delay` extension, the start is
+     * triggered by `ears::update`.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to play the Sound at
+     *
+     * # Example
+     * ```Rust
+     * use ears::clock;
+     *
+     * let mut snd = Sound::new("path/to/the/sound.ogg").unwrap();
+     * snd.play_at(clock::now() + Duration::milliseconds(500));
+     * ```
+     */
+    pub fn play_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        let infos = sound_data::get_sndinfo(&*self.sound_data.borrow()).clone();
+        clock::schedule_play(self.al_source,
+                             time,
+                             infos.samplerate,
+                             Some(infos.frames));
+    }
+
+    /**
+     * Stop the Sound at a time of the audio clock.
+     *
+     * The stop is triggered by `ears::update`.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to stop the Sound at
+     */
+    pub fn stop_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        clock::schedule_stop(self.al_source, time);
+    }
+
+    /**
+     * Mix down the samples to mono when the Sound is positioned.
+     *
+     * OpenAL only positions the mono sounds. When the Sound is absolute and
+     * gets a position, its SoundData is replaced by a mono copy shared by all
+     * the Sounds of the same SoundData. The copy is mixed at load time with
+     * `LoadOptions::mono_copy`, or loaded from the file at the first
+     * positioning otherwise. If the Sound is playing, a warning is printed
+     * instead.
+     *
+     * Default is true.
+     *
+     * # Argument
+     * `auto_mono` - True to switch to mono samples automatically, false to
+     * keep the samples as loaded.
+     */
+    pub fn set_auto_mono(&mut self, auto_mono: bool) -> () {
+        self.auto_mono = auto_mono;
+    }
+
+    /**
+     * Does the Sound switch to mono samples when it is positioned ?
+     *
+     * # Return
+     * True if the samples are mixed down automatically, false otherwise.
+     */
+    pub fn is_auto_mono(&self) -> bool {
+        self.auto_mono
+    }
+
+    /// Switch to mono samples or warn if a Sound with several channels is
+    /// positioned.
+    fn check_positioning(&mut self) -> () {
+        let channels = sound_data::get_channels(&*self.sound_data.borrow());
+        // No channel while the samples are loading
+        if channels <= 1 || self.is_relative() {
+            return;
+        }
+
+        if self.auto_mono && !self.is_playing() {
+            match sound_data::get_mono(&self.sound_data) {
+                Some(mono) => {
+                    let markers = self.markers.clone();
+                    self.set_datas(mono);
+                    self.markers = markers;
+                    return;
+                },
+                None       => {}
+            }
+        }
+
+        if !self.unpositioned_warned {
+            let path = sound_data::get_path(&*self.sound_data.borrow()).to_string();
+            channels::warn_unpositioned(path.as_slice(), channels);
+            self.unpositioned_warned = true;
+        }
+    }
 }
 
 impl AudioTags for Sound {
@@ -206,6 +525,18 @@ impl AudioTags for Sound {
     }
 }
 
+impl AudioMetadata for Sound {
+    /**
+     * Get the extended metadata of a Sound.
+     *
+     * # Return
+     * A copy of the metadata of the SoundData
+     */
+    fn get_metadata(&self) -> Metadata {
+        (*self.sound_data).borrow().get_metadata()
+    }
+}
+
 impl AudioController for Sound {
     /**
      * Play or resume the Sound.
@@ -219,6 +550,10 @@ impl AudioController for Sound {
     fn play(&mut self) -> () {
         check_openal_context!(());
 
+        // The samples are still loading, play once they arrive
+        if loader::play_when_ready(self.al_source, true) {
+            return;
+        }
         al::alSourcePlay(self.al_source);
 
         match al::openal_has_error() {
@@ -247,6 +582,8 @@ impl AudioController for Sound {
     /**
      * Stop the Sound.
      *
+     * The scheduled playbacks of the Sound are cancelled.
+     *
      * # Example
      * ```Rust
      * let snd = Sound::new("path/to/the/sound.ogg").unwrap();
@@ -258,6 +595,9 @@ impl AudioController for Sound {
     fn stop(&mut self) -> () {
         check_openal_context!(());
 
+        clock::cancel(self.al_source);
+        loader::play_when_ready(self.al_source, false);
+        self.marker_position = None;
         al::alSourceStop(self.al_source)
     }
 
@@ -332,7 +672,10 @@ impl AudioController for Sound {
     fn set_volume(&mut self, volume: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+        match self.bus {
+            Some(ref b) => bus::set_source_volume(b, self.al_source, volume),
+            None        => al::alSourcef(self.al_source, ffi::AL_GAIN, volume)
+        }
     }
 
     /**
@@ -344,6 +687,11 @@ impl AudioController for Sound {
     fn get_volume(&self) -> f32 {
         check_openal_context!(0.);
 
+        match self.bus {
+            Some(ref b) => return bus::get_source_volume(b, self.al_source),
+            None        => {}
+        }
+
         let mut volume : f32 = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
         volume
@@ -462,7 +810,10 @@ impl AudioController for Sound {
     fn set_pitch(&mut self, pitch: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+        match self.bus {
+            Some(ref b) => bus::set_source_pitch(b, self.al_source, pitch),
+            None        => al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+        }
     }
 
     /**
@@ -474,6 +825,11 @@ impl AudioController for Sound {
     fn get_pitch(&self) -> f32 {
         check_openal_context!(0.);
 
+        match self.bus {
+            Some(ref b) => return bus::get_source_pitch(b, self.al_source),
+            None        => {}
+        }
+
         let mut pitch = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_PITCH, &mut pitch);
         pitch
@@ -499,6 +855,10 @@ impl AudioController for Sound {
                                      ffi::AL_SOURCE_RELATIVE,
                                      ffi::ALC_FALSE as i32)
         };
+
+        if !relative && self.get_position() != [0., ..3] {
+            self.check_positioning();
+        }
     }
 
     /**
@@ -539,6 +899,7 @@ impl AudioController for Sound {
         check_openal_context!(());
 
         al::alSourcefv(self.al_source, ffi::AL_POSITION, &position[0]);
+        self.check_positioning();
     }
 
     /**
@@ -689,13 +1050,33 @@ impl AudioController for Sound {
 
 }
 
+/// Replace the markers copied from a SoundData by its new markers.
+fn replace_markers(markers: &Vec<Marker>, old: &Vec<Marker>, new: &Vec<Marker>) -> Vec<Marker> {
+    let mut markers = markers.clone();
+    for marker in old.iter() {
+        match markers.iter().position(|m| m == marker) {
+            Some(i) => { markers.remove(i); },
+            None    => {}
+        }
+    }
+    for marker in new.iter() {
+        marker::insert(&mut markers, marker.clone());
+    }
+    markers
+}
+
 #[unsafe_destructor]
 impl Drop for Sound {
     ///Destroy all the resources attached to the Sound.
     fn drop(&mut self) -> () {
-        unsafe {
-            ffi::alDeleteSources(1, &mut self.al_source);
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
         }
+        clock::cancel(self.al_source);
+        loader::remove_source(self.al_source);
+        sound_data::remove_source(&mut *self.sound_data.borrow_mut(), self.al_source);
+        al::alDeleteSources(1, &mut self.al_source);
     }
 }
 
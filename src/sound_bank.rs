@@ -0,0 +1,318 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! A cache of SoundDatas shared by the Sounds.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use internal::OpenAlData;
+use sound::Sound;
+use sound_data::SoundData;
+use sound_data;
+use load_options::{LoadOptions, get_default_load_options};
+
+/// A SoundData loaded in the SoundBank.
+struct Entry {
+    /// The shared samples
+    data: Rc<RefCell<SoundData>>,
+    /// When the entry was last used, to unload the least recently used first
+    last_use: u64
+}
+
+/**
+ * A cache of SoundDatas.
+ *
+ * The SoundBank loads each file once and hands out shared SoundDatas to
+ * create Sounds with `Sound::new_with_data`. The files are identified by
+ * their path or by an asset id registered with `add`.
+ *
+ * Without a memory budget, the SoundDatas stay loaded until `unload` is
+ * called. With a memory budget, the least recently used SoundDatas are
+ * unloaded when a new one doesn't fit. The memory is released once the
+ * Sounds using an unloaded SoundData are dropped.
+ *
+ * # Example
+ * ```Rust
+ * use ears::{SoundBank, Sound, AudioController};
+ *
+ * let mut bank = SoundBank::new();
+ * bank.add("shot", "res/shot.wav");
+ * bank.preload(&["shot", "res/explosion.wav"]);
+ *
+ * // The two Sounds share the same samples
+ * let mut shot1 = bank.sound("shot").unwrap();
+ * let mut shot2 = bank.sound("res/shot.wav").unwrap();
+ * shot1.play();
+ * shot2.play();
+ * ```
+ */
+pub struct SoundBank {
+    /// The loaded SoundDatas, by path
+    entries: HashMap<String, Entry>,
+    /// The paths of the asset ids
+    ids: HashMap<String, String>,
+    /// The options to load the SoundDatas
+    options: LoadOptions,
+    /// The maximum size in bytes of the loaded SoundDatas
+    budget: Option<uint>,
+    /// Incremented at each use of a SoundData
+    uses: u64
+}
+
+impl SoundBank {
+    /**
+     * Create an empty SoundBank.
+     *
+     * The SoundDatas are loaded with the options of
+     * `set_default_load_options`.
+     */
+    pub fn new() -> SoundBank {
+        SoundBank::new_with_options(&get_default_load_options())
+    }
+
+    /**
+     * Create an empty SoundBank loading the SoundDatas with options.
+     *
+     * # Argument
+     * * `options` - How to load the samples
+     */
+    pub fn new_with_options(options: &LoadOptions) -> SoundBank {
+        SoundBank {
+            entries: HashMap::new(),
+            ids: HashMap::new(),
+            options: options.clone(),
+            budget: None,
+            uses: 0
+        }
+    }
+
+    /**
+     * Register an asset id.
+     *
+     * The file is not loaded until the id is used.
+     *
+     * # Arguments
+     * * `id` - The asset id
+     * * `path` - The path of the file of the asset
+     */
+    pub fn add(&mut self, id: &str, path: &str) -> () {
+        self.ids.insert(id.to_string(), path.to_string());
+    }
+
+    /// Get the path of an asset id or a path.
+    fn resolve(&self, key: &str) -> String {
+        match self.ids.find(&key.to_string()) {
+            Some(path) => path.clone(),
+            None       => key.to_string()
+        }
+    }
+
+    /**
+     * Get a SoundData, load it if needed.
+     *
+     * # Argument
+     * * `key` - An asset id registered with `add`, or the path of a file
+     *
+     * # Return
+     * Some(SoundData) shared with the other users of the same file, None if
+     * the file can't be loaded.
+     */
+    pub fn get(&mut self, key: &str) -> Option<Rc<RefCell<SoundData>>> {
+        check_openal_context!(None);
+
+        let path = self.resolve(key);
+        self.uses += 1;
+        match self.entries.find_mut(&path) {
+            Some(entry) => {
+                entry.last_use = self.uses;
+                return Some(entry.data.clone());
+            },
+            None        => {}
+        }
+
+        let data = match SoundData::new_with_options(path.as_slice(), &self.options) {
+            Some(data) => Rc::new(RefCell::new(data)),
+            None       => return None
+        };
+        self.entries.insert(path.clone(), Entry {
+            data: data.clone(),
+            last_use: self.uses
+        });
+        self.fit_budget(path.as_slice());
+        Some(data)
+    }
+
+    /**
+     * Create a Sound playing a SoundData of the SoundBank.
+     *
+     * # Argument
+     * * `key` - An asset id registered with `add`, or the path of a file
+     *
+     * # Return
+     * Some(Sound) if the file can be loaded, None otherwise.
+     */
+    pub fn sound(&mut self, key: &str) -> Option<Sound> {
+        match self.get(key) {
+            Some(data) => Sound::new_with_data(data),
+            None       => None
+        }
+    }
+
+    /**
+     * Load a list of SoundDatas.
+     *
+     * # Argument
+     * * `keys` - Asset ids registered with `add`, or paths of files
+     *
+     * # Return
+     * The count of SoundDatas loaded or already loaded.
+     */
+    pub fn preload(&mut self, keys: &[&str]) -> uint {
+        keys.iter().filter(|key| self.get(**key).is_some()).count()
+    }
+
+    /**
+     * Check if a SoundData is loaded.
+     *
+     * # Argument
+     * * `key` - An asset id registered with `add`, or the path of a file
+     */
+    pub fn is_loaded(&self, key: &str) -> bool {
+        self.entries.contains_key(&self.resolve(key))
+    }
+
+    /**
+     * Unload a SoundData.
+     *
+     * The Sounds already created keep their samples.
+     *
+     * # Argument
+     * * `key` - An asset id registered with `add`, or the path of a file
+     *
+     * # Return
+     * true if the SoundData was loaded, false otherwise.
+     */
+    pub fn unload(&mut self, key: &str) -> bool {
+        let path = self.resolve(key);
+        self.entries.remove(&path)
+    }
+
+    /// Unload all the SoundDatas.
+    pub fn unload_all(&mut self) -> () {
+        self.entries.clear();
+    }
+
+    /**
+     * Set the maximum size in bytes of the loaded SoundDatas.
+     *
+     * The least recently used SoundDatas are unloaded to fit in the budget.
+     *
+     * # Argument
+     * * `budget` - The size in bytes, None for no limit
+     */
+    pub fn set_memory_budget(&mut self, budget: Option<uint>) -> () {
+        self.budget = budget;
+        self.fit_budget("");
+    }
+
+    /// Get the maximum size in bytes of the loaded SoundDatas.
+    pub fn get_memory_budget(&self) -> Option<uint> {
+        self.budget
+    }
+
+    /// Get the size in bytes of the loaded SoundDatas, with their mono copies.
+    pub fn get_memory_usage(&self) -> uint {
+        self.entries.values().fold(0, |sum, entry| {
+            sum + sound_data::get_byte_size(&*entry.data.borrow())
+        })
+    }
+
+    /// Get the count of loaded SoundDatas.
+    pub fn len(&self) -> uint {
+        self.entries.len()
+    }
+
+    /// Unload the least recently used SoundDatas, except `keep`, until the
+    /// memory usage fits in the budget.
+    fn fit_budget(&mut self, keep: &str) -> () {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None         => return
+        };
+
+        while self.get_memory_usage() > budget {
+            let oldest = self.entries.iter()
+                                     .filter(|&(path, _)| path.as_slice() != keep)
+                                     .min_by(|&(_, entry)| entry.last_use)
+                                     .map(|(path, _)| path.clone());
+            match oldest {
+                Some(path) => { self.entries.remove(&path); },
+                None       => return
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::cell::RefCell;
+    use sound_bank::SoundBank;
+    use sound_data::SoundData;
+
+    #[test]
+    fn sound_bank_dedupe_OK() -> () {
+        let mut bank = SoundBank::new();
+        bank.add("shot", "res/shot.wav");
+
+        let data1 = bank.get("shot").expect("Cannot load the sound");
+        let data2 = bank.get("res/shot.wav").expect("Cannot load the sound");
+
+        assert!(&*data1 as *const RefCell<SoundData> == &*data2 as *const RefCell<SoundData>);
+        assert_eq!(bank.len(), 1);
+    }
+
+    #[test]
+    fn sound_bank_unload_OK() -> () {
+        let mut bank = SoundBank::new();
+
+        assert_eq!(bank.preload(&["res/shot.wav", "toto.wav"]), 1);
+        assert!(bank.is_loaded("res/shot.wav"));
+        assert!(bank.unload("res/shot.wav"));
+        assert!(!bank.is_loaded("res/shot.wav"));
+        assert_eq!(bank.get_memory_usage(), 0);
+    }
+
+    #[test]
+    fn sound_bank_budget_OK() -> () {
+        let mut bank = SoundBank::new();
+        bank.set_memory_budget(Some(0));
+
+        // The last loaded SoundData is kept even if it doesn't fit
+        assert!(bank.get("res/shot.wav").is_some());
+        assert!(bank.is_loaded("res/shot.wav"));
+        bank.set_memory_budget(Some(0));
+        assert!(!bank.is_loaded("res/shot.wav"));
+    }
+}
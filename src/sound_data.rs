@@ -21,14 +21,28 @@
 
 //! The datas extracted from a sound file.
 
-use std::mem;
-use libc::c_void;
 use std::vec::Vec;
+use std::mem;
+use std::rc::Rc;
+use std::cell::RefCell;
 
-use openal::{ffi, al};
-use sndfile::{SndFile, SndInfo, Read};
+use openal::al;
+use sndfile::SndInfo;
 use internal::OpenAlData;
-use audio_tags::{Tags, AudioTags, get_sound_tags};
+use sample::{SampleType, SampleBuffer, Float32Buffer, Float32};
+use resampler::Resampler;
+use load_options::{LoadOptions, get_default_load_options};
+use loader::{AsyncSoundData, LoadProgress};
+use loader;
+use channels::{ChannelPlan, ChannelLayout, LayoutMono, LayoutStereo};
+use channels;
+use audio_tags::{Tags, AudioTags};
+use audio_tags;
+use metadata::{Metadata, AudioMetadata};
+use metadata;
+use marker::{Marker, load_markers};
+use decoder::Decoder;
+use decoder;
 
 /**
  * Samples extracted from a file.
@@ -62,12 +76,28 @@ use audio_tags::{Tags, AudioTags, get_sound_tags};
 pub struct SoundData {
     /// The SoundTags who contains all the information of the sound
     sound_tags: Tags,
+    /// The broadcast info, cue points and instrument of the sound
+    metadata: Metadata,
+    /// The markers from the cue points and the sidecar file
+    markers: Vec<Marker>,
     /// The sndfile samples information
     snd_info: SndInfo,
     /// The total samples count of the Sound
     nb_sample: i64,
     /// The OpenAl internal identifier for the buffer
-    al_buffer: u32
+    al_buffer: u32,
+    /// The path of the file
+    path: String,
+    /// The options used to load the samples
+    options: LoadOptions,
+    /// The count of channels of the buffer
+    channels: uint,
+    /// The size in bytes of the buffer
+    byte_size: uint,
+    /// The samples mixed down to mono, to position the sound
+    mono: Option<Rc<RefCell<SoundData>>>,
+    /// The sources bound to the buffer
+    sources: Vec<u32>
 }
 
 impl SoundData {
@@ -77,6 +107,7 @@ impl SoundData {
      * The SoundData contains all the information extracted from the
      * file: samples and tags.
      * It's an easy way to share the same samples between man Sounds objects.
+     * The samples are loaded with the options of `set_default_load_options`.
      *
      * # Arguments
      * * `path` - The path of the file to load
@@ -86,55 +117,437 @@ impl SoundData {
      * an error has occured.
      */
     pub fn new(path: &str) -> Option<SoundData> {
-        check_openal_context!(None);
+        SoundData::new_with_options(path, &get_default_load_options())
+    }
 
-        let mut file;
+    /**
+     * Create a new SoundData with options.
+     *
+     * If `options.sample_rate` differs from the rate of the file, the samples
+     * are converted at load time and the infos and the markers of the
+     * SoundData use the new rate.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load
+     * * `options` - How to load the samples
+     *
+     * # Return
+     * An Option with Some(SoundData) if the SoundData is create, or None if
+     * an error has occured.
+     */
+    pub fn new_with_options(path: &str, options: &LoadOptions) -> Option<SoundData> {
+        check_openal_context!(None);
 
-        match SndFile::new(path, Read) {
-            Ok(file_) => file = file_,
-            Err(err) => { println!("{}", err); return None; }
+        let prepared = match prepare(path, options) {
+            Some(prepared) => prepared,
+            None           => return None
         };
+        match decode(&prepared, None) {
+            Ok(decoded) => upload(decoded, &prepared),
+            Err(err)    => { println!("{}", err); None }
+        }
+    }
 
-        let infos = file.get_sndinfo();
-
-        let nb_sample = infos.channels as i64 * infos.frames;
-
-        let mut samples = Vec::from_elem(nb_sample as uint, 0i16);
-        file.read_i16(samples.as_mut_slice(), nb_sample as i64);
-
-        let mut buffer_id = 0;
-        let len = mem::size_of::<i16>() * (samples.len());
+    /**
+     * Create a new SoundData from samples in memory.
+     *
+     * The samples are uploaded as they are, the SoundData has no file, no
+     * tags and is never reloaded.
+     *
+     * # Arguments
+     * * `samples` - The interleaved samples, from -1 to 1
+     * * `channels` - The count of channels, 1 or 2
+     * * `samplerate` - The sample rate of the samples
+     *
+     * # Return
+     * An Option with Some(SoundData) if the SoundData is create, or None if
+     * an error has occured.
+     */
+    pub fn from_samples(samples: &[f32], channels: uint, samplerate: i32) -> Option<SoundData> {
+        check_openal_context!(None);
 
-        // Retrieve format informations
-        let format =  match al::get_channels_format(infos.channels) {
-            Some(fmt) => fmt,
-            None => {
-                println!("Internal error : unrecognized format.");
+        let layout = match channels {
+            1 => LayoutMono,
+            2 => LayoutStereo,
+            _ => {
+                println!("Error: a SoundData from samples must have 1 or 2 channels.");
                 return None;
             }
         };
+        let (format, sample_type) = match al::negotiate_format(layout.clone(), None) {
+            Some(negotiated) => negotiated,
+            None             => return None
+        };
+        let buffer = SampleBuffer::from_f32(sample_type.clone(), samples);
+        let frames = samples.len() / channels;
 
+        let mut buffer_id = 0;
         al::alGenBuffers(1, &mut buffer_id);
         al::alBufferData(buffer_id,
                          format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         infos.samplerate);
+                         buffer.as_ptr(),
+                         buffer.byte_size(frames * channels) as i32,
+                         samplerate);
 
         match al::openal_has_error() {
             Some(err)   => { println!("{}", err); return None; },
             None        => {}
         };
 
-        let sound_data = SoundData {
-            sound_tags  : get_sound_tags(&file),
-            snd_info    : infos,
-            nb_sample   : nb_sample,
-            al_buffer   : buffer_id
+        let mut data = SoundData {
+            sound_tags  : audio_tags::empty(),
+            metadata    : metadata::empty(),
+            markers     : Vec::new(),
+            snd_info    : SndInfo {
+                frames: frames as i64,
+                samplerate: samplerate,
+                channels: channels as i32,
+                format: 0,
+                sections: 0,
+                seekable: 0
+            },
+            nb_sample   : (frames * channels) as i64,
+            al_buffer   : buffer_id,
+            path        : String::new(),
+            options     : get_default_load_options(),
+            channels    : channels,
+            byte_size   : buffer.byte_size(frames * channels),
+            mono        : None,
+            sources     : Vec::new()
         };
-        file.close();
+        if channels > 1 && data.options.mono_copy {
+            let mono = mix_mono(samples, layout);
+            data.mono = upload_mono(&data, mono.as_slice(), sample_type);
+        }
+        Some(data)
+    }
+
+    /**
+     * Load a SoundData in the background.
+     *
+     * The file is decoded by a pool of worker tasks and the samples are
+     * uploaded to OpenAL by `ears::update`. The Sounds created from the
+     * SoundData of the handle before it's ready start to play when the
+     * samples arrive.
+     *
+     * The samples are loaded with the options of `set_default_load_options`.
+     *
+     * # Argument
+     * * `path` - The path of the file to load
+     *
+     * # Return
+     * A handle to follow the loading.
+     *
+     * # Example
+     * ```Rust
+     * let ambience = SoundData::load_async("path/to/ambience.flac");
+     * let mut snd = Sound::new_with_data(ambience.get_data()).unwrap();
+     * // Starts when the samples are loaded
+     * snd.play();
+     *
+     * loop {
+     *     ears::update();
+     *     println!("{}%", ambience.get_progress() * 100.);
+     * }
+     * ```
+     */
+    pub fn load_async(path: &str) -> AsyncSoundData {
+        SoundData::load_async_with_options(path, &get_default_load_options())
+    }
+
+    /**
+     * Load a SoundData in the background with options.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load
+     * * `options` - How to load the samples
+     *
+     * # Return
+     * A handle to follow the loading.
+     */
+    pub fn load_async_with_options(path: &str, options: &LoadOptions) -> AsyncSoundData {
+        loader::load(path, options)
+    }
+}
+
+/// The count of frames decoded at once.
+static DECODE_CHUNK: uint = 16384;
+
+/// The format of a file to load, chosen before decoding it.
+#[doc(hidden)]
+#[deriving(Clone)]
+pub struct PreparedSound {
+    pub path: String,
+    pub options: LoadOptions,
+    pub format: i32,
+    pub sample_type: SampleType,
+    pub plan: ChannelPlan,
+    pub frames: i64
+}
+
+/// The samples and the information decoded from a file.
+#[doc(hidden)]
+pub struct DecodedSound {
+    samples: SampleBuffer,
+    len: uint,
+    infos: SndInfo,
+    tags: Tags,
+    metadata: Metadata,
+    markers: Vec<Marker>,
+    /// The samples mixed down to mono, with `LoadOptions::mono_copy`
+    mono: Option<Vec<f32>>
+}
+
+/**
+ * Open a file and choose the OpenAL format of its samples.
+ *
+ * # Return
+ * Some(PreparedSound) if the file is supported, None otherwise.
+ */
+#[doc(hidden)]
+pub fn prepare(path: &str, options: &LoadOptions) -> Option<PreparedSound> {
+    let file = match decoder::open(path) {
+        Ok(file) => file,
+        Err(err) => { println!("{}", err); return None; }
+    };
+
+    let (format, sample_type, plan) = match channels::negotiate(&*file, options) {
+        Some(negotiated) => negotiated,
+        None => {
+            println!("Internal error : unrecognized format.");
+            return None;
+        }
+    };
+    let frames = file.get_sndinfo().frames;
+
+    Some(PreparedSound {
+        path: path.to_string(),
+        options: options.clone(),
+        format: format,
+        sample_type: sample_type,
+        plan: plan,
+        frames: frames
+    })
+}
+
+/**
+ * Decode the samples of a file.
+ *
+ * Doesn't need OpenAL, it can run in any task.
+ *
+ * # Arguments
+ * * `prepared` - The file and its format
+ * * `progress` - Where to count the decoded frames and check the
+ * cancellation
+ */
+#[doc(hidden)]
+pub fn decode(prepared: &PreparedSound,
+              progress: Option<&LoadProgress>) -> Result<DecodedSound, String> {
+    let mut file = try!(decoder::open(prepared.path.as_slice()));
+    let mut infos = file.get_sndinfo();
+    let channels = prepared.plan.get_output_channels();
+    let resample = match prepared.options.sample_rate {
+        Some(rate) if rate != infos.samplerate => Some(rate),
+        _                                      => None
+    };
+    let read_type = if resample.is_some() { Float32 } else { prepared.sample_type.clone() };
+
+    let mut samples = SampleBuffer::new(read_type.clone(), 0);
+    let mut chunk = SampleBuffer::new(read_type, DECODE_CHUNK * channels);
+    loop {
+        match progress {
+            Some(progress) if progress.is_cancelled() => {
+                return Err(format!("Loading of {} cancelled.", prepared.path));
+            },
+            _                                         => {}
+        }
+        let read = chunk.read_from(&mut *file, &prepared.plan);
+        if read == 0 {
+            break;
+        }
+        samples.append(&chunk, read);
+        match progress {
+            Some(progress) => progress.add_frames(read / channels),
+            None           => {}
+        }
+    }
+    let mut len = samples.len();
+
+    let file_samplerate = infos.samplerate;
+    match resample {
+        Some(rate) => {
+            let resampler = Resampler::new(infos.samplerate,
+                                           rate,
+                                           channels,
+                                           prepared.options.resample_quality.clone());
+            let resampled = match samples {
+                Float32Buffer(ref floats) => resampler.process(floats.as_slice()),
+                _                         => unreachable!()
+            };
+            infos.samplerate = rate;
+            infos.frames = (resampled.len() / channels) as i64;
+            len = resampled.len();
+            samples = SampleBuffer::from_f32(prepared.sample_type.clone(), resampled.as_slice());
+        },
+        None       => {}
+    }
+
+    let metadata = file.get_metadata();
+    let mut markers = load_markers(prepared.path.as_slice(), &metadata);
+    if infos.samplerate != file_samplerate {
+        for marker in markers.iter_mut() {
+            marker.position = marker.position * infos.samplerate as u64
+                              / file_samplerate as u64;
+        }
+    }
+    let tags = file.get_tags();
+
+    // Mixed here to keep it out of the task uploading the samples
+    let mono = if prepared.options.mono_copy && channels > 1 {
+        Some(mix_mono(samples.to_f32(len).as_slice(), prepared.plan.get_layout()))
+    } else {
+        None
+    };
+
+    Ok(DecodedSound {
+        samples: samples,
+        len: len,
+        infos: infos,
+        tags: tags,
+        metadata: metadata,
+        markers: markers,
+        mono: mono
+    })
+}
+
+/**
+ * Upload decoded samples to an OpenAL buffer.
+ *
+ * # Return
+ * Some(SoundData) if the buffer is created, None otherwise.
+ */
+#[doc(hidden)]
+pub fn upload(decoded: DecodedSound, prepared: &PreparedSound) -> Option<SoundData> {
+    let DecodedSound { samples, len, infos, tags, metadata, markers, mono } = decoded;
 
-        Some(sound_data)
+    let mut buffer_id = 0;
+    al::alGenBuffers(1, &mut buffer_id);
+    al::alBufferData(buffer_id,
+                     prepared.format,
+                     samples.as_ptr(),
+                     samples.byte_size(len) as i32,
+                     infos.samplerate);
+
+    match al::openal_has_error() {
+        Some(err)   => { println!("{}", err); return None; },
+        None        => {}
+    };
+
+    let mut data = SoundData {
+        sound_tags  : tags,
+        metadata    : metadata,
+        markers     : markers,
+        nb_sample   : infos.channels as i64 * infos.frames,
+        snd_info    : infos,
+        al_buffer   : buffer_id,
+        path        : prepared.path.clone(),
+        options     : prepared.options.clone(),
+        channels    : prepared.plan.get_output_channels(),
+        byte_size   : samples.byte_size(len),
+        mono        : None,
+        sources     : Vec::new()
+    };
+    match mono {
+        Some(mono) => {
+            data.mono = upload_mono(&data, mono.as_slice(), prepared.sample_type.clone());
+        },
+        None       => {}
+    }
+    Some(data)
+}
+
+/// Mix down interleaved samples to mono.
+fn mix_mono(samples: &[f32], layout: ChannelLayout) -> Vec<f32> {
+    ChannelPlan::downmix(layout.get_speakers().as_slice(), LayoutMono).mix(samples)
+}
+
+/**
+ * Upload the samples of a SoundData mixed down to mono.
+ *
+ * Done at load time with `LoadOptions::mono_copy`, so positioning a Sound
+ * switches to the mono samples without decoding the file again.
+ *
+ * # Arguments
+ * * `data` - The SoundData of the samples
+ * * `samples` - The samples mixed down to mono
+ * * `sample_type` - The type of the samples sent to OpenAL
+ */
+fn upload_mono(data: &SoundData,
+               samples: &[f32],
+               sample_type: SampleType) -> Option<Rc<RefCell<SoundData>>> {
+    let (format, sample_type) = match al::negotiate_format(LayoutMono, Some(sample_type)) {
+        Some(negotiated) => negotiated,
+        None             => return None
+    };
+    let mono = SampleBuffer::from_f32(sample_type, samples);
+    let len = mono.len();
+
+    let mut buffer_id = 0;
+    al::alGenBuffers(1, &mut buffer_id);
+    al::alBufferData(buffer_id,
+                     format,
+                     mono.as_ptr(),
+                     mono.byte_size(len) as i32,
+                     data.snd_info.samplerate);
+
+    match al::openal_has_error() {
+        Some(err)   => { println!("{}", err); return None; },
+        None        => {}
+    };
+
+    let mut options = data.options.clone();
+    options.downmix_to_mono = true;
+    Some(Rc::new(RefCell::new(SoundData {
+        sound_tags  : data.sound_tags.clone(),
+        metadata    : data.metadata.clone(),
+        markers     : data.markers.clone(),
+        snd_info    : data.snd_info.clone(),
+        nb_sample   : data.nb_sample,
+        al_buffer   : buffer_id,
+        path        : data.path.clone(),
+        options     : options,
+        channels    : 1,
+        byte_size   : mono.byte_size(len),
+        mono        : None,
+        sources     : Vec::new()
+    })))
+}
+
+/**
+ * Create a SoundData without samples, replaced once loaded.
+ */
+#[doc(hidden)]
+pub fn placeholder(path: &str, options: &LoadOptions) -> SoundData {
+    SoundData {
+        sound_tags  : audio_tags::empty(),
+        metadata    : metadata::empty(),
+        markers     : Vec::new(),
+        snd_info    : SndInfo {
+            frames: 0,
+            samplerate: 0,
+            channels: 0,
+            format: 0,
+            sections: 0,
+            seekable: 0
+        },
+        nb_sample   : 0,
+        al_buffer   : 0,
+        path        : path.to_string(),
+        options     : options.clone(),
+        channels    : 0,
+        byte_size   : 0,
+        mono        : None,
+        sources     : Vec::new()
     }
 }
 
@@ -160,6 +573,126 @@ pub fn get_buffer(s_data: &SoundData) -> u32 {
     s_data.al_buffer
 }
 
+/**
+ * Get the markers loaded with the sound.
+ *
+ * # Return
+ * The markers from the cue points of the file and its sidecar file.
+ */
+#[doc(hidden)]
+pub fn get_markers<'r>(s_data: &'r SoundData) -> &'r Vec<Marker> {
+    &s_data.markers
+}
+
+/**
+ * Get the count of channels of the samples buffer.
+ *
+ * # Return
+ * The count of channels sent to OpenAL, after the downmix if any.
+ */
+#[doc(hidden)]
+pub fn get_channels(s_data: &SoundData) -> uint {
+    s_data.channels
+}
+
+/**
+ * Get the size in bytes of the samples buffer and of its mono copy.
+ */
+#[doc(hidden)]
+pub fn get_byte_size(s_data: &SoundData) -> uint {
+    match s_data.mono {
+        Some(ref mono) => s_data.byte_size + mono.borrow().byte_size,
+        None           => s_data.byte_size
+    }
+}
+
+/**
+ * Get the path of the file of the sound.
+ */
+#[doc(hidden)]
+pub fn get_path<'r>(s_data: &'r SoundData) -> &'r str {
+    s_data.path.as_slice()
+}
+
+/**
+ * Get the options used to load the samples.
+ */
+#[doc(hidden)]
+pub fn get_options(s_data: &SoundData) -> LoadOptions {
+    s_data.options.clone()
+}
+
+/**
+ * Get the samples of the sound mixed down to mono.
+ *
+ * The mono samples are mixed when the sound is loaded with
+ * `LoadOptions::mono_copy`, or loaded from the file at the first call
+ * otherwise. They are shared by all the Sounds.
+ *
+ * # Return
+ * The SoundData itself if it's already mono, None if its samples are not
+ * loaded yet or if it has no file to load them from.
+ */
+#[doc(hidden)]
+pub fn get_mono(s_data: &Rc<RefCell<SoundData>>) -> Option<Rc<RefCell<SoundData>>> {
+    let (path, mut options) = {
+        let data = s_data.borrow();
+        if data.channels == 1 {
+            return Some(s_data.clone());
+        }
+        if data.mono.is_some() || data.channels == 0 || data.path.is_empty() {
+            return data.mono.clone();
+        }
+        (data.path.clone(), data.options.clone())
+    };
+    options.downmix_to_mono = true;
+    options.mono_copy = false;
+    let mono = SoundData::new_with_options(path.as_slice(), &options)
+                         .map(|mono| Rc::new(RefCell::new(mono)));
+    s_data.borrow_mut().mono = mono.clone();
+    mono
+}
+
+/**
+ * Register a source bound to the buffer of a SoundData.
+ *
+ * The sources are bound to the new buffer when the samples are replaced.
+ */
+#[doc(hidden)]
+pub fn add_source(s_data: &mut SoundData, source: u32) -> () {
+    if !s_data.sources.contains(&source) {
+        s_data.sources.push(source);
+    }
+}
+
+/**
+ * Forget a source no longer bound to the buffer of a SoundData.
+ */
+#[doc(hidden)]
+pub fn remove_source(s_data: &mut SoundData, source: u32) -> () {
+    s_data.sources.retain(|&s| s != source);
+}
+
+/**
+ * Get the sources bound to the buffer of a SoundData.
+ */
+#[doc(hidden)]
+pub fn get_sources<'r>(s_data: &'r SoundData) -> &'r Vec<u32> {
+    &s_data.sources
+}
+
+/**
+ * Replace the samples of a SoundData, keeping its sources.
+ *
+ * The sources must be bound to the new buffer before the call: the previous
+ * buffer is deleted.
+ */
+#[doc(hidden)]
+pub fn replace(s_data: &mut SoundData, mut new_data: SoundData) -> () {
+    new_data.sources = mem::replace(&mut s_data.sources, Vec::new());
+    *s_data = new_data;
+}
+
 impl AudioTags for SoundData {
     /**
      * Get the tags of a Sound.
@@ -172,12 +705,22 @@ impl AudioTags for SoundData {
     }
 }
 
+impl AudioMetadata for SoundData {
+    /**
+     * Get the extended metadata of a Sound.
+     *
+     * # Return
+     * A copy of the metadata read from the file
+     */
+    fn get_metadata(&self) -> Metadata {
+        self.metadata.clone()
+    }
+}
+
 impl Drop for SoundData {
     /// Destroy all the resources attached to the SoundData
     fn drop(&mut self) -> () {
-        unsafe {
-            ffi::alDeleteBuffers(1, &mut self.al_buffer);
-        }
+        al::alDeleteBuffers(1, &mut self.al_buffer);
     }
 }
 
@@ -185,8 +728,13 @@ impl Drop for SoundData {
 mod test {
     #![allow(non_snake_case_functions)]
 
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::default::Default;
+
     #[allow(unused_variable)]
-    use sound_data::SoundData;
+    use sound_data::{SoundData, get_mono, get_channels, get_sndinfo, get_byte_size};
+    use load_options::{LoadOptions, set_default_load_options};
 
     #[test]
     fn sounddata_create_OK() -> () {
@@ -201,4 +749,48 @@ mod test {
         #![allow(unused_variable)]
         let snd_data = SoundData::new("toto.wav").unwrap();
     }
+
+    #[test]
+    fn sounddata_get_mono_OK() -> () {
+        let options = LoadOptions { downmix_to_mono: true, ..Default::default() };
+        let snd_data = Rc::new(RefCell::new(SoundData::new_with_options("res/shot.wav",
+                                                                        &options).unwrap()));
+        let mono = get_mono(&snd_data).unwrap();
+
+        assert_eq!(get_channels(&*mono.borrow()), 1);
+        assert!(&*mono as *const RefCell<SoundData> == &*snd_data as *const RefCell<SoundData>);
+
+        // Without file, the mono copy is only made with the option
+        let samples = [0.5f32, 0.5, -0.5, -0.5];
+        let stereo = SoundData::from_samples(samples.as_slice(), 2, 44100);
+        let stereo = Rc::new(RefCell::new(stereo.unwrap()));
+        assert!(get_mono(&stereo).is_none());
+        let stereo_size = get_byte_size(&*stereo.borrow());
+
+        set_default_load_options(&LoadOptions { mono_copy: true, ..Default::default() });
+        let stereo = SoundData::from_samples(samples.as_slice(), 2, 44100);
+        let stereo = Rc::new(RefCell::new(stereo.unwrap()));
+        let mono = get_mono(&stereo).unwrap();
+
+        assert_eq!(get_channels(&*mono.borrow()), 1);
+        assert_eq!(get_sndinfo(&*mono.borrow()).frames, 2);
+        // The size of the mono copy is counted
+        assert_eq!(get_byte_size(&*stereo.borrow()), stereo_size + stereo_size / 2);
+        // The mono samples are shared
+        let again = get_mono(&stereo).unwrap();
+        assert!(&*mono as *const RefCell<SoundData> == &*again as *const RefCell<SoundData>);
+    }
+
+    #[test]
+    fn sounddata_from_samples_OK() -> () {
+        let snd_data = SoundData::from_samples([0f32, 0.5, -0.5, 0.].as_slice(), 2, 44100).unwrap();
+
+        assert_eq!(get_channels(&snd_data), 2);
+        assert_eq!(get_sndinfo(&snd_data).frames, 2);
+    }
+
+    #[test]
+    fn sounddata_from_samples_FAIL() -> () {
+        assert!(SoundData::from_samples([0f32, ..6].as_slice(), 3, 44100).is_none());
+    }
 }
@@ -0,0 +1,154 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Control the OpenAL source of the streamed sounds.
+//!
+//! Music, Playlist and StreamSource stream in a single OpenAL source, their
+//! AudioController implementations call these functions.
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use states::{State, Initial, Playing, Paused, Stopped};
+use bus::Bus;
+use bus;
+
+/// Get the state of a source.
+pub fn get_state(al_source: u32) -> State {
+    check_openal_context!(Initial);
+
+    match al::alGetState(al_source) {
+        ffi::AL_INITIAL => Initial,
+        ffi::AL_PLAYING => Playing,
+        ffi::AL_PAUSED  => Paused,
+        ffi::AL_STOPPED => Stopped,
+        _               => unreachable!()
+    }
+}
+
+/// Pause a source.
+pub fn pause(al_source: u32) -> () {
+    check_openal_context!(());
+
+    al::alSourcePause(al_source)
+}
+
+/// Set the volume of a source, relative to its Bus if any.
+pub fn set_volume(al_source: u32, bus: &Option<Bus>, volume: f32) -> () {
+    check_openal_context!(());
+
+    match *bus {
+        Some(ref b) => bus::set_source_volume(b, al_source, volume),
+        None        => al::alSourcef(al_source, ffi::AL_GAIN, volume)
+    }
+}
+
+/// Get the volume of a source, relative to its Bus if any.
+pub fn get_volume(al_source: u32, bus: &Option<Bus>) -> f32 {
+    check_openal_context!(0.);
+
+    match *bus {
+        Some(ref b) => return bus::get_source_volume(b, al_source),
+        None        => {}
+    }
+
+    let mut volume : f32 = 0.;
+    al::alGetSourcef(al_source, ffi::AL_GAIN, &mut volume);
+    volume
+}
+
+/// Set the pitch of a source, relative to its Bus if any.
+pub fn set_pitch(al_source: u32, bus: &Option<Bus>, pitch: f32) -> () {
+    check_openal_context!(());
+
+    match *bus {
+        Some(ref b) => bus::set_source_pitch(b, al_source, pitch),
+        None        => al::alSourcef(al_source, ffi::AL_PITCH, pitch)
+    }
+}
+
+/// Get the pitch of a source, relative to its Bus if any.
+pub fn get_pitch(al_source: u32, bus: &Option<Bus>) -> f32 {
+    check_openal_context!(0.);
+
+    match *bus {
+        Some(ref b) => return bus::get_source_pitch(b, al_source),
+        None        => {}
+    }
+
+    let mut pitch = 0.;
+    al::alGetSourcef(al_source, ffi::AL_PITCH, &mut pitch);
+    pitch
+}
+
+/// Set a float parameter of a source.
+pub fn set_float(al_source: u32, param: i32, value: f32) -> () {
+    check_openal_context!(());
+
+    al::alSourcef(al_source, param, value);
+}
+
+/// Get a float parameter of a source, or `default` without context.
+pub fn get_float(al_source: u32, param: i32, default: f32) -> f32 {
+    check_openal_context!(default);
+
+    let mut value = 0.;
+    al::alGetSourcef(al_source, param, &mut value);
+    value
+}
+
+/// Set a boolean parameter of a source.
+pub fn set_bool(al_source: u32, param: i32, value: bool) -> () {
+    check_openal_context!(());
+
+    match value {
+        true    => al::alSourcei(al_source, param, ffi::ALC_TRUE as i32),
+        false   => al::alSourcei(al_source, param, ffi::ALC_FALSE as i32)
+    };
+}
+
+/// Get a boolean parameter of a source.
+pub fn get_bool(al_source: u32, param: i32) -> bool {
+    check_openal_context!(false);
+
+    let mut boolean = 0;
+    al::alGetSourcei(al_source, param, &mut boolean);
+    match boolean as i8 {
+        ffi::ALC_TRUE  => true,
+        ffi::ALC_FALSE => false,
+        _              => unreachable!()
+    }
+}
+
+/// Set a vector parameter of a source.
+pub fn set_vector(al_source: u32, param: i32, value: [f32, ..3]) -> () {
+    check_openal_context!(());
+
+    al::alSourcefv(al_source, param, &value[0]);
+}
+
+/// Get a vector parameter of a source.
+pub fn get_vector(al_source: u32, param: i32) -> [f32, ..3] {
+    check_openal_context!([0., ..3]);
+
+    let mut value : [f32, ..3] = [0., ..3];
+    al::alGetSourcefv(al_source, param, &mut value[0]);
+    value
+}
@@ -0,0 +1,588 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Stream samples progressively into an OpenAL source.
+
+use std::io::timer::sleep;
+use std::task;
+use std::u64;
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, SeqCst};
+use std::vec::Vec;
+use std::mem;
+use std::cell::RefCell;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use decoder::Decoder;
+use marker::Marker;
+use sample::{SampleType, SampleBuffer, Int16};
+use marker;
+use channels::ChannelPlan;
+use channels;
+use load_options::LoadOptions;
+
+local_data_key!(synchronous_streams: RefCell<Vec<(Streamer, Sender<Streamer>)>>)
+
+/// The events sent by a streaming task.
+#[deriving(Clone, PartialEq, Show)]
+pub enum StreamEvent {
+    /// A track started to play, with the index of the track
+    TrackStarted(uint),
+    /// The playback crossed a marker
+    MarkerReached(Marker)
+}
+
+/// The provider of a reloaded file and the markers of the file.
+pub type ReloadedFile = (Box<StreamProvider + Send>, Vec<Marker>);
+
+/// Something which provides samples to a streaming source.
+pub trait StreamProvider {
+    /**
+     * Read interleaved samples.
+     *
+     * # Argument
+     * * `samples` - The buffer to fill, of the type given by
+     * get_sample_type
+     *
+     * # Return
+     * The count of samples written, 0 at the end of the current track.
+     */
+    fn read(&mut self, samples: &mut SampleBuffer) -> uint;
+
+    /// The type of the samples returned by read.
+    fn get_sample_type(&self) -> SampleType {
+        Int16
+    }
+
+    /// The OpenAL format of the samples returned by read.
+    fn get_format(&self) -> i32;
+
+    /// The sample rate of the samples returned by read.
+    fn get_samplerate(&self) -> i32;
+
+    /// Go back to the beginning of the stream.
+    fn rewind(&mut self) -> ();
+
+    /**
+     * Go to the next track of the stream.
+     *
+     * # Return
+     * true if there is a next track, false if the stream is over.
+     */
+    fn next_track(&mut self) -> bool {
+        false
+    }
+
+    /// The index of the track returned by read.
+    fn get_track(&self) -> uint {
+        0
+    }
+}
+
+/// Provide the samples of a sound file.
+pub struct FileProvider {
+    file: Box<Decoder + Send>,
+    plan: ChannelPlan,
+    format: i32,
+    sample_type: SampleType,
+    samplerate: i32
+}
+
+impl FileProvider {
+    /**
+     * Create a new FileProvider.
+     *
+     * # Arguments
+     * * `file` - The file to stream
+     * * `options` - How to load the samples
+     *
+     * # Return
+     * Some(FileProvider) if the format of the file is supported by OpenAL,
+     * None otherwise.
+     */
+    pub fn new(file: Box<Decoder + Send>,
+               options: &LoadOptions) -> Option<FileProvider> {
+        let infos = file.get_sndinfo();
+        let (format, sample_type, plan) = match channels::negotiate(&*file, options) {
+            Some(negotiated) => negotiated,
+            None      => {
+                println!("Internal error : unrecognized format.");
+                return None;
+            }
+        };
+
+        Some(FileProvider {
+            file: file,
+            plan: plan,
+            format: format,
+            sample_type: sample_type,
+            samplerate: infos.samplerate
+        })
+    }
+
+    /// Get the count of channels sent to OpenAL.
+    pub fn get_channels(&self) -> uint {
+        self.plan.get_output_channels()
+    }
+}
+
+impl StreamProvider for FileProvider {
+    fn read(&mut self, samples: &mut SampleBuffer) -> uint {
+        samples.read_from(&mut *self.file, &self.plan)
+    }
+
+    fn get_sample_type(&self) -> SampleType {
+        self.sample_type.clone()
+    }
+
+    fn get_format(&self) -> i32 {
+        self.format
+    }
+
+    fn get_samplerate(&self) -> i32 {
+        self.samplerate
+    }
+
+    fn rewind(&mut self) -> () {
+        self.file.rewind();
+    }
+}
+
+/// Fill and queue the buffers of a streaming source.
+pub struct Streamer {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The internal OpenAL buffers
+    al_buffers: Vec<u32>,
+    /// Where the samples come from
+    provider: Box<StreamProvider + Send>,
+    /// Temporary storage for the samples
+    samples: SampleBuffer,
+    /// The buffers queued on the source, with the track and the count of
+    /// frames of their samples
+    queue: Vec<(u32, uint, u64)>,
+    /// The frames played in the buffers already unqueued
+    played: u64,
+    /// The markers to notify
+    markers: Option<Arc<Mutex<Vec<Marker>>>>,
+    /// The position of the last markers check
+    marker_position: Option<u64>,
+    /// The frames streamed before the beginning of the current file
+    origin: u64,
+    /// The format and the sample rate of the queued buffers
+    format: (i32, i32),
+    /// The track currently playing
+    track: Option<uint>,
+    /// The next track has a different format, wait for the queue to drain
+    format_changed: bool,
+    /// The provider has no more samples
+    end: bool,
+    /// Set by the owner of the source to terminate the stream
+    stop: Arc<AtomicBool>,
+    /// Where to send the stream events
+    events: Option<Sender<StreamEvent>>,
+    /// Receive the providers of the reloaded files
+    reload: Option<Receiver<ReloadedFile>>
+}
+
+impl Streamer {
+    /**
+     * Create a new Streamer.
+     *
+     * # Arguments
+     * * `al_source` - The source to stream in
+     * * `al_buffers` - The buffers used to stream
+     * * `provider` - Where the samples come from
+     * * `sample_to_read` - The count of samples per buffer
+     */
+    pub fn new(al_source: u32,
+               al_buffers: Vec<u32>,
+               provider: Box<StreamProvider + Send>,
+               sample_to_read: uint) -> Streamer {
+        let samples = SampleBuffer::new(provider.get_sample_type(), sample_to_read);
+        Streamer {
+            al_source: al_source,
+            al_buffers: al_buffers,
+            provider: provider,
+            samples: samples,
+            queue: Vec::new(),
+            played: 0,
+            markers: None,
+            marker_position: None,
+            origin: 0,
+            format: (0, 0),
+            track: None,
+            format_changed: false,
+            end: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            events: None,
+            reload: None
+        }
+    }
+
+    /// Get the provider of the Streamer.
+    pub fn get_provider<'r>(&'r mut self) -> &'r mut Box<StreamProvider + Send> {
+        &mut self.provider
+    }
+
+    /// Get the flag to set to terminate the stream.
+    pub fn get_stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /**
+     * Set where to send the stream events.
+     *
+     * # Argument
+     * * `events` - The sender for the events
+     */
+    pub fn set_events(&mut self, events: Sender<StreamEvent>) -> () {
+        self.events = Some(events);
+    }
+
+    /**
+     * Set where to receive the providers of the reloaded files.
+     *
+     * The new provider replaces the current one once the queued buffers
+     * are played, from the beginning of the file. The markers are replaced
+     * by the markers of the new file.
+     *
+     * # Argument
+     * * `reload` - The receiver for the new providers and markers
+     */
+    pub fn set_reload(&mut self, reload: Receiver<ReloadedFile>) -> () {
+        self.reload = Some(reload);
+    }
+
+    /// Replace the provider and the markers if the file was reloaded.
+    fn check_reload(&mut self) -> () {
+        let (provider, markers) = match self.reload {
+            Some(ref reload) => match reload.try_recv() {
+                Ok(reloaded) => reloaded,
+                Err(_)       => return
+            },
+            None             => return
+        };
+        match self.markers {
+            Some(ref current) => *current.lock() = markers,
+            None              => {}
+        }
+        // The new file starts after the queued buffers
+        let queued = self.queue.iter().fold(0, |sum, &(_, _, frames)| sum + frames);
+        self.origin = self.played + queued;
+        self.marker_position = None;
+        self.provider = provider;
+        self.end = false;
+        let format = (self.provider.get_format(),
+                      self.provider.get_samplerate());
+        if format != self.format {
+            if self.queue.is_empty() {
+                self.format = format;
+            } else {
+                self.format_changed = true;
+            }
+        }
+    }
+
+    /**
+     * Set the markers to notify with MarkerReached events.
+     *
+     * # Argument
+     * * `markers` - The markers, sorted by position in frames
+     */
+    pub fn set_markers(&mut self, markers: Arc<Mutex<Vec<Marker>>>) -> () {
+        self.markers = Some(markers);
+    }
+
+    /// Send the events of the markers crossed since the last check.
+    fn check_markers(&mut self, end: bool) -> () {
+        let markers = match self.markers {
+            Some(ref markers) => markers.clone(),
+            None              => return
+        };
+        let position = if end {
+            u64::MAX
+        } else {
+            if al::alGetState(self.al_source) != ffi::AL_PLAYING {
+                return;
+            }
+            let mut offset = 0;
+            al::alGetSourcei(self.al_source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+            let position = self.played + offset as u64;
+            // The end of the previous file is still playing
+            if position < self.origin {
+                return;
+            }
+            position - self.origin
+        };
+        let crossed = marker::crossed(markers.lock().as_slice(),
+                                      self.marker_position,
+                                      position);
+        self.marker_position = Some(position);
+        for m in crossed.into_iter() {
+            self.send_event(MarkerReached(m));
+        }
+    }
+
+    fn send_event(&self, event: StreamEvent) -> () {
+        match self.events {
+            Some(ref sender) => { let _ = sender.send_opt(event); },
+            None             => {}
+        }
+    }
+
+    /**
+     * Fill a buffer with the next samples of the provider.
+     *
+     * # Return
+     * true if the buffer is filled and ready to be queued, false otherwise.
+     */
+    fn fill(&mut self, buffer: u32) -> bool {
+        loop {
+            if self.end || self.format_changed {
+                return false;
+            }
+
+            let sample_type = self.provider.get_sample_type();
+            if self.samples.get_type() != sample_type {
+                self.samples = SampleBuffer::new(sample_type, self.samples.len());
+            }
+            let read = self.provider.read(&mut self.samples);
+            if read == 0 {
+                if !self.provider.next_track() {
+                    self.end = true;
+                    return false;
+                }
+                let format = (self.provider.get_format(),
+                              self.provider.get_samplerate());
+                if format != self.format {
+                    if self.queue.is_empty() {
+                        self.format = format;
+                    } else {
+                        self.format_changed = true;
+                    }
+                }
+                continue;
+            }
+
+            let (format, samplerate) = self.format;
+            al::alBufferData(buffer,
+                             format,
+                             self.samples.as_ptr(),
+                             self.samples.byte_size(read) as i32,
+                             samplerate);
+            let mut channels = 1;
+            al::alGetBufferi(buffer, ffi::AL_CHANNELS, &mut channels);
+            self.queue.push((buffer,
+                             self.provider.get_track(),
+                             (read / channels as uint) as u64));
+            return true;
+        }
+    }
+
+    /// Send an event if the track at the head of the queue has changed.
+    fn check_track(&mut self) -> () {
+        let head = match self.queue.as_slice().head() {
+            Some(&(_, track, _)) => track,
+            None              => return
+        };
+        if self.track != Some(head) {
+            self.track = Some(head);
+            self.send_event(TrackStarted(head));
+        }
+    }
+
+    /**
+     * Fill and queue all the buffers.
+     *
+     * The source must be stopped or in its initial state.
+     */
+    pub fn prefill(&mut self) -> () {
+        // Back to the initial state, so a scheduled start is not
+        // mistaken for the end of the stream.
+        al::alSourceRewind(self.al_source);
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        self.queue.clear();
+        self.check_reload();
+        self.played = 0;
+        self.origin = 0;
+        self.marker_position = None;
+        self.track = None;
+        self.end = false;
+        self.format_changed = false;
+        self.stop.store(false, SeqCst);
+        self.format = (self.provider.get_format(),
+                       self.provider.get_samplerate());
+
+        let buffers = self.al_buffers.clone();
+        for buffer in buffers.iter() {
+            if self.fill(*buffer) {
+                al::alSourceQueueBuffers(self.al_source, 1, buffer);
+            }
+        }
+        self.check_track();
+    }
+
+    /**
+     * Refill the processed buffers.
+     *
+     * # Return
+     * false when the stream is over, true otherwise.
+     */
+    pub fn step(&mut self) -> bool {
+        if self.stop.load(SeqCst) {
+            return false;
+        }
+        self.check_reload();
+
+        // The sample offset counts the processed buffers still queued
+        self.check_markers(false);
+
+        let mut processed = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+        while processed > 0 {
+            let mut buffer = 0;
+            al::alSourceUnqueueBuffers(self.al_source, 1, &mut buffer);
+            match self.queue.remove(0) {
+                Some((_, _, frames)) => self.played += frames,
+                None                 => {}
+            }
+            if self.fill(buffer) {
+                al::alSourceQueueBuffers(self.al_source, 1, &buffer);
+            }
+            processed -= 1;
+        }
+        self.check_track();
+
+        match al::alGetState(self.al_source) {
+            ffi::AL_STOPPED if self.format_changed => {
+                // All the samples of the previous format are played,
+                // restart the source with the new format.
+                al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+                for &(_, _, frames) in self.queue.iter() {
+                    self.played += frames;
+                }
+                self.queue.clear();
+                self.format_changed = false;
+                self.format = (self.provider.get_format(),
+                               self.provider.get_samplerate());
+                let buffers = self.al_buffers.clone();
+                for buffer in buffers.iter() {
+                    if self.fill(*buffer) {
+                        al::alSourceQueueBuffers(self.al_source, 1, buffer);
+                    }
+                }
+                self.check_track();
+                al::alSourcePlay(self.al_source);
+                true
+            },
+            ffi::AL_STOPPED => {
+                if self.end {
+                    self.check_markers(true);
+                }
+                false
+            },
+            _               => true
+        }
+    }
+}
+
+/**
+ * Stream in a new task until the end of the stream.
+ *
+ * The Streamer must be prefilled and the source playing or scheduled.
+ *
+ * # Argument
+ * * `streamer` - The Streamer to run
+ *
+ * # Return
+ * A Receiver to get back the Streamer at the end of the stream.
+ */
+pub fn spawn(streamer: Streamer) -> Receiver<Streamer> {
+    let (sender, receiver) = channel();
+
+    if synchronous_streams.get().is_some() {
+        with_synchronous_streams(|streams| streams.push((streamer, sender)));
+        return receiver;
+    }
+
+    task::spawn(proc() {
+        match OpenAlData::check_al_context() {
+            Ok(_)       => {},
+            Err(err)    => { println!("{}", err);}
+        };
+
+        let mut streamer = streamer;
+        while streamer.step() {
+            // wait a bit
+            sleep(Duration::milliseconds(50i64));
+        }
+        al::alSourcei(streamer.al_source, ffi::AL_BUFFER, 0);
+        let _ = sender.send_opt(streamer);
+    });
+    receiver
+}
+
+/// Call a function with the Streamers stepped by `pump`.
+fn with_synchronous_streams<T>(f: |&mut Vec<(Streamer, Sender<Streamer>)>| -> T) -> T {
+    if synchronous_streams.get().is_none() {
+        synchronous_streams.replace(Some(RefCell::new(Vec::new())));
+    }
+    let streams = synchronous_streams.get().unwrap();
+    let mut streams = streams.borrow_mut();
+    f(&mut *streams)
+}
+
+/**
+ * Step the Streamers in the current task instead of streaming tasks.
+ *
+ * Once enabled, `spawn` keeps the Streamers in the task and `pump` must be
+ * called to refill their buffers, for example between two renderings of
+ * the loopback device.
+ */
+pub fn set_synchronous() -> () {
+    with_synchronous_streams(|_| ());
+}
+
+/**
+ * Refill the buffers of the synchronous Streamers.
+ *
+ * The Streamers at the end of their stream are sent back to their owner.
+ * Does nothing if the Streamers run in their own tasks.
+ */
+pub fn pump() -> () {
+    if synchronous_streams.get().is_none() {
+        return;
+    }
+    let streams = with_synchronous_streams(|streams| mem::replace(streams, Vec::new()));
+    let mut running = Vec::new();
+    for (mut streamer, sender) in streams.into_iter() {
+        if streamer.step() {
+            running.push((streamer, sender));
+        } else {
+            al::alSourcei(streamer.al_source, ffi::AL_BUFFER, 0);
+            let _ = sender.send_opt(streamer);
+        }
+    }
+    with_synchronous_streams(|streams| streams.extend(running.into_iter()));
+}
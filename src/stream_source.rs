@@ -0,0 +1,771 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Play samples generated by the program.
+
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, SeqCst};
+use std::time::Duration;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use states::{State, Playing, Paused};
+use audio_controller::AudioController;
+use stream::{Streamer, StreamProvider};
+use stream;
+use sample::{SampleType, SampleBuffer};
+use channels::{LayoutMono, LayoutStereo};
+use channels;
+use bus::Bus;
+use bus;
+use source_control;
+use clock;
+
+/// The default sample rate of a StreamSource.
+static DEFAULT_SAMPLERATE: i32 = 44100;
+
+/// The count of frames of each streaming buffer.
+static FRAMES_PER_BUFFER: uint = 4096;
+
+/// The count of streaming buffers.
+static BUFFERS: uint = 4;
+
+/**
+ * Something which generates samples, like a synthesizer.
+ *
+ * The samples are interleaved floats from -1 to 1. A SampleSource is
+ * played by a StreamSource, in the streaming task.
+ */
+pub trait SampleSource {
+    /// The count of channels of the samples, 1 or 2.
+    fn get_channels(&self) -> uint {
+        1
+    }
+
+    /**
+     * Generate the next frames.
+     *
+     * # Arguments
+     * * `buffer` - The interleaved samples to fill, a whole number of frames
+     * * `samplerate` - The sample rate of the frames
+     *
+     * # Return
+     * The count of frames written, 0 when the source has no more samples.
+     */
+    fn fill(&mut self, buffer: &mut [f32], samplerate: i32) -> uint;
+
+    /// Go back to the beginning, called each time the StreamSource is played.
+    fn rewind(&mut self) -> () {}
+}
+
+/// Provide the samples of a SampleSource to a Streamer.
+struct SourceProvider {
+    source: Box<SampleSource + Send>,
+    samplerate: i32,
+    channels: uint,
+    format: i32,
+    sample_type: SampleType,
+    frames: Vec<f32>
+}
+
+impl SourceProvider {
+    fn new(source: Box<SampleSource + Send>, samplerate: i32) -> Option<SourceProvider> {
+        let channels = source.get_channels();
+        let layout = match channels {
+            1 => LayoutMono,
+            2 => LayoutStereo,
+            _ => {
+                println!("Error: a SampleSource must have 1 or 2 channels, not {}.", channels);
+                return None;
+            }
+        };
+        let (format, sample_type) = match al::negotiate_format(layout, None) {
+            Some(negotiated) => negotiated,
+            None             => return None
+        };
+
+        Some(SourceProvider {
+            source: source,
+            samplerate: samplerate,
+            channels: channels,
+            format: format,
+            sample_type: sample_type,
+            frames: Vec::new()
+        })
+    }
+}
+
+impl StreamProvider for SourceProvider {
+    fn read(&mut self, samples: &mut SampleBuffer) -> uint {
+        let len = samples.len() - samples.len() % self.channels;
+        if self.frames.len() != len {
+            self.frames = Vec::from_elem(len, 0f32);
+        }
+        let frames = self.source.fill(self.frames.as_mut_slice(), self.samplerate);
+        let count = cmp::min(frames * self.channels, len);
+        samples.copy_from_f32(self.frames.slice_to(count));
+        count
+    }
+
+    fn get_sample_type(&self) -> SampleType {
+        self.sample_type.clone()
+    }
+
+    fn get_format(&self) -> i32 {
+        self.format
+    }
+
+    fn get_samplerate(&self) -> i32 {
+        self.samplerate
+    }
+
+    fn rewind(&mut self) -> () {
+        self.source.rewind();
+    }
+}
+
+/**
+ * Play the samples of a SampleSource.
+ *
+ * A StreamSource streams the samples like a Music streams a file: the
+ * samples are generated progressively in the streaming task. It can be
+ * positioned, assigned to a Bus and scheduled like the Musics.
+ *
+ * # Example
+ * ```Rust
+ * extern crate ears;
+ * use std::num::FloatMath;
+ * use ears::{SampleSource, StreamSource, AudioController};
+ *
+ * struct Hum {
+ *     phase: f32
+ * }
+ *
+ * impl SampleSource for Hum {
+ *     fn fill(&mut self, buffer: &mut [f32], samplerate: i32) -> uint {
+ *         for sample in buffer.iter_mut() {
+ *             *sample = self.phase.sin() * 0.2;
+ *             self.phase += 2. * Float::pi() * 50. / samplerate as f32;
+ *         }
+ *         buffer.len()
+ *     }
+ * }
+ *
+ * fn main() -> () {
+ *     let mut hum = StreamSource::new(box Hum { phase: 0. }).unwrap();
+ *     hum.set_position([2., 0., 0.]);
+ *     hum.play();
+ * }
+ * ```
+ */
+pub struct StreamSource {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The internal OpenAL buffers
+    al_buffers: Vec<u32>,
+    /// The Streamer of the source, when the StreamSource is not streaming
+    streamer: Option<Streamer>,
+    /// Receive the Streamer at the end of the streaming task
+    streamer_receiver: Option<Receiver<Streamer>>,
+    /// Set to terminate the streaming task
+    stop_flag: Arc<AtomicBool>,
+    /// The sample rate of the generated samples
+    samplerate: i32,
+    /// The count of channels of the generated samples
+    channels: uint,
+    /// The Bus the StreamSource is assigned to
+    bus: Option<Bus>,
+    /// The warning about the positioning of several channels was printed
+    unpositioned_warned: bool
+}
+
+impl StreamSource {
+    /**
+     * Create a new StreamSource at 44100 Hz.
+     *
+     * # Argument
+     * * `source` - The SampleSource to play
+     *
+     * # Return
+     * An Option containing Some(StreamSource) on success, None otherwise
+     */
+    pub fn new(source: Box<SampleSource + Send>) -> Option<StreamSource> {
+        StreamSource::new_with_samplerate(source, DEFAULT_SAMPLERATE)
+    }
+
+    /**
+     * Create a new StreamSource.
+     *
+     * # Arguments
+     * * `source` - The SampleSource to play
+     * * `samplerate` - The sample rate the source generates its samples at
+     *
+     * # Return
+     * An Option containing Some(StreamSource) on success, None otherwise
+     */
+    pub fn new_with_samplerate(source: Box<SampleSource + Send>,
+                               samplerate: i32) -> Option<StreamSource> {
+        check_openal_context!(None);
+
+        let provider = match SourceProvider::new(source, samplerate) {
+            Some(provider) => box provider,
+            None           => return None
+        };
+        let channels = provider.channels;
+
+        let mut source_id = 0;
+        let mut buffer_ids = Vec::from_elem(BUFFERS, 0u32);
+        al::alGenSources(1, &mut source_id);
+        al::alGenBuffers(BUFFERS as i32, buffer_ids.as_mut_ptr());
+
+        match al::openal_has_error() {
+            Some(err) => { println!("{}", err); return None; },
+            None => {}
+        };
+
+        let streamer = Streamer::new(source_id,
+                                     buffer_ids.clone(),
+                                     provider as Box<StreamProvider + Send>,
+                                     FRAMES_PER_BUFFER * channels);
+
+        Some(StreamSource {
+            al_source: source_id,
+            al_buffers: buffer_ids,
+            stop_flag: streamer.get_stop_flag(),
+            streamer: Some(streamer),
+            streamer_receiver: None,
+            samplerate: samplerate,
+            channels: channels,
+            bus: None,
+            unpositioned_warned: false
+        })
+    }
+
+    /// Get the sample rate of the generated samples.
+    pub fn get_samplerate(&self) -> i32 {
+        self.samplerate
+    }
+
+    /// Get the count of channels of the generated samples.
+    pub fn get_channels(&self) -> uint {
+        self.channels
+    }
+
+    /// Get back the Streamer, wait for the end of the streaming task if needed.
+    fn take_streamer(&mut self) -> Option<Streamer> {
+        match self.streamer.take() {
+            Some(streamer) => Some(streamer),
+            None           => match self.streamer_receiver.take() {
+                Some(receiver) => {
+                    // The synchronous Streamers stop at their next step
+                    stream::pump();
+                    receiver.recv_opt().ok()
+                },
+                None           => None
+            }
+        }
+    }
+
+    fn process_stream(&mut self, start: Option<Duration>) -> () {
+        // Terminate a streaming task still waiting for a scheduled start
+        self.stop_flag.store(true, SeqCst);
+        let mut streamer = match self.take_streamer() {
+            Some(streamer) => streamer,
+            None           => return
+        };
+
+        // Generate the samples from the beginning
+        streamer.get_provider().rewind();
+        streamer.prefill();
+
+        match start {
+            Some(time) => clock::schedule_play(self.al_source,
+                                               time,
+                                               self.samplerate,
+                                               None),
+            None       => al::alSourcePlay(self.al_source)
+        }
+
+        self.streamer_receiver = Some(stream::spawn(streamer));
+    }
+
+    /**
+     * Assign the StreamSource to a Bus.
+     *
+     * The StreamSource keeps its volume and its pitch, which are now relative
+     * to the Bus.
+     *
+     * # Argument
+     * `bus` - The Bus to assign the StreamSource to.
+     */
+    pub fn set_bus(&mut self, bus: &Bus) -> () {
+        check_openal_context!(());
+
+        let volume = self.get_volume();
+        let pitch = self.get_pitch();
+        self.remove_bus();
+        bus::add_source(bus, self.al_source, volume, pitch);
+        self.bus = Some(bus.clone());
+    }
+
+    /**
+     * Remove the StreamSource from its Bus.
+     *
+     * The volume and the pitch of the StreamSource are no longer modified by
+     * the Bus.
+     */
+    pub fn remove_bus(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+    }
+
+    /**
+     * Get the Bus of the StreamSource.
+     *
+     * # Return
+     * Some(Bus) if the StreamSource is assigned to a Bus, None otherwise.
+     */
+    pub fn get_bus(&self) -> Option<Bus> {
+        self.bus.clone()
+    }
+
+    /**
+     * Play the StreamSource at a time of the audio clock.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to play the StreamSource at
+     */
+    pub fn play_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        self.stop();
+        self.process_stream(Some(time));
+    }
+
+    /**
+     * Stop the StreamSource at a time of the audio clock.
+     *
+     * The stop is triggered by `ears::update`.
+     *
+     * # Argument
+     * * `time` - The time of the audio clock to stop the StreamSource at
+     */
+    pub fn stop_at(&mut self, time: Duration) -> () {
+        check_openal_context!(());
+
+        clock::schedule_stop(self.al_source, time);
+    }
+}
+
+impl AudioController for StreamSource {
+    /**
+     * Play or resume the StreamSource.
+     *
+     * The SampleSource is rewound unless the StreamSource is paused.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.get_state() {
+            Paused => { al::alSourcePlay(self.al_source); return; },
+            _      => {
+                if self.is_playing() {
+                    self.stop();
+                }
+                self.process_stream(None);
+            }
+        }
+    }
+
+    /**
+     * Pause the StreamSource.
+     */
+    fn pause(&mut self) -> () {
+        source_control::pause(self.al_source)
+    }
+
+    /**
+     * Stop the StreamSource.
+     *
+     * The scheduled playbacks of the StreamSource are cancelled.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        clock::cancel(self.al_source);
+        self.stop_flag.store(true, SeqCst);
+        al::alSourceStop(self.al_source);
+        // wait for the streaming task to terminate
+        self.streamer = self.take_streamer();
+    }
+
+    /**
+     * Check if the StreamSource is playing or not.
+     *
+     * # Return
+     * True if the StreamSource is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the StreamSource
+     *
+     * # Return
+     * The state of the StreamSource as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        source_control::get_state(self.al_source)
+    }
+
+    /**
+     * Set the volume of the StreamSource.
+     *
+     * # Argument
+     * * `volume` - The volume of the StreamSource, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        source_control::set_volume(self.al_source, &self.bus, volume)
+    }
+
+    /**
+     * Get the volume of the StreamSource.
+     *
+     * # Return
+     * The volume of the StreamSource between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        source_control::get_volume(self.al_source, &self.bus)
+    }
+
+    /**
+     * Set the minimal volume for a StreamSource.
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume, between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MIN_GAIN, min_volume)
+    }
+
+    /**
+     * Get the minimal volume of the StreamSource.
+     *
+     * # Return
+     * The minimal volume of the StreamSource between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MIN_GAIN, 0.)
+    }
+
+    /**
+     * Set the maximal volume for a StreamSource.
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume, between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MAX_GAIN, max_volume)
+    }
+
+    /**
+     * Get the maximal volume of the StreamSource.
+     *
+     * # Return
+     * The maximal volume of the StreamSource between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MAX_GAIN, 0.)
+    }
+
+    /**
+     * Set the StreamSource looping or not
+     *
+     * A StreamSource only stops when its SampleSource has no more samples,
+     * the looping is kept for the AudioController interface.
+     *
+     * # Arguments
+     * `looping` - The new looping state.
+     */
+    fn set_looping(&mut self, looping: bool) -> () {
+        source_control::set_bool(self.al_source, ffi::AL_LOOPING, looping)
+    }
+
+    /**
+     * Check if the StreamSource is looping or not
+     *
+     * # Return
+     * True if the StreamSource is looping, false otherwise.
+     */
+    fn is_looping(&self) -> bool {
+        source_control::get_bool(self.al_source, ffi::AL_LOOPING)
+    }
+
+    /**
+     * Set the pitch of the StreamSource.
+     *
+     * # Argument
+     * * `pitch` - The new pitch of the StreamSource in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        source_control::set_pitch(self.al_source, &self.bus, pitch)
+    }
+
+    /**
+     * Get the pitch of the StreamSource.
+     *
+     * # Return
+     * The pitch of the StreamSource in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        source_control::get_pitch(self.al_source, &self.bus)
+    }
+
+    /**
+     * Set the position of the StreamSource relative to the listener or
+     * absolute.
+     *
+     * # Argument
+     * `relative` - True to set the StreamSource relative to the listener,
+     * false to set its position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        source_control::set_bool(self.al_source, ffi::AL_SOURCE_RELATIVE, relative)
+    }
+
+    /**
+     * Is the StreamSource relative to the listener or not ?
+     *
+     * # Return
+     * True if the StreamSource is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        source_control::get_bool(self.al_source, ffi::AL_SOURCE_RELATIVE)
+    }
+
+    /**
+     * Set the StreamSource location in three dimensional space.
+     *
+     * Only the mono SampleSources are positioned.
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the
+     * position of the StreamSource [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32, ..3]) -> () {
+        source_control::set_vector(self.al_source, ffi::AL_POSITION, position);
+
+        if self.channels > 1 && !self.unpositioned_warned && !self.is_relative() {
+            channels::warn_unpositioned("StreamSource", self.channels);
+            self.unpositioned_warned = true;
+        }
+    }
+
+    /**
+     * Get the position of the StreamSource in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * StreamSource [x, y, z].
+     */
+    fn get_position(&self) -> [f32, ..3] {
+        source_control::get_vector(self.al_source, ffi::AL_POSITION)
+    }
+
+    /**
+     * Set the direction of the StreamSource.
+     *
+     * # Argument
+     * `direction` - The new direction of the StreamSource.
+     */
+    fn set_direction(&mut self, direction: [f32, ..3]) -> () {
+        source_control::set_vector(self.al_source, ffi::AL_DIRECTION, direction)
+    }
+
+    /**
+     * Get the direction of the StreamSource.
+     *
+     * # Return
+     * The current direction of the StreamSource.
+     */
+    fn get_direction(&self)  -> [f32, ..3] {
+        source_control::get_vector(self.al_source, ffi::AL_DIRECTION)
+    }
+
+    /**
+     * Set the maximum distance of the StreamSource.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_MAX_DISTANCE, max_distance)
+    }
+
+    /**
+     * Get the maximum distance of the StreamSource.
+     *
+     * # Return
+     * The maximum distance of the StreamSource in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_MAX_DISTANCE, 0.)
+    }
+
+    /**
+     * Set the reference distance of the StreamSource.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the StreamSource.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance)
+    }
+
+    /**
+     * Get the reference distance of the StreamSource.
+     *
+     * # Return
+     * The current reference distance of the StreamSource.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_REFERENCE_DISTANCE, 1.)
+    }
+
+    /**
+     * Set the attenuation of a StreamSource.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the StreamSource in the range
+     * [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        source_control::set_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation)
+    }
+
+    /**
+     * Get the attenuation of a StreamSource.
+     *
+     * # Return
+     * The current attenuation for the StreamSource in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        source_control::get_float(self.al_source, ffi::AL_ROLLOFF_FACTOR, 1.)
+    }
+}
+
+impl Drop for StreamSource {
+    /// Destroy all the resources of the StreamSource.
+    fn drop(&mut self) -> () {
+        match self.bus.take() {
+            Some(bus) => bus::remove_source(&bus, self.al_source),
+            None      => {}
+        }
+        clock::cancel(self.al_source);
+        self.stop_flag.store(true, SeqCst);
+        al::alSourceStop(self.al_source);
+        self.take_streamer();
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        al::alDeleteBuffers(BUFFERS as i32, self.al_buffers.as_mut_ptr());
+        al::alDeleteSources(1, &mut self.al_source);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case_functions)]
+
+    use std::cmp;
+    use stream::StreamProvider;
+    use stream_source::{SampleSource, StreamSource, SourceProvider};
+    use sample::{SampleBuffer, Int16};
+    use states::{Playing, Stopped};
+    use audio_controller::AudioController;
+
+    /// Generate `remaining` frames of a constant value.
+    struct Constant {
+        remaining: uint
+    }
+
+    impl SampleSource for Constant {
+        fn get_channels(&self) -> uint {
+            2
+        }
+
+        fn fill(&mut self, buffer: &mut [f32], _: i32) -> uint {
+            let frames = cmp::min(buffer.len() / 2, self.remaining);
+            for sample in buffer.slice_to_mut(frames * 2).iter_mut() {
+                *sample = 0.5;
+            }
+            self.remaining -= frames;
+            frames
+        }
+    }
+
+    #[test]
+    fn source_provider_read_OK() -> () {
+        let mut provider = SourceProvider::new(box Constant { remaining: 3 }, 100).unwrap();
+        let mut samples = SampleBuffer::new(Int16, 4);
+        assert_eq!(provider.read(&mut samples), 4);
+        assert_eq!(provider.read(&mut samples), 2);
+        assert_eq!(provider.read(&mut samples), 0);
+    }
+
+    #[test]
+    fn stream_source_create_OK() -> () {
+        let source = StreamSource::new(box Constant { remaining: 100 }).unwrap();
+        assert_eq!(source.get_channels(), 2);
+        assert_eq!(source.get_samplerate(), 44100);
+    }
+
+    #[test]
+    fn stream_source_play_stop_OK() -> () {
+        let mut source = StreamSource::new(box Constant { remaining: 44100 }).unwrap();
+        source.play();
+        assert_eq!(source.get_state() as i32, Playing as i32);
+        source.stop();
+        assert_eq!(source.get_state() as i32, Stopped as i32);
+    }
+
+    #[test]
+    fn stream_source_set_volume_OK() -> () {
+        let mut source = StreamSource::new(box Constant { remaining: 100 }).unwrap();
+        source.set_volume(0.7);
+        assert_eq!(source.get_volume(), 0.7);
+    }
+
+    #[test]
+    fn stream_source_set_position_OK() -> () {
+        let mut source = StreamSource::new(box Constant { remaining: 100 }).unwrap();
+        source.set_relative(true);
+        source.set_position([50., 150., 250.]);
+        assert_eq!(source.get_position(), [50., 150., 250.]);
+    }
+}
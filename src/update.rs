@@ -0,0 +1,77 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2014 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The ears update loop.
+
+use std::time::Duration;
+use time;
+
+use internal::OpenAlData;
+use oneshot;
+use clock;
+use ducking;
+use loader;
+use hot_reload;
+
+local_data_key!(last_update: u64)
+
+/**
+ * Update the internal state of ears.
+ *
+ * This function recycle the finished one-shot sounds, evaluate the ducking
+ * rules, trigger the scheduled playbacks, upload the SoundDatas loaded
+ * in the background and reload the files changed on disk. It should be
+ * called regularly, for example once per frame, in the task where ears is
+ * initialized.
+ *
+ * # Example
+ * ```Rust
+ * loop {
+ *     ears::update();
+ *     // do the rest of the frame
+ * }
+ * ```
+ */
+pub fn update() -> () {
+    check_openal_context!(());
+
+    let now = time::precise_time_ns();
+    let elapsed = match last_update.replace(Some(now)) {
+        Some(last) => Duration::nanoseconds((now - last) as i64),
+        None       => Duration::nanoseconds(0)
+    };
+    process(elapsed);
+}
+
+/**
+ * Update the internal state of ears after some time.
+ *
+ * # Argument
+ * * `elapsed` - The time since the last update
+ */
+#[doc(hidden)]
+pub fn process(elapsed: Duration) -> () {
+    clock::process();
+    loader::process();
+    hot_reload::process();
+    oneshot::recycle();
+    ducking::process(elapsed);
+}